@@ -0,0 +1,41 @@
+//! Lookups in the breached password list happen on every keystroke in the password field, so they
+//! have to stay well under a millisecond, even with a big imported list.
+
+use blop_core::user::auth::breach::{BreachedPasswords, HashList};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tempfile::tempdir;
+
+/// A cheap, deterministic stand-in for real truncated hashes.
+fn keys(count: u64) -> impl Iterator<Item = u64> {
+  (0..count).map(|x| x.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+fn bundled(c: &mut Criterion) {
+  let breached = BreachedPasswords::bundled();
+  // build the bundled list before measuring
+  breached.contains("");
+
+  c.bench_function("bundled hit", |b| {
+    b.iter(|| breached.contains(black_box("password")))
+  });
+  c.bench_function("bundled miss", |b| {
+    b.iter(|| breached.contains(black_box("tractor attic velvet")))
+  });
+}
+
+fn imported(c: &mut Criterion) {
+  let dir = tempdir().unwrap();
+  let list = HashList::from_hashes(keys(1_000_000));
+  BreachedPasswords::import(dir.path(), &list).unwrap();
+  let breached = BreachedPasswords::load(dir.path());
+
+  c.bench_function("imported miss, 1M hashes", |b| {
+    b.iter(|| breached.contains(black_box("tractor attic velvet")))
+  });
+  c.bench_function("hash list lookup, 1M hashes", |b| {
+    b.iter(|| list.contains_hash(black_box(keys(12346).last().unwrap())))
+  });
+}
+
+criterion_group!(benches, bundled, imported);
+criterion_main!(benches);
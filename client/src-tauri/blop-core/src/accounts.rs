@@ -0,0 +1,591 @@
+//! Several accounts that are logged in at once. Every account has its own data directory, API
+//! client, WebSocket connection and message history, and commands and events go to whichever one
+//! is active. Accounts are kept separately for each server profile, in
+//! `<data dir>/accounts/<profile>/<user ID>`.
+
+use std::{
+  collections::HashMap,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+  },
+};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+  api::{ApiClient, LoginResult, VerifyTokenResult},
+  events::{emit, AccountSwitchedEventPayload, AuthStateEventPayload, Event, EventSink},
+  history::MessageHistory,
+  user::{
+    auth::{
+      persist::{write_private, SessionFile},
+      secret::Secret,
+      totp::SubmitTotpResult,
+      AuthenticationState,
+    },
+    CreateUserResult, DeleteAccountResult,
+  },
+  websocket::WebSocketClient,
+  Config,
+};
+
+/// The name of the directory that accounts are kept in, inside of the data directory.
+const ACCOUNTS_DIR_NAME: &str = "accounts";
+
+/// The name of the file that lists a profile's accounts, inside of its directory.
+const REGISTRY_FILE_NAME: &str = "accounts.json";
+
+/// Identifies an account: the user that it's logged in as, on the server that it's logged in to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/accounts/account-key.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct AccountKey {
+  pub profile: String,
+  pub user_id: String,
+}
+
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/accounts/account-info.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+  pub profile: String,
+  pub user_id: String,
+  /// Commands and events go to this account.
+  pub active: bool,
+  /// It still has a token. It doesn't after the server rejected it, until it logs in again.
+  pub logged_in: bool,
+  /// It stays logged in after a restart.
+  pub auto_login: bool,
+}
+
+/// The result of switching to or removing an account.
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/accounts/account-action.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum AccountActionResult {
+  Success,
+  UnknownAccount,
+}
+
+/// The accounts of a profile, as they're saved to disk. Their sessions are saved in their own
+/// directories.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Registry {
+  /// The user ID of the active account.
+  active: Option<String>,
+  /// Every account, in the order that they were added.
+  accounts: Vec<StoredAccount>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredAccount {
+  user_id: String,
+  auto_login: bool,
+}
+
+/// Returns the directory that a profile's accounts are kept in.
+fn profile_dir(data_dir: &Path, profile: &str) -> PathBuf {
+  data_dir.join(ACCOUNTS_DIR_NAME).join(profile)
+}
+
+/// Returns the directory that an account's data is kept in.
+pub fn account_dir(data_dir: &Path, key: &AccountKey) -> PathBuf {
+  profile_dir(data_dir, &key.profile).join(&key.user_id)
+}
+
+/// Decides which events reach the sink: only the active account's.
+#[derive(Default)]
+struct EventRouter {
+  active: RwLock<Option<AccountKey>>,
+  /// Events are dropped until there is one.
+  sink: RwLock<Option<Arc<dyn EventSink>>>,
+}
+
+impl EventSink for EventRouter {
+  fn emit(&self, event: &str, payload: serde_json::Value) {
+    if let Some(sink) = &*self.sink.read().unwrap() {
+      sink.emit(event, payload);
+    }
+  }
+}
+
+/// The events of one account, which only get through while it's active.
+struct AccountEvents {
+  key: Option<AccountKey>,
+  router: Arc<EventRouter>,
+}
+
+impl EventSink for AccountEvents {
+  fn emit(&self, event: &str, payload: serde_json::Value) {
+    if *self.router.active.read().unwrap() == self.key {
+      self.router.emit(event, payload);
+    }
+  }
+}
+
+/// One account, or the signed out client that accounts are logged in with.
+pub struct Account {
+  /// `None` for the signed out client.
+  pub key: Option<AccountKey>,
+  pub api: ApiClient,
+  pub ws: Arc<WebSocketClient>,
+  pub history: Arc<MessageHistory>,
+  /// It stays logged in after a restart.
+  auto_login: AtomicBool,
+  /// Its WebSocket client was started.
+  connected: AtomicBool,
+}
+
+impl Account {
+  /// Creates an account that keeps its data in `data_dir`. Its WebSocket client isn't started.
+  fn new(
+    config: &Arc<Config>,
+    key: Option<AccountKey>,
+    data_dir: &Path,
+    router: &Arc<EventRouter>,
+  ) -> Account {
+    let auth = Arc::new(AuthenticationState::default());
+    let api = ApiClient::with_data_dir(config.clone(), auth.clone(), data_dir);
+    let history = Arc::new(MessageHistory::load(data_dir));
+
+    let events = AccountEvents {
+      key: key.clone(),
+      router: router.clone(),
+    };
+    let mut ws = WebSocketClient::new(config.clone(), Arc::new(events))
+      .with_profiles(api.profiles())
+      .with_contacts(api.contacts())
+      .with_keyring(api.keyring())
+      .with_history(history.clone());
+    // the signed out client's sessions only last until an account takes them over, and it would
+    // reconnect for each one
+    if key.is_some() {
+      ws = ws.with_auth(auth);
+    }
+
+    Account {
+      key,
+      api,
+      ws: Arc::new(ws),
+      history,
+      auto_login: AtomicBool::new(true),
+      connected: AtomicBool::new(false),
+    }
+  }
+
+  pub fn auto_login(&self) -> bool {
+    self.auto_login.load(Ordering::Relaxed)
+  }
+
+  /// Starts its WebSocket client, unless it already was.
+  fn connect(&self) {
+    if !self.connected.swap(true, Ordering::Relaxed) {
+      self.ws.start();
+    }
+  }
+}
+
+/// Every account of the configured profile, and which one is active.
+pub struct AccountManager {
+  config: Arc<Config>,
+  router: Arc<EventRouter>,
+  /// Logs in new accounts, and is what commands go to while no account is active.
+  signed_out: Arc<Account>,
+  /// In the order that they were added.
+  accounts: Mutex<Vec<Arc<Account>>>,
+  /// `start` was called, so new accounts connect right away.
+  started: Mutex<bool>,
+  /// Whether logins that are waiting for a code should stay logged in, keyed by challenge ID.
+  challenges: Mutex<HashMap<String, bool>>,
+}
+
+impl AccountManager {
+  /// Creates a manager without any accounts. Call `restore` to load the ones that were saved.
+  pub fn new(config: Arc<Config>) -> AccountManager {
+    let router = Arc::new(EventRouter::default());
+    let signed_out = Account::new(&config, None, &config.data_dir, &router);
+
+    AccountManager {
+      config,
+      router,
+      signed_out: Arc::new(signed_out),
+      accounts: Default::default(),
+      started: Default::default(),
+      challenges: Default::default(),
+    }
+  }
+
+  /// Loads the accounts that were saved, and makes the one that was active active again. A session
+  /// that was saved before there were several accounts becomes an account too.
+  pub async fn restore(&self) {
+    let registry: Registry = fs::read(self.registry_path())
+      .ok()
+      .and_then(|x| serde_json::from_slice(&x).ok())
+      .unwrap_or_default();
+
+    for stored in registry.accounts {
+      let key = self.key(stored.user_id);
+      let account = Account::new(
+        &self.config,
+        Some(key.clone()),
+        &account_dir(&self.config.data_dir, &key),
+        &self.router,
+      );
+
+      // its session was cleared, e.g. because it logged out while we weren't running
+      if !account.api.restore_session().await {
+        continue;
+      }
+
+      account
+        .auto_login
+        .store(stored.auto_login, Ordering::Relaxed);
+      self.accounts.lock().unwrap().push(Arc::new(account));
+    }
+
+    let active = registry.active.map(|x| self.key(x));
+    let active = match active {
+      Some(x) if self.get(&x).is_some() => Some(x),
+      _ => None,
+    };
+    *self.router.active.write().unwrap() = active;
+
+    self.adopt_signed_out(true).await;
+    self.save();
+  }
+
+  /// Logs out of the accounts that shouldn't stay logged in after a restart, and checks the token
+  /// of the active account if it's still there. If `start` was called, the rest connect before
+  /// that check. Meant to be called once, at startup, after `restore`.
+  pub async fn auto_login(&self) -> AuthStateEventPayload {
+    let accounts = self.accounts.lock().unwrap().clone();
+    for account in accounts.iter().filter(|x| !x.auto_login()) {
+      let key = account.key.as_ref().expect("accounts have keys");
+      self.take(key);
+      account.ws.stop().await;
+      account.api.end_session().await;
+    }
+
+    if *self.started.lock().unwrap() {
+      for account in self.accounts.lock().unwrap().iter() {
+        account.connect();
+      }
+    }
+
+    let active = self.router.active.read().unwrap().clone();
+    let (key, account) = match active.and_then(|x| Some((x.clone(), self.get(&x)?))) {
+      Some(x) => x,
+      None => {
+        *self.router.active.write().unwrap() = None;
+        self.save();
+        return AuthStateEventPayload::None;
+      }
+    };
+    self.save();
+
+    match account.api.verify_token().await {
+      Ok(VerifyTokenResult::Authorized) => (),
+      Ok(_) => return AuthStateEventPayload::Expired { account: key },
+      Err(_) => return AuthStateEventPayload::Offline { account: key },
+    }
+
+    // the directory has the username too, so the frontend doesn't need to ask for it
+    let users = account
+      .api
+      .resolve_users(std::slice::from_ref(&key.user_id))
+      .await
+      .map(|mut x| x.remove(&key.user_id).flatten());
+
+    match users {
+      Ok(Some(user)) => AuthStateEventPayload::Restored { account: key, user },
+      // the account is gone, even though its token isn't
+      Ok(None) => {
+        account.api.end_session().await;
+        AuthStateEventPayload::Expired { account: key }
+      }
+      Err(_) => AuthStateEventPayload::Offline { account: key },
+    }
+  }
+
+  /// Delivers events through `events` from now on, and connects every account, along with the
+  /// signed out client. Accounts that `restore` loads later connect in `auto_login`.
+  pub fn start(&self, events: Arc<dyn EventSink>) {
+    *self.router.sink.write().unwrap() = Some(events);
+    *self.started.lock().unwrap() = true;
+
+    self.signed_out.connect();
+    for account in self.accounts.lock().unwrap().iter() {
+      account.connect();
+    }
+  }
+
+  /// Delivers events through `events` from now on, like `start`, but only connects the active
+  /// account, or the signed out client if no account is active. Meant for short-lived clients that
+  /// don't switch accounts, like the CLI.
+  pub fn start_active(&self, events: Arc<dyn EventSink>) -> Arc<Account> {
+    *self.router.sink.write().unwrap() = Some(events);
+
+    let account = self.active();
+    account.connect();
+    account
+  }
+
+  /// Returns the account that commands go to, i.e. the active one, or the signed out client if no
+  /// account is active.
+  pub fn active(&self) -> Arc<Account> {
+    let active = self.router.active.read().unwrap().clone();
+    active
+      .and_then(|x| self.get(&x))
+      .unwrap_or_else(|| self.signed_out.clone())
+  }
+
+  /// Lists every account of the configured profile.
+  pub async fn list_accounts(&self) -> Vec<AccountInfo> {
+    let active = self.router.active.read().unwrap().clone();
+    let accounts = self.accounts.lock().unwrap().clone();
+
+    let mut infos = Vec::new();
+    for account in accounts {
+      let key = account.key.clone().expect("accounts have keys");
+      infos.push(AccountInfo {
+        active: active.as_ref() == Some(&key),
+        logged_in: account.api.auth().get_token().is_some(),
+        auto_login: account.auto_login(),
+        profile: key.profile,
+        user_id: key.user_id,
+      });
+    }
+
+    infos
+  }
+
+  /// Logs in, adding an account for whoever logged in and making it active. If they already had
+  /// an account, its session is replaced. Unless `auto_login` is set, it's logged out when we
+  /// restart.
+  pub async fn log_in(
+    &self,
+    username: String,
+    password: Secret,
+    auto_login: bool,
+  ) -> Result<LoginResult, String> {
+    let result = self.signed_out.api.log_in(username, password).await?;
+    match &result {
+      LoginResult::Authorized => self.adopt_signed_out(auto_login).await,
+      LoginResult::TwoFactorRequired { challenge_id } => {
+        let mut challenges = self.challenges.lock().unwrap();
+        challenges.insert(challenge_id.clone(), auto_login);
+      }
+      _ => (),
+    }
+
+    Ok(result)
+  }
+
+  /// Finishes a login that needed a code, adding the account like `log_in` would have.
+  pub async fn submit_totp(
+    &self,
+    challenge_id: String,
+    code: Secret,
+  ) -> Result<SubmitTotpResult, String> {
+    let result = self.signed_out.api.submit_totp(&challenge_id, code).await?;
+    if let SubmitTotpResult::WrongCode = result {
+      return Ok(result);
+    }
+
+    let auto_login = self.challenges.lock().unwrap().remove(&challenge_id);
+    if let SubmitTotpResult::Authorized = result {
+      self.adopt_signed_out(auto_login.unwrap_or(true)).await;
+    }
+
+    Ok(result)
+  }
+
+  /// Creates a user, adding an account for them and making it active.
+  pub async fn create_user(
+    &self,
+    username: String,
+    password: Secret,
+  ) -> Result<CreateUserResult, String> {
+    let result = self.signed_out.api.create_user(username, password).await?;
+    if let CreateUserResult::Success(_) = result {
+      self.adopt_signed_out(true).await;
+    }
+
+    Ok(result)
+  }
+
+  /// Makes commands and events go to the account of the user with the given ID.
+  pub fn switch_account(&self, key: &AccountKey) -> AccountActionResult {
+    if self.get(key).is_none() {
+      return AccountActionResult::UnknownAccount;
+    }
+
+    self.activate(Some(key.clone()));
+    AccountActionResult::Success
+  }
+
+  /// Sets whether an account stays logged in after a restart.
+  pub fn set_auto_login(&self, key: &AccountKey, enabled: bool) -> AccountActionResult {
+    let account = match self.get(key) {
+      Some(x) => x,
+      None => return AccountActionResult::UnknownAccount,
+    };
+
+    account.auto_login.store(enabled, Ordering::Relaxed);
+    self.save();
+    AccountActionResult::Success
+  }
+
+  /// Logs an account out, disconnects it and deletes its data, including its message history and
+  /// this device's keys. If it was active, no account is active anymore.
+  pub async fn remove_account(&self, key: &AccountKey) -> AccountActionResult {
+    let account = match self.take(key) {
+      Some(x) => x,
+      None => return AccountActionResult::UnknownAccount,
+    };
+
+    account.ws.stop().await;
+    account.api.forget_device().await;
+
+    let dir = account_dir(&self.config.data_dir, key);
+    match fs::remove_dir_all(&dir) {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => {
+        eprintln!("couldn't delete account data: {}", e)
+      }
+      _ => (),
+    }
+
+    if self.router.active.read().unwrap().as_ref() == Some(key) {
+      self.activate(None);
+    } else {
+      self.save();
+    }
+
+    AccountActionResult::Success
+  }
+
+  /// Deletes the active account's user, and then removes the account like `remove_account` would,
+  /// so that it doesn't come back as a logged out account after a restart.
+  pub async fn delete_account(&self, password: Secret) -> Result<DeleteAccountResult, String> {
+    let account = self.active();
+    let result = account.api.delete_account(password).await?;
+    if let (DeleteAccountResult::Success, Some(key)) = (&result, &account.key) {
+      self.remove_account(key).await;
+    }
+
+    Ok(result)
+  }
+}
+
+impl AccountManager {
+  fn key(&self, user_id: String) -> AccountKey {
+    AccountKey {
+      profile: self.config.profile.clone(),
+      user_id,
+    }
+  }
+
+  fn get(&self, key: &AccountKey) -> Option<Arc<Account>> {
+    let accounts = self.accounts.lock().unwrap();
+    accounts
+      .iter()
+      .find(|x| x.key.as_ref() == Some(key))
+      .cloned()
+  }
+
+  /// Removes an account from the list, without touching its data.
+  fn take(&self, key: &AccountKey) -> Option<Arc<Account>> {
+    let mut accounts = self.accounts.lock().unwrap();
+    let i = accounts.iter().position(|x| x.key.as_ref() == Some(key))?;
+    Some(accounts.remove(i))
+  }
+
+  /// Moves the signed out client's session, if it has one, to the account that it belongs to, and
+  /// makes that account active.
+  async fn adopt_signed_out(&self, auto_login: bool) {
+    let session = match self.signed_out.api.take_session().await {
+      Some(x) => x,
+      None => return,
+    };
+    let key = self.key(session.user_id.clone());
+    let dir = account_dir(&self.config.data_dir, &key);
+
+    // the new session is the same device, so only the old one's token goes
+    if let Some(old) = self.take(&key) {
+      old.ws.stop().await;
+      old.api.end_session().await;
+    }
+
+    if let Err(e) = SessionFile::new(&dir).save(&session) {
+      eprintln!("couldn't save session: {}", e);
+    }
+
+    let account = Account::new(&self.config, Some(key.clone()), &dir, &self.router);
+    account.auto_login.store(auto_login, Ordering::Relaxed);
+    account.api.restore_session().await;
+    if *self.started.lock().unwrap() {
+      account.connect();
+    }
+
+    self.accounts.lock().unwrap().push(Arc::new(account));
+    self.activate(Some(key));
+  }
+
+  /// Makes the account with the given key active, saves the registry and tells the sink.
+  fn activate(&self, key: Option<AccountKey>) {
+    *self.router.active.write().unwrap() = key.clone();
+    self.save();
+
+    let payload = AccountSwitchedEventPayload { account: key };
+    emit(&*self.router, Event::AccountSwitched(payload));
+  }
+
+  fn registry_path(&self) -> PathBuf {
+    profile_dir(&self.config.data_dir, &self.config.profile).join(REGISTRY_FILE_NAME)
+  }
+
+  fn save(&self) {
+    let registry = Registry {
+      active: self
+        .router
+        .active
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|x| x.user_id.clone()),
+      accounts: self
+        .accounts
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|x| {
+          let user_id = x.key.as_ref()?.user_id.clone();
+          Some(StoredAccount {
+            user_id,
+            auto_login: x.auto_login(),
+          })
+        })
+        .collect(),
+    };
+
+    let path = self.registry_path();
+    let write = || -> io::Result<()> {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+
+      write_private(&path, &serde_json::to_vec(&registry)?)
+    };
+
+    // the sessions are still there, but they won't be found after a restart
+    if let Err(e) = write() {
+      eprintln!("couldn't save accounts: {}", e);
+    }
+  }
+}
@@ -0,0 +1,1062 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use futures::lock::Mutex;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use ts_rs::TS;
+
+use crate::{
+  clock::unix_millis,
+  common::BadRequestResponseBody,
+  e2e::{
+    keyring::Keyring,
+    keys::{cross_sign_device, fetch_devices, register_device, remove_device, Identity},
+    safety_number, ListDevicesResult, SafetyNumberResult, VerifyDeviceResult,
+  },
+  user::{
+    auth::{
+      breach::BreachedPasswords,
+      password::{PasswordPolicy, PasswordValidation},
+      persist::{IdentityStore, PersistedSession, SessionFile},
+      secret::Secret,
+      throttle::{parse_retry_after, LoginThrottle},
+      totp::{
+        self, enable_totp, submit_totp, ConfirmTotpResult, PendingEnrollment, SubmitTotpResult,
+        TotpChallengeResponse, TotpEnrollmentResult,
+      },
+      username::{
+        availability::{Availability, AvailabilityChecker},
+        user_exists, validate_username, UsernameValidation,
+      },
+      AuthenticationState,
+    },
+    change_password, change_username,
+    contacts::{
+      contact_action, fetch_contacts, send_friend_request, Contact, ContactActionResult,
+      ContactStatus, ContactStore, FriendRequestResult, ListContactsResult,
+    },
+    create_user, delete_account,
+    directory::UserDirectory,
+    profile::{
+      fetch_profile, update_profile, Profile, ProfileCache, ProfileUpdate, UpdateProfileResult,
+    },
+    AuthenticationSuccessResponse, ChangePasswordResult, ChangeUsernameResult, CreateUserResult,
+    Credentials, DeleteAccountResult, User,
+  },
+  Config,
+};
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/verify-token-result.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum VerifyTokenResult {
+  NotLoggedIn,
+  Authorized,
+  Expired,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/login-result.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum LoginResult {
+  Authorized,
+  Unauthorized,
+  UserDoesNotExist,
+  /// The password was right, but the login has to be finished with `submit_totp`.
+  TwoFactorRequired {
+    #[serde(rename = "challengeId")]
+    challenge_id: String,
+  },
+  /// Too many logins failed, either here or according to the server, so the next one has to wait.
+  RateLimited {
+    #[serde(rename = "retryAfterMs")]
+    #[ts(type = "number")]
+    retry_after_ms: u64,
+  },
+}
+
+#[derive(Serialize, TS)]
+#[ts(export, export_to = "../../src/types/user/info.d.ts")]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MyInfoResult {
+  Success(Profile),
+  NotLoggedIn,
+}
+
+/// A client for the Blop REST API that keeps track of the logged in user.
+pub struct ApiClient {
+  config: Arc<Config>,
+  auth: Arc<AuthenticationState>,
+  session_file: SessionFile,
+  /// This device's keys for each user, which outlive their sessions.
+  identities: IdentityStore,
+  /// The server's password policy, once it has been fetched.
+  password_policy: Mutex<Option<PasswordPolicy>>,
+  /// The known compromised passwords, including any imported into the data directory.
+  breached: BreachedPasswords,
+  availability: AvailabilityChecker,
+  /// Shared with the WebSocket client, which invalidates profiles when they change.
+  profiles: Arc<ProfileCache>,
+  directory: Arc<UserDirectory>,
+  /// Shared with the WebSocket client, which filters out blocked users.
+  contacts: Arc<ContactStore>,
+  /// Shared with the WebSocket client, which encrypts and decrypts direct messages.
+  keyring: Arc<Keyring>,
+  /// Two-factor authentication that was set up, but not confirmed yet.
+  totp_enrollment: Mutex<Option<PendingEnrollment>>,
+  login_throttle: LoginThrottle,
+  http: Client,
+}
+
+impl ApiClient {
+  /// Creates a client for the API at `config.api_url`.
+  pub fn new(config: Arc<Config>, auth: Arc<AuthenticationState>) -> ApiClient {
+    let data_dir = config.data_dir.clone();
+    ApiClient::with_data_dir(config, auth, &data_dir)
+  }
+
+  /// Creates a client like `new`, but that keeps the session, contacts and known devices in
+  /// `data_dir`, e.g. because it belongs to one of several accounts.
+  pub fn with_data_dir(
+    config: Arc<Config>,
+    auth: Arc<AuthenticationState>,
+    data_dir: &Path,
+  ) -> ApiClient {
+    let http = Client::new();
+    let directory = UserDirectory::new(
+      http.clone(),
+      config.get_api_url("/user/resolve"),
+      config.directory_capacity,
+      config.directory_ttl,
+      config.directory_batch_window,
+    );
+    let keyring = Keyring::new(http.clone(), config.get_api_url("/user/keys"), data_dir);
+
+    ApiClient {
+      session_file: SessionFile::new(data_dir),
+      identities: IdentityStore::new(&config.data_dir, &config.profile),
+      contacts: Arc::new(ContactStore::load(data_dir)),
+      // imported lists are shared by every account
+      breached: BreachedPasswords::load(&config.data_dir),
+      availability: AvailabilityChecker::new(config.availability_debounce, config.availability_ttl),
+      login_throttle: LoginThrottle::new(
+        config.login_free_attempts,
+        config.login_cooldown,
+        config.login_max_cooldown,
+      ),
+      config,
+      auth,
+      password_policy: Mutex::from(None),
+      profiles: Default::default(),
+      directory: Arc::new(directory),
+      keyring: Arc::new(keyring),
+      totp_enrollment: Mutex::from(None),
+      http,
+    }
+  }
+
+  /// Returns the state of the logged in user.
+  pub fn auth(&self) -> &AuthenticationState {
+    &self.auth
+  }
+
+  /// Returns the cache of fetched profiles.
+  pub fn profiles(&self) -> Arc<ProfileCache> {
+    self.profiles.clone()
+  }
+
+  /// Returns the directory that resolves user IDs.
+  pub fn directory(&self) -> Arc<UserDirectory> {
+    self.directory.clone()
+  }
+
+  /// Returns the logged in user's contacts, as they were last seen.
+  pub fn contacts(&self) -> Arc<ContactStore> {
+    self.contacts.clone()
+  }
+
+  /// Returns our identity and the devices of other users.
+  pub fn keyring(&self) -> Arc<Keyring> {
+    self.keyring.clone()
+  }
+
+  /// Logs in with the session that was saved to disk, if there is one. The token isn't verified.
+  /// Returns `true` if a session was restored.
+  pub async fn restore_session(&self) -> bool {
+    let mut session = match self.session_file.load() {
+      Some(x) => x,
+      None => return false,
+    };
+
+    // sessions that were saved before there were device keys get them now
+    let register = session.identity.is_none();
+    let identity = match &session.identity {
+      Some(x) => {
+        // and ones that were saved before keys outlived sessions keep them from now on
+        if self.identities.load(&session.user_id).is_none() {
+          self.save_identity(x);
+        }
+        x.clone()
+      }
+      None => {
+        let identity = self.identity_for(&session.user_id);
+        session.identity = Some(identity.clone());
+        if let Err(e) = self.session_file.save(&session) {
+          eprintln!("couldn't save session: {}", e);
+        }
+        identity
+      }
+    };
+
+    self.keyring.set_identity(Some(identity));
+    self.auth.login(session.token, session.user_id);
+
+    if register {
+      self.register_device().await;
+    }
+
+    true
+  }
+
+  /// Sets the token and user ID, and saves them to disk along with this device's keys. The keys are
+  /// only generated if the user never logged in on this device before.
+  async fn start_session(&self, token: Secret, user_id: String) {
+    let identity = self.identity_for(&user_id);
+    let session = PersistedSession {
+      token: token.clone(),
+      user_id: user_id.clone(),
+      identity: Some(identity.clone()),
+    };
+
+    // we're still logged in for now, even if it won't survive a restart
+    if let Err(e) = self.session_file.save(&session) {
+      eprintln!("couldn't save session: {}", e);
+    }
+
+    // they might belong to someone else
+    self.contacts.clear();
+
+    self.keyring.set_identity(Some(identity));
+    self.auth.login(token, user_id);
+
+    self.register_device().await;
+  }
+
+  /// Returns this device's keys for the user with the given ID, generating and saving them if it
+  /// doesn't have any yet.
+  fn identity_for(&self, user_id: &str) -> Identity {
+    if let Some(x) = self.identities.load(user_id) {
+      return x;
+    }
+
+    let identity = Identity::generate(user_id);
+    self.save_identity(&identity);
+    identity
+  }
+
+  fn save_identity(&self, identity: &Identity) {
+    // we can still log in, but we'll be a new device next time
+    if let Err(e) = self.identities.save(identity) {
+      eprintln!("couldn't save device keys: {}", e);
+    }
+  }
+
+  /// Publishes this device's keys, so that others can send us direct messages. Keys that the server
+  /// already has aren't published again, since that would drop the signatures of our other
+  /// devices. Failures are only logged, since they don't stop us from using the rest of the app.
+  async fn register_device(&self) {
+    let (token, identity) = match (self.auth.get_token(), self.keyring.identity()) {
+      (Some(token), Some(identity)) => (token, identity),
+      _ => return,
+    };
+
+    let url = self.config.get_api_url("/user/keys");
+    let device = identity.device();
+    if let Ok(devices) = fetch_devices(&self.http, &url, &identity.user_id).await {
+      let published = devices.iter().any(|x| {
+        x.device_id == device.device_id
+          && x.public_key == device.public_key
+          && x.signing_key == device.signing_key
+      });
+      if published {
+        return;
+      }
+    }
+
+    if let Err(e) = register_device(&self.http, &url, &token, &identity).await {
+      eprintln!("couldn't register device: {}", e);
+    }
+  }
+
+  /// Clears the token and user ID, and forgets the session that was saved to disk. This device
+  /// keeps its keys and the devices that it has seen, so that logging in again as the same user is
+  /// the same device to everyone else.
+  pub async fn end_session(&self) {
+    self.forget_session().await;
+  }
+
+  /// Removes this device from the server, deletes its keys and the devices that it has seen, and
+  /// ends the session. For when the account leaves this device for good, e.g. because it was
+  /// removed or deleted.
+  pub async fn forget_device(&self) {
+    // nobody should encrypt for this device anymore. the token might not be valid anymore, which is
+    // fine
+    if let (Some(token), Some(identity)) = (self.auth.get_token(), self.keyring.identity()) {
+      let url = self.config.get_api_url("/user/keys/remove");
+      if let Err(e) = remove_device(&self.http, &url, &token, &identity.device_id).await {
+        eprintln!("couldn't remove device: {}", e);
+      }
+
+      if let Err(e) = self.identities.remove(&identity.user_id) {
+        eprintln!("couldn't delete device keys: {}", e);
+      }
+    }
+
+    self.keyring.forget_devices();
+    self.forget_session().await;
+  }
+
+  /// Logs out and returns the session that was saved to disk, so that someone else can take it
+  /// over, e.g. the account that it belongs to.
+  pub async fn take_session(&self) -> Option<PersistedSession> {
+    let session = self.session_file.load();
+    self.forget_session().await;
+    session
+  }
+
+  /// Clears the token and user ID, and forgets the session that was saved to disk.
+  async fn forget_session(&self) {
+    if let Err(e) = self.session_file.clear() {
+      eprintln!("couldn't clear session: {}", e);
+    }
+
+    self.contacts.clear();
+    self.keyring.set_identity(None);
+    *self.totp_enrollment.lock().await = None;
+
+    self.auth.logout();
+  }
+
+  /// Returns the safety number of our conversation with the user with the given ID. Both of our
+  /// devices are always fetched again, so that the number reflects the keys that messages are
+  /// encrypted with.
+  pub async fn safety_number(&self, user_id: &str) -> Result<SafetyNumberResult, String> {
+    let identity = match self.keyring.identity() {
+      Some(x) => x,
+      None => return Ok(SafetyNumberResult::NotLoggedIn),
+    };
+
+    let mut ours = self.keyring.refresh(&identity.user_id).await?;
+    // our device might not have been registered yet
+    if !ours.iter().any(|x| x.device_id == identity.device_id) {
+      ours.push(identity.device());
+    }
+
+    let theirs = self.keyring.refresh(user_id).await?;
+    if theirs.is_empty() {
+      return Ok(SafetyNumberResult::NoKey);
+    }
+
+    Ok(SafetyNumberResult::Success {
+      safety_number: safety_number((&identity.user_id, &ours), (user_id, &theirs)),
+    })
+  }
+
+  /// Lists the devices of the user with the given ID, including their fingerprints.
+  pub async fn list_devices(&self, user_id: &str) -> Result<ListDevicesResult, String> {
+    if self.keyring.identity().is_none() {
+      return Ok(ListDevicesResult::NotLoggedIn);
+    }
+
+    let devices = self.keyring.list_devices(user_id).await?;
+    Ok(ListDevicesResult::Success { devices })
+  }
+
+  /// Compares the fingerprint of one of a user's devices with the one that the device shows. If
+  /// they match and it's one of our own devices, this device vouches for it, so that our contacts
+  /// don't get warned about it.
+  pub async fn verify_device(
+    &self,
+    user_id: &str,
+    device_id: &str,
+    fingerprint: &str,
+  ) -> Result<VerifyDeviceResult, String> {
+    let (result, device) = self
+      .keyring
+      .verify_device(user_id, device_id, fingerprint)
+      .await?;
+
+    let (device, identity, token) = match (device, self.keyring.identity(), self.auth.get_token()) {
+      (Some(device), Some(identity), Some(token)) if identity.user_id == user_id => {
+        (device, identity, token)
+      }
+      _ => return Ok(result),
+    };
+
+    if device.device_id == identity.device_id {
+      return Ok(result);
+    }
+
+    let url = self.config.get_api_url("/user/keys/sign");
+    match cross_sign_device(&self.http, &url, &token, &identity, &device).await? {
+      true => Ok(result),
+      false => {
+        self.end_session().await;
+        Ok(VerifyDeviceResult::NotLoggedIn)
+      }
+    }
+  }
+
+  /// Returns the password policy from the config, or else the server's. If the server's policy
+  /// can't be fetched, the default policy is returned, and it's fetched again next time.
+  pub async fn password_policy(&self) -> PasswordPolicy {
+    if let Some(x) = &self.config.password_policy {
+      return x.clone();
+    }
+
+    let mut cached = self.password_policy.lock().await;
+    if let Some(x) = &*cached {
+      return x.clone();
+    }
+
+    match self.fetch_password_policy().await {
+      Ok(x) => {
+        *cached = Some(x.clone());
+        x
+      }
+      Err(e) => {
+        eprintln!("couldn't fetch password policy: {}", e);
+        PasswordPolicy::default()
+      }
+    }
+  }
+
+  async fn fetch_password_policy(&self) -> Result<PasswordPolicy, String> {
+    let resp = self
+      .http
+      .get(self.config.get_api_url("/auth/policy"))
+      .send()
+      .await
+      .map_err(|e| e.to_string())?;
+
+    match resp.status() {
+      StatusCode::OK => resp
+        .json()
+        .await
+        .map_err(|_| "invalid server response".into()),
+      other => Err(format!("unexpected status {}", other)),
+    }
+  }
+
+  /// Returns the validation for `password` under the current password policy.
+  pub async fn validate_password(&self, password: &str) -> PasswordValidation {
+    self
+      .password_policy()
+      .await
+      .validate(password, &self.breached)
+  }
+
+  /// Returns the validation for `username` under the configured username policy.
+  pub fn validate_username(&self, username: &str) -> UsernameValidation {
+    validate_username(username, &self.config.username_policy)
+  }
+
+  pub async fn create_user(
+    &self,
+    username: String,
+    password: Secret,
+  ) -> Result<CreateUserResult, String> {
+    // can't log in while logged in
+    if self.auth.is_logged_in() {
+      return Ok(CreateUserResult::AlreadyLoggedIn);
+    }
+
+    let create_user_result = create_user(
+      &self.http,
+      &self.config.get_api_url("/auth/create"),
+      username,
+      password,
+      &self.password_policy().await,
+      &self.breached,
+      &self.config.username_policy,
+    )
+    .await;
+
+    if let Ok(CreateUserResult::Success(x)) = &create_user_result {
+      // the username we checked isn't available anymore
+      self.availability.clear();
+      self.start_session(x.token.clone(), x.id.clone()).await;
+    }
+
+    create_user_result
+  }
+
+  /// Changes the logged in user's password, and logs out if it worked. The old password has to be
+  /// given again.
+  pub async fn change_password(
+    &self,
+    old_password: Secret,
+    new_password: Secret,
+  ) -> Result<ChangePasswordResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(ChangePasswordResult::NotLoggedIn),
+    };
+
+    let result = change_password(
+      &self.http,
+      &self.config.get_api_url("/auth/password"),
+      &token,
+      old_password,
+      new_password,
+      &self.password_policy().await,
+      &self.breached,
+    )
+    .await;
+
+    // if the token expired, it's no use keeping it around. if the password changed, whoever
+    // changed it should prove they know it
+    if let Ok(ChangePasswordResult::Success | ChangePasswordResult::NotLoggedIn) = result {
+      self.end_session().await;
+    }
+
+    result
+  }
+
+  /// Changes the logged in user's username.
+  pub async fn change_username(&self, username: String) -> Result<ChangeUsernameResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(ChangeUsernameResult::NotLoggedIn),
+    };
+
+    let result = change_username(
+      &self.http,
+      &self.config.get_api_url("/auth/username"),
+      &token,
+      username,
+      &self.config.username_policy,
+    )
+    .await;
+
+    match result {
+      Ok(ChangeUsernameResult::NotLoggedIn) => self.end_session().await,
+      // both the old and the new username changed availability
+      Ok(ChangeUsernameResult::Success) => {
+        self.availability.clear();
+        if let Some(id) = self.auth.get_user_id() {
+          self.directory.invalidate(&id);
+        }
+      }
+      _ => (),
+    }
+
+    result
+  }
+
+  /// Deletes the logged in user's account, and logs out if it worked. The password has to be
+  /// given again.
+  pub async fn delete_account(&self, password: Secret) -> Result<DeleteAccountResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(DeleteAccountResult::NotLoggedIn),
+    };
+
+    let result = delete_account(
+      &self.http,
+      &self.config.get_api_url("/auth/delete"),
+      &token,
+      password,
+    )
+    .await;
+
+    match result {
+      Ok(DeleteAccountResult::Success) => {
+        self.availability.clear();
+        // the account is gone, and this device along with it
+        self.forget_device().await;
+      }
+      Ok(DeleteAccountResult::NotLoggedIn) => self.end_session().await,
+      _ => (),
+    }
+
+    result
+  }
+
+  pub async fn user_exists(&self, username: &str) -> Result<bool, String> {
+    user_exists(
+      &self.http,
+      &self.config.get_api_url("/user/getid"),
+      username,
+      &self.config.username_policy,
+    )
+    .await
+  }
+
+  /// Returns whether `username` can be taken. This is meant to be called on every keystroke:
+  /// checks are debounced, a new check cancels the last one, and answers are cached.
+  pub async fn check_username(&self, username: &str) -> Availability {
+    let validation = self.validate_username(username);
+    self
+      .availability
+      .check(validation, || self.user_exists(username))
+      .await
+  }
+
+  /// Resolves user IDs to users, mapping IDs that don't belong to anyone to `None`. Lookups are
+  /// cached, and batched with any others that happen at about the same time.
+  pub async fn resolve_users(
+    &self,
+    ids: &[String],
+  ) -> Result<HashMap<String, Option<User>>, String> {
+    self.directory.resolve(ids).await
+  }
+
+  /// Returns the logged in user's friends, friend requests and blocked users. If the server can't
+  /// be reached, the contacts that were seen last are returned instead.
+  pub async fn list_contacts(&self) -> Result<ListContactsResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(ListContactsResult::NotLoggedIn),
+    };
+
+    match fetch_contacts(
+      &self.http,
+      &self.config.get_api_url("/user/contacts"),
+      &token,
+    )
+    .await
+    {
+      Ok(Some(contacts)) => {
+        self.contacts.replace(contacts.clone());
+        Ok(ListContactsResult::Success {
+          contacts,
+          offline: false,
+        })
+      }
+      Ok(None) => {
+        self.end_session().await;
+        Ok(ListContactsResult::NotLoggedIn)
+      }
+      Err(e) => {
+        eprintln!("couldn't fetch contacts: {}", e);
+        Ok(ListContactsResult::Success {
+          contacts: self.contacts.list(),
+          offline: true,
+        })
+      }
+    }
+  }
+
+  /// Records that the user with the given ID is now a contact with the given status.
+  async fn set_contact(&self, user_id: &str, status: ContactStatus) {
+    let username = match self.contacts.get(user_id) {
+      Some(x) => x.username,
+      None => match self.directory.resolve(&[user_id.into()]).await {
+        Ok(mut x) => match x.remove(user_id).flatten() {
+          Some(x) => x.username,
+          None => return,
+        },
+        // it'll show up the next time contacts are listed
+        Err(_) => return,
+      },
+    };
+
+    self.contacts.set(Contact {
+      id: user_id.into(),
+      username,
+      status,
+    });
+  }
+
+  /// Sends a friend request to the user with the given ID. If they already sent us one, this
+  /// accepts it instead.
+  pub async fn send_friend_request(&self, user_id: &str) -> Result<FriendRequestResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(FriendRequestResult::NotLoggedIn),
+    };
+
+    let result = send_friend_request(
+      &self.http,
+      &self.config.get_api_url("/user/friends/request"),
+      &token,
+      user_id,
+    )
+    .await;
+
+    match result {
+      Ok(FriendRequestResult::Sent) => self.set_contact(user_id, ContactStatus::Outgoing).await,
+      Ok(FriendRequestResult::Accepted) => self.set_contact(user_id, ContactStatus::Friend).await,
+      Ok(FriendRequestResult::NotLoggedIn) => self.end_session().await,
+      _ => (),
+    }
+
+    result
+  }
+
+  /// Posts to one of the contact endpoints, and logs out if the token isn't valid.
+  async fn contact_action(
+    &self,
+    endpoint: &str,
+    body: serde_json::Value,
+  ) -> Result<ContactActionResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(ContactActionResult::NotLoggedIn),
+    };
+
+    let result = contact_action(&self.http, &self.config.get_api_url(endpoint), &token, body).await;
+
+    if let Ok(ContactActionResult::NotLoggedIn) = result {
+      self.end_session().await;
+    }
+
+    result
+  }
+
+  /// Accepts or declines the friend request from the user with the given ID.
+  pub async fn respond_friend_request(
+    &self,
+    user_id: &str,
+    accept: bool,
+  ) -> Result<ContactActionResult, String> {
+    let body = json!({
+      "userId": user_id,
+      "accept": accept,
+    });
+    let result = self.contact_action("/user/friends/respond", body).await;
+
+    if let Ok(ContactActionResult::Success) = result {
+      if accept {
+        self.set_contact(user_id, ContactStatus::Friend).await;
+      } else {
+        self.contacts.remove(user_id);
+      }
+    }
+
+    result
+  }
+
+  /// Removes the user with the given ID from our friends, or cancels our request to them.
+  pub async fn remove_friend(&self, user_id: &str) -> Result<ContactActionResult, String> {
+    let body = json!({
+      "userId": user_id,
+    });
+    let result = self.contact_action("/user/friends/remove", body).await;
+
+    if let Ok(ContactActionResult::Success) = result {
+      self.contacts.remove(user_id);
+    }
+
+    result
+  }
+
+  /// Blocks the user with the given ID. This also ends any friendship or request between us.
+  pub async fn block_user(&self, user_id: &str) -> Result<ContactActionResult, String> {
+    let body = json!({
+      "userId": user_id,
+    });
+    let result = self.contact_action("/user/block", body).await;
+
+    if let Ok(ContactActionResult::Success) = result {
+      self.set_contact(user_id, ContactStatus::Blocked).await;
+    }
+
+    result
+  }
+
+  pub async fn unblock_user(&self, user_id: &str) -> Result<ContactActionResult, String> {
+    let body = json!({
+      "userId": user_id,
+    });
+    let result = self.contact_action("/user/unblock", body).await;
+
+    if let Ok(ContactActionResult::Success) = result {
+      self.contacts.remove(user_id);
+    }
+
+    result
+  }
+
+  /// Returns the profile of the user with the given ID, or `None` if there's no such user.
+  /// Profiles are cached until the server says they changed.
+  pub async fn get_profile(&self, user_id: &str) -> Result<Option<Profile>, String> {
+    if let Some(x) = self.profiles.get(user_id) {
+      return Ok(Some(x));
+    }
+
+    let profile = fetch_profile(
+      &self.http,
+      &self.config.get_api_url("/user/profile"),
+      user_id,
+    )
+    .await?;
+
+    if let Some(x) = &profile {
+      self.profiles.insert(x.clone());
+    }
+
+    Ok(profile)
+  }
+
+  /// Replaces the logged in user's profile.
+  pub async fn update_profile(&self, update: ProfileUpdate) -> Result<UpdateProfileResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(UpdateProfileResult::NotLoggedIn),
+    };
+
+    let result = update_profile(
+      &self.http,
+      &self.config.get_api_url("/user/profile"),
+      &token,
+      &update,
+    )
+    .await;
+
+    match &result {
+      Ok(UpdateProfileResult::Success(x)) => self.profiles.insert(x.clone()),
+      Ok(UpdateProfileResult::NotLoggedIn) => self.end_session().await,
+      _ => (),
+    }
+
+    result
+  }
+
+  pub async fn verify_token(&self) -> Result<VerifyTokenResult, String> {
+    let maybe_token = self.auth.get_token();
+
+    match maybe_token {
+      None => Ok(VerifyTokenResult::NotLoggedIn),
+      Some(token) => {
+        match self
+          .http
+          .get(self.config.get_api_url("/auth/verify"))
+          .bearer_auth(token.expose())
+          .send()
+          .await
+        {
+          Err(_) => Err("couldn't verify token".into()),
+          Ok(x) => match x.status() {
+            StatusCode::OK => Ok(VerifyTokenResult::Authorized),
+            StatusCode::BAD_REQUEST => Err("malformed token".into()),
+            StatusCode::UNAUTHORIZED => {
+              self.end_session().await;
+              Ok(VerifyTokenResult::Expired)
+            }
+            _ => Err("invalid server response".into()),
+          },
+        }
+      }
+    }
+  }
+
+  pub async fn log_in(&self, username: String, password: Secret) -> Result<LoginResult, String> {
+    if let Some(x) = self.login_throttle.retry_after(&username) {
+      return Ok(LoginResult::RateLimited {
+        retry_after_ms: x.as_millis() as u64,
+      });
+    }
+
+    let request_body = Credentials {
+      username: &username,
+      password: password.expose(),
+    };
+
+    match self
+      .http
+      .get(self.config.get_api_url("/auth/login"))
+      .json(&request_body)
+      .send()
+      .await
+    {
+      // we couldn't connect
+      Err(e) => Err(format!("couldn't log in! {}", e)),
+      Ok(x) => match x.status() {
+        StatusCode::OK => {
+          // parse response body
+          let body: AuthenticationSuccessResponse = match x.json().await {
+            Err(_) => return Err("invalid server response (c5d3)".into()),
+            Ok(x) => x,
+          };
+
+          // update token and ID
+          self.start_session(body.token, body.id).await;
+          self.login_throttle.clear(&username);
+
+          Ok(LoginResult::Authorized)
+        }
+        // the password was right, but a code is needed too
+        StatusCode::ACCEPTED => match x.json::<TotpChallengeResponse>().await {
+          Ok(x) => {
+            self.login_throttle.clear(&username);
+            Ok(LoginResult::TwoFactorRequired {
+              challenge_id: x.challenge_id,
+            })
+          }
+          Err(_) => Err("invalid server response".into()),
+        },
+        // invalid credentials
+        StatusCode::UNAUTHORIZED => {
+          self.login_throttle.fail(&username);
+          Ok(LoginResult::Unauthorized)
+        }
+        StatusCode::TOO_MANY_REQUESTS => {
+          // without a Retry-After, fall back to our own cooldown, even if we'd have allowed more
+          let delay = match parse_retry_after(&x) {
+            Some(x) => x,
+            None => self
+              .login_throttle
+              .fail(&username)
+              .max(self.config.login_cooldown),
+          };
+          self.login_throttle.lock(&username, delay);
+
+          Ok(LoginResult::RateLimited {
+            retry_after_ms: delay.as_millis() as u64,
+          })
+        }
+        StatusCode::BAD_REQUEST => {
+          // parse the bad request response
+          // we expect a field that contains the specific type
+          let body: BadRequestResponseBody = match x.json().await {
+            Err(_) => return Err("invalid server response (1908)".into()),
+            Ok(x) => x,
+          };
+
+          // match expected types
+          match body.typ.as_str() {
+            "USER" => {
+              self.login_throttle.fail(&username);
+              Ok(LoginResult::UserDoesNotExist)
+            }
+            other => Err(format!("invalid server response (7c63): {}", other)),
+          }
+        }
+        other => Err(format!("unexpected status {}", other)),
+      },
+    }
+  }
+
+  /// Finishes a login that returned `TwoFactorRequired`, with a code from an authenticator app or
+  /// one of the recovery codes.
+  pub async fn submit_totp(
+    &self,
+    challenge_id: &str,
+    code: Secret,
+  ) -> Result<SubmitTotpResult, String> {
+    let url = self.config.get_api_url("/auth/login/totp");
+    match submit_totp(&self.http, &url, challenge_id, &code).await? {
+      Ok(x) => {
+        self.start_session(x.token, x.id).await;
+        Ok(SubmitTotpResult::Authorized)
+      }
+      Err(x) => Ok(x),
+    }
+  }
+
+  /// Generates a secret and recovery codes for two-factor authentication. It isn't turned on until
+  /// a code from the secret is confirmed with `confirm_totp_enrollment`. Starting again replaces
+  /// them.
+  pub async fn begin_totp_enrollment(&self) -> Result<TotpEnrollmentResult, String> {
+    let user_id = match self.auth.get_user_id() {
+      Some(x) => x,
+      None => return Ok(TotpEnrollmentResult::NotLoggedIn),
+    };
+
+    // authenticator apps show it next to the code, and usernames are easier to recognize
+    let account = match self
+      .directory
+      .resolve(std::slice::from_ref(&user_id))
+      .await?
+      .remove(&user_id)
+    {
+      Some(Some(user)) => user.username,
+      _ => user_id,
+    };
+
+    let enrollment = PendingEnrollment {
+      secret: totp::generate_secret(),
+      recovery_codes: totp::generate_recovery_codes(),
+    };
+    let result = TotpEnrollmentResult::Success {
+      secret: totp::base32_encode(&enrollment.secret),
+      uri: totp::otpauth_uri(&account, &enrollment.secret),
+      recovery_codes: enrollment
+        .recovery_codes
+        .iter()
+        .map(|x| x.expose().into())
+        .collect(),
+    };
+
+    *self.totp_enrollment.lock().await = Some(enrollment);
+    Ok(result)
+  }
+
+  /// Turns on two-factor authentication, if `code` is right for the secret from
+  /// `begin_totp_enrollment`.
+  pub async fn confirm_totp_enrollment(&self, code: Secret) -> Result<ConfirmTotpResult, String> {
+    let token = match self.auth.get_token() {
+      Some(x) => x,
+      None => return Ok(ConfirmTotpResult::NotLoggedIn),
+    };
+
+    let mut enrollment = self.totp_enrollment.lock().await;
+    let pending = match &*enrollment {
+      Some(x) => x,
+      None => return Ok(ConfirmTotpResult::NotStarted),
+    };
+
+    // there's no need to bother the server with a code that we know is wrong
+    if !totp::verify(&pending.secret, code.expose(), unix_millis() / 1000) {
+      return Ok(ConfirmTotpResult::WrongCode);
+    }
+
+    let url = self.config.get_api_url("/auth/totp/enable");
+    let result = enable_totp(&self.http, &url, &token, pending, &code).await;
+
+    match result {
+      Ok(ConfirmTotpResult::Enabled | ConfirmTotpResult::AlreadyEnabled) => *enrollment = None,
+      Ok(ConfirmTotpResult::NotLoggedIn) => {
+        drop(enrollment);
+        self.end_session().await;
+      }
+      _ => (),
+    }
+
+    result
+  }
+
+  pub async fn my_info(&self) -> Result<MyInfoResult, String> {
+    let token = match self.auth.get_token() {
+      None => {
+        self.end_session().await;
+        return Ok(MyInfoResult::NotLoggedIn);
+      }
+      Some(x) => x,
+    };
+
+    let resp = self
+      .http
+      .get(self.config.get_api_url("/user/me"))
+      .bearer_auth(token.expose())
+      .send()
+      .await
+      .map_err(|e| e.to_string())?;
+
+    match resp.status() {
+      StatusCode::OK => {
+        let body: Profile = match resp.json().await {
+          Err(_) => return Err("invalid server response (c5d3)".into()),
+          Ok(x) => x,
+        };
+
+        self.profiles.insert(body.clone());
+        Ok(MyInfoResult::Success(body))
+      }
+      other => Err(format!("authorization error {}", other)),
+    }
+  }
+}
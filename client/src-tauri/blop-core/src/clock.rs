@@ -0,0 +1,94 @@
+use std::{
+  collections::VecDeque,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The number of samples that the offset is estimated from.
+const MAX_SAMPLES: usize = 8;
+
+/// Returns the current time in milliseconds since the Unix epoch.
+pub fn unix_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|x| x.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+/// A single measurement of the server's clock, taken from a ping/pong exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+  /// How far the server's clock is ahead of ours, in milliseconds.
+  pub offset: i64,
+  /// The round trip time of the exchange, in milliseconds.
+  pub delay: u64,
+}
+
+impl ClockSample {
+  /// Creates a sample from the time we sent the ping, the server's time when it sent the pong,
+  /// and the time we received the pong. All times are in milliseconds since the Unix epoch.
+  ///
+  /// Like NTP, this assumes that the ping and the pong took the same amount of time to arrive.
+  pub fn new(sent_at: u64, server_time: u64, received_at: u64) -> ClockSample {
+    let midpoint = (sent_at as i64 + received_at as i64) / 2;
+
+    ClockSample {
+      offset: server_time as i64 - midpoint,
+      delay: received_at.saturating_sub(sent_at),
+    }
+  }
+}
+
+/// Estimates the offset between our clock and the server's clock.
+#[derive(Default)]
+pub struct ClockEstimator {
+  /// The most recent samples, oldest first.
+  samples: VecDeque<ClockSample>,
+  /// Whether the offset was beyond the threshold the last time it was checked.
+  skewed: bool,
+}
+
+impl ClockEstimator {
+  /// Adds a sample, forgetting the oldest one if there are too many.
+  pub fn add_sample(&mut self, sample: ClockSample) {
+    if self.samples.len() == MAX_SAMPLES {
+      self.samples.pop_front();
+    }
+
+    self.samples.push_back(sample);
+  }
+
+  /// Returns the estimated offset of the server's clock in milliseconds, or `None` if we don't
+  /// have any samples yet.
+  ///
+  /// The sample with the lowest delay is used, because it leaves the least room for the network
+  /// to have been asymmetric.
+  pub fn offset(&self) -> Option<i64> {
+    self
+      .samples
+      .iter()
+      .min_by_key(|x| x.delay)
+      .map(|x| x.offset)
+  }
+
+  /// Returns our best guess of the server's current time in milliseconds since the Unix epoch.
+  /// If we don't have any samples yet, this is our own time.
+  pub fn server_now(&self) -> u64 {
+    (unix_millis() as i64 + self.offset().unwrap_or(0)).max(0) as u64
+  }
+
+  /// Returns the offset if it has just gone beyond `threshold` milliseconds in either direction.
+  /// This only returns `Some` once until the offset comes back within the threshold.
+  pub fn check_skew(&mut self, threshold: u64) -> Option<i64> {
+    let offset = self.offset()?;
+    let skewed = offset.unsigned_abs() > threshold;
+
+    let newly_skewed = skewed && !self.skewed;
+    self.skewed = skewed;
+
+    if newly_skewed {
+      Some(offset)
+    } else {
+      None
+    }
+  }
+}
@@ -0,0 +1,114 @@
+use std::{env, path::PathBuf, time::Duration};
+
+use crate::user::auth::{password::PasswordPolicy, username::UsernamePolicy};
+
+pub struct Config {
+  pub ws_url: String,
+  pub api_url: String,
+  /// The name of the server that `ws_url` and `api_url` point to. Accounts are kept separately for
+  /// each one.
+  pub profile: String,
+  /// Where persistent data, like the session, is stored.
+  pub data_dir: PathBuf,
+  pub ping_interval: Duration,
+  /// How long to wait before trying to connect to the WebSocket server again.
+  pub reconnect_delay: Duration,
+  /// The number of ping intervals without a pong before the connection is considered dead.
+  pub max_missed_pongs: u32,
+  /// How far the server's clock can be from ours, in milliseconds, before we warn about it.
+  pub max_clock_skew: u64,
+  /// The password policy to use instead of the server's.
+  pub password_policy: Option<PasswordPolicy>,
+  pub username_policy: UsernamePolicy,
+  /// How long to wait for more typing before checking if a username is taken.
+  pub availability_debounce: Duration,
+  /// How long to remember whether a username is taken.
+  pub availability_ttl: Duration,
+  /// The most users to remember when resolving user IDs.
+  pub directory_capacity: usize,
+  /// How long to remember who a user ID belongs to.
+  pub directory_ttl: Duration,
+  /// How long to collect user IDs before looking them all up at once.
+  pub directory_batch_window: Duration,
+  /// How many logins for a username can fail in a row before we make them wait.
+  pub login_free_attempts: u32,
+  /// How long to wait after the first login past the free ones fails. This doubles with every
+  /// failure after that, up to `login_max_cooldown`.
+  pub login_cooldown: Duration,
+  pub login_max_cooldown: Duration,
+}
+
+impl Config {
+  /// Creates the default config, with any of the `BLOP_WS_URL`, `BLOP_API_URL`, `BLOP_PROFILE`,
+  /// `BLOP_DATA_DIR`, `BLOP_PASSWORD_POLICY` and `BLOP_USERNAME_POLICY` (as JSON) environment
+  /// variables taking precedence.
+  pub fn from_env() -> Config {
+    let mut config = Config::default();
+
+    if let Ok(x) = env::var("BLOP_WS_URL") {
+      config.ws_url = x;
+    }
+
+    if let Ok(x) = env::var("BLOP_API_URL") {
+      config.api_url = x;
+    }
+
+    if let Ok(x) = env::var("BLOP_PROFILE") {
+      config.profile = x;
+    }
+
+    if let Ok(x) = env::var("BLOP_DATA_DIR") {
+      config.data_dir = x.into();
+    }
+
+    if let Ok(x) = env::var("BLOP_PASSWORD_POLICY") {
+      match serde_json::from_str(&x) {
+        Ok(x) => config.password_policy = Some(x),
+        Err(e) => eprintln!("ignoring invalid password policy: {}", e),
+      }
+    }
+
+    if let Ok(x) = env::var("BLOP_USERNAME_POLICY") {
+      match serde_json::from_str(&x) {
+        Ok(x) => config.username_policy = x,
+        Err(e) => eprintln!("ignoring invalid username policy: {}", e),
+      }
+    }
+
+    config
+  }
+
+  /// Gets the URL for the given API endpoint.
+  pub fn get_api_url(&self, endpoint: &str) -> String {
+    format!(
+      "{}/{}",
+      self.api_url.trim_end_matches('/'),
+      endpoint.trim_start_matches('/')
+    )
+  }
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Config {
+      ws_url: "ws://localhost:80/ws".into(),
+      api_url: "http://localhost:80/".into(),
+      profile: "default".into(),
+      data_dir: dirs::data_dir().unwrap_or_default().join("blop"),
+      ping_interval: Duration::from_secs(5),
+      reconnect_delay: Duration::from_secs(5),
+      max_missed_pongs: 3,
+      max_clock_skew: 2000,
+      password_policy: None,
+      username_policy: Default::default(),
+      availability_debounce: Duration::from_millis(300),
+      availability_ttl: Duration::from_secs(30),
+      directory_capacity: 1000,
+      directory_ttl: Duration::from_secs(300),
+      directory_batch_window: Duration::from_millis(10),
+      login_free_attempts: 3,
+      login_cooldown: Duration::from_secs(2),
+      login_max_cooldown: Duration::from_secs(300),
+    }
+  }
+}
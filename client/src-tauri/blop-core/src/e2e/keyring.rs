@@ -0,0 +1,304 @@
+//! Our identity and other users' devices. The devices that we've seen for each user are kept in
+//! the data directory, so that a device that shows up without being vouched for by one that we
+//! already knew, or a device whose keys change, can be warned about. They're kept when we log out,
+//! and only forgotten when the account leaves this device.
+
+use std::{
+  collections::HashMap,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{
+  device_fingerprint,
+  keys::{encode_public_key, fetch_devices, Device, Identity},
+  open, seal, DeviceInfo, Envelope, VerifyDeviceResult,
+};
+use crate::events::{emit, DeviceWarningEventPayload, DeviceWarningReason, Event, EventSink};
+
+/// The name of the file that known devices are saved to, inside of the data directory.
+const KNOWN_DEVICES_FILE_NAME: &str = "devices.json";
+
+/// A device that we've seen before.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KnownDevice {
+  public_key: String,
+  signing_key: String,
+  /// The user compared its fingerprint.
+  verified: bool,
+}
+
+impl KnownDevice {
+  fn new(device: &Device, verified: bool) -> KnownDevice {
+    KnownDevice {
+      public_key: encode_public_key(&device.public_key),
+      signing_key: base64::encode(device.signing_key.as_bytes()),
+      verified,
+    }
+  }
+
+  fn matches(&self, device: &Device) -> bool {
+    let known = KnownDevice::new(device, false);
+    self.public_key == known.public_key && self.signing_key == known.signing_key
+  }
+}
+
+/// Known devices, keyed by user ID and then by device ID.
+type KnownDevices = HashMap<String, HashMap<String, KnownDevice>>;
+
+pub struct Keyring {
+  http: Client,
+  /// The URL of the device key endpoint.
+  url: String,
+  identity: Mutex<Option<Identity>>,
+  /// The devices that the server last listed, keyed by user ID.
+  devices: Mutex<HashMap<String, Vec<Device>>>,
+  /// The file that `known` is saved to.
+  path: PathBuf,
+  known: Mutex<KnownDevices>,
+  /// Where device warnings go.
+  events: Mutex<Option<Arc<dyn EventSink>>>,
+}
+
+impl Keyring {
+  /// Creates a keyring that fetches devices from `url`, and loads the devices that were seen before
+  /// from `data_dir`.
+  pub fn new(http: Client, url: String, data_dir: &Path) -> Keyring {
+    let path = data_dir.join(KNOWN_DEVICES_FILE_NAME);
+    let known = fs::read(&path)
+      .ok()
+      .and_then(|x| serde_json::from_slice(&x).ok())
+      .unwrap_or_default();
+
+    Keyring {
+      http,
+      url,
+      identity: Default::default(),
+      devices: Default::default(),
+      path,
+      known: Mutex::new(known),
+      events: Default::default(),
+    }
+  }
+
+  /// Makes the keyring emit device warnings through `events`.
+  pub fn set_event_sink(&self, events: Arc<dyn EventSink>) {
+    *self.events.lock().unwrap() = Some(events);
+  }
+
+  /// Returns our identity, if we're logged in.
+  pub fn identity(&self) -> Option<Identity> {
+    self.identity.lock().unwrap().clone()
+  }
+
+  /// Replaces our identity, e.g. because we logged in or out.
+  pub fn set_identity(&self, identity: Option<Identity>) {
+    *self.identity.lock().unwrap() = identity;
+    self.devices.lock().unwrap().clear();
+  }
+
+  /// Forgets every device that we've seen, e.g. because the account was removed from this device.
+  pub fn forget_devices(&self) {
+    self.known.lock().unwrap().clear();
+
+    match fs::remove_file(&self.path) {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => {
+        eprintln!("couldn't clear known devices: {}", e)
+      }
+      _ => (),
+    }
+  }
+
+  /// Returns the devices of the user with the given ID, fetching them if they aren't cached.
+  pub async fn devices(&self, user_id: &str) -> Result<Vec<Device>, String> {
+    if let Some(x) = self.devices.lock().unwrap().get(user_id) {
+      return Ok(x.clone());
+    }
+
+    self.refresh(user_id).await
+  }
+
+  /// Fetches the devices of the user with the given ID, even if they're cached, and warns about any
+  /// that changed unexpectedly.
+  pub async fn refresh(&self, user_id: &str) -> Result<Vec<Device>, String> {
+    let devices = fetch_devices(&self.http, &self.url, user_id).await?;
+
+    let warnings = self.remember(user_id, &devices);
+    if let Some(events) = &*self.events.lock().unwrap() {
+      for (device_id, reason) in warnings {
+        let payload = DeviceWarningEventPayload {
+          user_id: user_id.into(),
+          device_id,
+          reason,
+        };
+        emit(&**events, Event::DeviceWarning(payload));
+      }
+    }
+
+    self
+      .devices
+      .lock()
+      .unwrap()
+      .insert(user_id.into(), devices.clone());
+    Ok(devices)
+  }
+
+  /// Records the devices that the server listed for a user. Returns the devices that we should
+  /// warn about. The first time that we see a user, their devices are trusted as they are.
+  fn remember(&self, user_id: &str, devices: &[Device]) -> Vec<(String, DeviceWarningReason)> {
+    let mut all = self.known.lock().unwrap();
+    let first_seen = !all.contains_key(user_id);
+    let known = all.entry(user_id.into()).or_default();
+
+    // a device is expected if one that we already knew vouched for it
+    let vouched = |device: &Device| {
+      device.signed_by.iter().any(|signer_id| {
+        let signer = devices.iter().find(|x| &x.device_id == signer_id);
+        match (signer, known.get(signer_id)) {
+          (Some(signer), Some(x)) => x.matches(signer),
+          _ => false,
+        }
+      })
+    };
+
+    let mut warnings = Vec::new();
+    let mut remembered = HashMap::new();
+
+    for device in devices {
+      let verified = match known.get(&device.device_id) {
+        Some(x) if x.matches(device) => x.verified,
+        Some(_) => {
+          warnings.push((device.device_id.clone(), DeviceWarningReason::KeyChanged));
+          false
+        }
+        None if first_seen || vouched(device) => false,
+        None => {
+          warnings.push((device.device_id.clone(), DeviceWarningReason::NewDevice));
+          false
+        }
+      };
+
+      remembered.insert(device.device_id.clone(), KnownDevice::new(device, verified));
+    }
+
+    *known = remembered;
+    self.save(&all);
+
+    warnings
+  }
+
+  fn save(&self, known: &KnownDevices) {
+    let write = || -> io::Result<()> {
+      if let Some(parent) = self.path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+
+      fs::write(&self.path, serde_json::to_vec(known)?)
+    };
+
+    // they'll be trusted on first use again, so this isn't a big deal
+    if let Err(e) = write() {
+      eprintln!("couldn't save known devices: {}", e);
+    }
+  }
+
+  /// Lists the devices of the user with the given ID, as they're shown to the user.
+  pub async fn list_devices(&self, user_id: &str) -> Result<Vec<DeviceInfo>, String> {
+    let identity = self.identity();
+    let devices = self.refresh(user_id).await?;
+    let known = self.known.lock().unwrap();
+
+    let infos = devices
+      .iter()
+      .map(|device| DeviceInfo {
+        device_id: device.device_id.clone(),
+        fingerprint: device_fingerprint(user_id, device),
+        verified: known
+          .get(user_id)
+          .and_then(|x| x.get(&device.device_id))
+          .map_or(false, |x| x.verified),
+        cross_signed: !device.signed_by.is_empty(),
+        current: identity.as_ref().map_or(false, |x| {
+          x.user_id == user_id && x.device_id == device.device_id
+        }),
+      })
+      .collect();
+
+    Ok(infos)
+  }
+
+  /// Compares the fingerprint of one of a user's devices with `fingerprint`, which the user read
+  /// off of that device. If they match, the device is marked as verified, and it's returned so that
+  /// it can be cross-signed.
+  pub async fn verify_device(
+    &self,
+    user_id: &str,
+    device_id: &str,
+    fingerprint: &str,
+  ) -> Result<(VerifyDeviceResult, Option<Device>), String> {
+    if self.identity().is_none() {
+      return Ok((VerifyDeviceResult::NotLoggedIn, None));
+    }
+
+    let devices = self.refresh(user_id).await?;
+    let device = match devices.into_iter().find(|x| x.device_id == device_id) {
+      Some(x) => x,
+      None => return Ok((VerifyDeviceResult::UnknownDevice, None)),
+    };
+
+    // people type them with all sorts of spacing
+    let digits = |x: &str| -> String { x.chars().filter(char::is_ascii_digit).collect() };
+    if digits(&device_fingerprint(user_id, &device)) != digits(fingerprint) {
+      return Ok((VerifyDeviceResult::Mismatch, None));
+    }
+
+    let mut all = self.known.lock().unwrap();
+    all
+      .entry(user_id.into())
+      .or_default()
+      .insert(device.device_id.clone(), KnownDevice::new(&device, true));
+    self.save(&all);
+
+    Ok((VerifyDeviceResult::Verified, Some(device)))
+  }
+
+  /// Encrypts `message` for every device of the user with the given ID.
+  pub async fn encrypt(&self, to: &str, message: &str) -> Result<Envelope, String> {
+    let identity = self.identity().ok_or("not logged in")?;
+    let devices = self.devices(to).await?;
+    if devices.is_empty() {
+      return Err("they haven't published an identity key".into());
+    }
+
+    Ok(seal(&identity, to, &devices, message))
+  }
+
+  /// Decrypts a message from the user with the given ID. If it came from a device that we haven't
+  /// seen, or with a key that we haven't seen, their devices are fetched again.
+  pub async fn decrypt(&self, from: &str, envelope: &Envelope) -> Result<String, String> {
+    let identity = self.identity().ok_or("not logged in")?;
+
+    let sender = |devices: Vec<Device>| {
+      devices.into_iter().find(|x| {
+        x.device_id == envelope.sender_device
+          && encode_public_key(&x.public_key) == envelope.sender_key
+      })
+    };
+
+    let mut device = sender(self.devices(from).await?);
+    if device.is_none() {
+      device = sender(self.refresh(from).await?);
+    }
+
+    // the server has to vouch for the key, otherwise anyone could claim to be them
+    match device {
+      Some(x) => open(&identity, from, &x, envelope),
+      None => Err("message was sealed with a key that they haven't published".into()),
+    }
+  }
+}
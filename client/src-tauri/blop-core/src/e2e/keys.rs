@@ -0,0 +1,350 @@
+//! Device keys: ours, which are kept with the session, and everyone else's, which are fetched from
+//! the API.
+
+use std::collections::HashMap;
+
+use ed25519_dalek::{Keypair, SecretKey, Signature, Signer, Verifier};
+use rand::{rngs::OsRng, RngCore};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::user::auth::secret::{serialize_exposed, Secret};
+
+/// Mixed into every signature over a device, so that it can't be mistaken for anything else.
+const DEVICE_SIGNATURE_CONTEXT: &[u8] = b"blop device v1";
+
+/// The public keys of one of a user's devices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+  pub device_id: String,
+  /// The X25519 key that messages to this device are sealed with.
+  pub public_key: PublicKey,
+  /// The Ed25519 key that this device signs the user's other devices with.
+  pub signing_key: ed25519_dalek::PublicKey,
+  /// The IDs of the user's other devices that vouched for this one. Only signatures that check out
+  /// are included.
+  pub signed_by: Vec<String>,
+}
+
+/// What a device signs when it vouches for another of the user's devices.
+fn device_statement(
+  user_id: &str,
+  device_id: &str,
+  public_key: &PublicKey,
+  signing_key: &ed25519_dalek::PublicKey,
+) -> Vec<u8> {
+  let mut statement = DEVICE_SIGNATURE_CONTEXT.to_vec();
+  for part in [user_id.as_bytes(), b"\n", device_id.as_bytes(), b"\n"] {
+    statement.extend_from_slice(part);
+  }
+  statement.extend_from_slice(public_key.as_bytes());
+  statement.extend_from_slice(signing_key.as_bytes());
+  statement
+}
+
+/// This device's keys, along with the user that they belong to. The private keys are zeroized when
+/// they're dropped.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "PersistedIdentity", into = "PersistedIdentity")]
+pub struct Identity {
+  pub user_id: String,
+  pub device_id: String,
+  secret: StaticSecret,
+  signing_secret: Zeroizing<[u8; 32]>,
+}
+
+/// How an identity is saved with the session.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedIdentity {
+  user_id: String,
+  device_id: String,
+  #[serde(serialize_with = "serialize_exposed")]
+  secret_key: Secret,
+  #[serde(serialize_with = "serialize_exposed")]
+  signing_key: Secret,
+}
+
+impl TryFrom<PersistedIdentity> for Identity {
+  type Error = String;
+
+  fn try_from(value: PersistedIdentity) -> Result<Identity, String> {
+    Ok(Identity {
+      user_id: value.user_id,
+      device_id: value.device_id,
+      secret: StaticSecret::from(*decode_key(&value.secret_key)?),
+      signing_secret: decode_key(&value.signing_key)?,
+    })
+  }
+}
+
+impl From<Identity> for PersistedIdentity {
+  fn from(value: Identity) -> PersistedIdentity {
+    let secret = Zeroizing::new(value.secret.to_bytes());
+
+    PersistedIdentity {
+      secret_key: encode_key(&secret),
+      signing_key: encode_key(&value.signing_secret),
+      user_id: value.user_id,
+      device_id: value.device_id,
+    }
+  }
+}
+
+/// The length of a base64 encoded private key.
+const ENCODED_KEY_LEN: usize = 44;
+
+/// Encodes a private key without leaving copies of it behind, which `base64::encode` would do in
+/// its buffers.
+fn encode_key(key: &[u8; 32]) -> Secret {
+  let mut buf = Zeroizing::new([0; ENCODED_KEY_LEN]);
+  let len = base64::encode_config_slice(key, base64::STANDARD, &mut *buf);
+
+  let mut encoded = Zeroizing::new(String::with_capacity(len));
+  encoded.push_str(std::str::from_utf8(&buf[..len]).expect("base64 is ASCII"));
+  encoded.into()
+}
+
+fn decode_key(encoded: &Secret) -> Result<Zeroizing<[u8; 32]>, String> {
+  // this also keeps longer keys from overflowing the buffer
+  if encoded.expose().len() != ENCODED_KEY_LEN {
+    return Err("identity key has the wrong length".into());
+  }
+
+  // base64 wants room for 3 bytes per 4 characters, padding included
+  let mut buf = Zeroizing::new([0; 33]);
+  let len = base64::decode_config_slice(encoded.expose(), base64::STANDARD, &mut *buf)
+    .map_err(|_| "identity key isn't valid base64")?;
+
+  let mut key = Zeroizing::new([0; 32]);
+  if len != key.len() {
+    return Err("identity key has the wrong length".into());
+  }
+
+  key.copy_from_slice(&buf[..len]);
+  Ok(key)
+}
+
+impl Identity {
+  /// Generates keys for a new device of `user_id`.
+  pub fn generate(user_id: &str) -> Identity {
+    let mut device_id = [0; 12];
+    let mut secret = Zeroizing::new([0; 32]);
+    let mut signing_secret = Zeroizing::new([0; 32]);
+    OsRng.fill_bytes(&mut device_id);
+    OsRng.fill_bytes(&mut *secret);
+    OsRng.fill_bytes(&mut *signing_secret);
+
+    Identity {
+      user_id: user_id.into(),
+      device_id: base64::encode_config(device_id, base64::URL_SAFE_NO_PAD),
+      secret: StaticSecret::from(*secret),
+      signing_secret,
+    }
+  }
+
+  pub fn public_key(&self) -> PublicKey {
+    PublicKey::from(&self.secret)
+  }
+
+  fn signing_keypair(&self) -> Keypair {
+    let secret = SecretKey::from_bytes(&*self.signing_secret).expect("signing keys are 32 bytes");
+    let public = (&secret).into();
+    Keypair { secret, public }
+  }
+
+  /// Returns this device, as other devices see it.
+  pub fn device(&self) -> Device {
+    Device {
+      device_id: self.device_id.clone(),
+      public_key: self.public_key(),
+      signing_key: self.signing_keypair().public,
+      signed_by: vec![],
+    }
+  }
+
+  /// Vouches for another of our devices. Returns the signature in base64.
+  pub fn sign_device(&self, device: &Device) -> String {
+    let statement = device_statement(
+      &self.user_id,
+      &device.device_id,
+      &device.public_key,
+      &device.signing_key,
+    );
+    base64::encode(self.signing_keypair().sign(&statement).to_bytes())
+  }
+
+  pub(super) fn diffie_hellman(&self, their_key: &PublicKey) -> SharedSecret {
+    self.secret.diffie_hellman(their_key)
+  }
+}
+
+pub fn encode_public_key(key: &PublicKey) -> String {
+  base64::encode(key.as_bytes())
+}
+
+pub fn decode_public_key(key: &str) -> Option<PublicKey> {
+  let bytes: [u8; 32] = base64::decode(key).ok()?.try_into().ok()?;
+  Some(PublicKey::from(bytes))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceResponse {
+  device_id: String,
+  public_key: String,
+  signing_key: String,
+  /// Signatures over this device, keyed by the ID of the device that made them.
+  #[serde(default)]
+  signatures: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct DevicesResponse {
+  devices: Vec<DeviceResponse>,
+}
+
+/// Turns the devices that the server listed for `user_id` into `Device`s, keeping only the
+/// signatures that were made by one of the others.
+fn parse_devices(user_id: &str, devices: Vec<DeviceResponse>) -> Option<Vec<Device>> {
+  let mut parsed = Vec::new();
+  for device in &devices {
+    let signing_key = base64::decode(&device.signing_key).ok()?;
+
+    parsed.push(Device {
+      device_id: device.device_id.clone(),
+      public_key: decode_public_key(&device.public_key)?,
+      signing_key: ed25519_dalek::PublicKey::from_bytes(&signing_key).ok()?,
+      signed_by: vec![],
+    });
+  }
+
+  let signed_by: Vec<Vec<String>> = devices
+    .iter()
+    .zip(&parsed)
+    .map(|(response, device)| {
+      let statement = device_statement(
+        user_id,
+        &device.device_id,
+        &device.public_key,
+        &device.signing_key,
+      );
+
+      response
+        .signatures
+        .iter()
+        .filter(|(signer_id, signature)| {
+          let signer = match parsed.iter().find(|x| &&x.device_id == signer_id) {
+            Some(x) if x.device_id != device.device_id => x,
+            _ => return false,
+          };
+
+          base64::decode(signature)
+            .ok()
+            .and_then(|x| Signature::try_from(&x[..]).ok())
+            .map_or(false, |x| signer.signing_key.verify(&statement, &x).is_ok())
+        })
+        .map(|(signer_id, _)| signer_id.clone())
+        .collect()
+    })
+    .collect();
+
+  for (device, signed_by) in parsed.iter_mut().zip(signed_by) {
+    device.signed_by = signed_by;
+  }
+
+  Some(parsed)
+}
+
+/// Fetches the devices of the user with the given ID. The list is empty if they don't exist, or
+/// haven't published any keys.
+pub async fn fetch_devices(http: &Client, url: &str, user_id: &str) -> Result<Vec<Device>, String> {
+  let resp = http
+    .get(url)
+    .query(&[("id", user_id)])
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => match resp.json::<DevicesResponse>().await {
+      Ok(x) => parse_devices(user_id, x.devices).ok_or_else(|| "malformed device keys".into()),
+      Err(_) => Err("malformed server response".into()),
+    },
+    StatusCode::NOT_FOUND => Ok(vec![]),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
+
+/// Posts `body` to one of the device endpoints. Returns `false` if the token isn't valid.
+async fn post_device(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  body: serde_json::Value,
+) -> Result<bool, String> {
+  let resp = http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => Ok(true),
+    StatusCode::UNAUTHORIZED => Ok(false),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
+
+/// Publishes the public keys of this device, replacing any that it published before.
+pub async fn register_device(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  identity: &Identity,
+) -> Result<bool, String> {
+  let device = identity.device();
+  let body = json!({
+    "deviceId": device.device_id,
+    "publicKey": encode_public_key(&device.public_key),
+    "signingKey": base64::encode(device.signing_key.as_bytes()),
+  });
+
+  post_device(http, url, token, body).await
+}
+
+/// Publishes this device's signature over another of our devices.
+pub async fn cross_sign_device(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  identity: &Identity,
+  device: &Device,
+) -> Result<bool, String> {
+  let body = json!({
+    "deviceId": device.device_id,
+    "signerId": identity.device_id,
+    "signature": identity.sign_device(device),
+  });
+
+  post_device(http, url, token, body).await
+}
+
+/// Removes one of our devices, so that nobody encrypts messages for it anymore.
+pub async fn remove_device(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  device_id: &str,
+) -> Result<bool, String> {
+  let body = json!({
+    "deviceId": device_id,
+  });
+
+  post_device(http, url, token, body).await
+}
@@ -0,0 +1,269 @@
+//! End-to-end encrypted direct messages. Every device has an X25519 identity key, whose public half
+//! is published through the API along with an Ed25519 key that it signs other devices with. A
+//! message is sealed separately for each of the recipient's devices with XChaCha20-Poly1305, using
+//! a key that the two devices derive from their identity keys, so the server only ever sees
+//! ciphertext. Users can compare fingerprints and safety numbers to make sure that the server gave
+//! them the right keys.
+
+pub mod keyring;
+pub mod keys;
+
+use std::collections::HashMap;
+
+use chacha20poly1305::{
+  aead::{Aead, NewAead, Payload},
+  Key, XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use ts_rs::TS;
+use x25519_dalek::PublicKey;
+
+use self::keys::{encode_public_key, Device, Identity};
+
+/// Mixed into every session key, so that keys for other purposes can never collide with them.
+const SESSION_KEY_INFO: &[u8] = b"blop direct message v1";
+
+/// How many times the fingerprint hash is iterated, which makes it expensive to find a key with a
+/// similar fingerprint.
+const FINGERPRINT_ITERATIONS: usize = 5200;
+
+/// The fingerprint format, which is hashed in first so that future formats can't collide with it.
+const FINGERPRINT_VERSION: [u8; 2] = [0, 1];
+
+/// An encrypted message, as it travels through the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+  /// The ID of the device that sent the message.
+  pub sender_device: String,
+  /// That device's public identity key, so that the recipient can tell if it changed.
+  pub sender_key: String,
+  /// The message, sealed for each of the recipient's devices, keyed by device ID.
+  pub sealed: HashMap<String, Sealed>,
+}
+
+/// A message that's sealed for one device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sealed {
+  pub nonce: String,
+  pub ciphertext: String,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/e2e/safety-number.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum SafetyNumberResult {
+  Success {
+    #[serde(rename = "safetyNumber")]
+    safety_number: String,
+  },
+  /// They haven't published an identity key, so we can't message them yet.
+  NoKey,
+  NotLoggedIn,
+}
+
+/// One of a user's devices, as it's shown to the user.
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/e2e/device-info.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+  pub device_id: String,
+  /// What the device shows as its own fingerprint, if it's the device that it claims to be.
+  pub fingerprint: String,
+  /// We compared its fingerprint with `verify_device`.
+  pub verified: bool,
+  /// Another of the user's devices vouched for it.
+  pub cross_signed: bool,
+  /// It's the device that we're running on.
+  pub current: bool,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/e2e/list-devices.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ListDevicesResult {
+  Success { devices: Vec<DeviceInfo> },
+  NotLoggedIn,
+}
+
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/e2e/verify-device.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum VerifyDeviceResult {
+  /// The fingerprints match. If it's one of our own devices, we cross-signed it too.
+  Verified,
+  /// The fingerprints don't match, so the server might have given us someone else's key.
+  Mismatch,
+  UnknownDevice,
+  NotLoggedIn,
+}
+
+/// The key that `identity` shares with whoever owns `their_key`. Both of them derive the same key.
+fn session_key(identity: &Identity, their_key: &PublicKey) -> Key {
+  let shared = identity.diffie_hellman(their_key);
+
+  // both sides have to order the keys the same way
+  let our_key = identity.public_key();
+  let (first, second) = if our_key.as_bytes() < their_key.as_bytes() {
+    (our_key, *their_key)
+  } else {
+    (*their_key, our_key)
+  };
+
+  let mut info = SESSION_KEY_INFO.to_vec();
+  info.extend_from_slice(first.as_bytes());
+  info.extend_from_slice(second.as_bytes());
+
+  let mut key = Key::default();
+  Hkdf::<Sha256>::new(None, shared.as_bytes())
+    .expand(&info, &mut key)
+    .expect("32 bytes is a valid HKDF output length");
+  key
+}
+
+/// Binds a message to the devices that it's between, so that the server can't pass it off as
+/// coming from someone else.
+fn associated_data(from: (&str, &str), to: (&str, &str)) -> Vec<u8> {
+  format!("{}\n{}\n{}\n{}", from.0, from.1, to.0, to.1).into_bytes()
+}
+
+/// Encrypts `message` from `identity` to each of `devices`, which belong to the user `to`.
+pub fn seal(identity: &Identity, to: &str, devices: &[Device], message: &str) -> Envelope {
+  let sealed = devices
+    .iter()
+    .map(|device| {
+      let cipher = XChaCha20Poly1305::new(&session_key(identity, &device.public_key));
+
+      // the session key never changes, so the nonces have to be random
+      let mut nonce = XNonce::default();
+      OsRng.fill_bytes(&mut nonce);
+
+      let payload = Payload {
+        msg: message.as_bytes(),
+        aad: &associated_data(
+          (&identity.user_id, &identity.device_id),
+          (to, &device.device_id),
+        ),
+      };
+      let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .expect("messages are never too long to encrypt");
+
+      let sealed = Sealed {
+        nonce: base64::encode(nonce),
+        ciphertext: base64::encode(ciphertext),
+      };
+      (device.device_id.clone(), sealed)
+    })
+    .collect();
+
+  Envelope {
+    sender_device: identity.device_id.clone(),
+    sender_key: encode_public_key(&identity.public_key()),
+    sealed,
+  }
+}
+
+/// Decrypts a message that `sender`, one of the devices of the user `from`, sent to `identity`.
+pub fn open(
+  identity: &Identity,
+  from: &str,
+  sender: &Device,
+  envelope: &Envelope,
+) -> Result<String, String> {
+  let sealed = envelope
+    .sealed
+    .get(&identity.device_id)
+    .ok_or("message wasn't sealed for this device")?;
+
+  let nonce = base64::decode(&sealed.nonce).map_err(|_| "malformed nonce")?;
+  if nonce.len() != XNonce::default().len() {
+    return Err("malformed nonce".into());
+  }
+  let ciphertext = base64::decode(&sealed.ciphertext).map_err(|_| "malformed ciphertext")?;
+
+  let cipher = XChaCha20Poly1305::new(&session_key(identity, &sender.public_key));
+  let payload = Payload {
+    msg: &ciphertext,
+    aad: &associated_data(
+      (from, &sender.device_id),
+      (&identity.user_id, &identity.device_id),
+    ),
+  };
+  let plaintext = cipher
+    .decrypt(XNonce::from_slice(&nonce), payload)
+    .map_err(|_| "message couldn't be decrypted")?;
+
+  String::from_utf8(plaintext).map_err(|_| "message isn't UTF-8".into())
+}
+
+/// Returns the 30 digit fingerprint of a user's keys.
+fn fingerprint(user_id: &str, keys: &[&[u8]]) -> String {
+  let hash_keys = |mut hasher: Sha512| {
+    for key in keys {
+      hasher.update(key);
+    }
+    hasher
+  };
+
+  let mut digest = hash_keys(Sha512::new().chain_update(FINGERPRINT_VERSION))
+    .chain_update(user_id.as_bytes())
+    .finalize();
+
+  for _ in 0..FINGERPRINT_ITERATIONS {
+    digest = hash_keys(Sha512::new().chain_update(digest)).finalize();
+  }
+
+  // each 5 byte chunk becomes 5 digits
+  digest[..30]
+    .chunks(5)
+    .map(|chunk| {
+      let n = chunk.iter().fold(0u64, |n, x| n << 8 | *x as u64);
+      format!("{:05}", n % 100_000)
+    })
+    .collect()
+}
+
+/// Splits digits into groups of 5, so that they're easier to compare.
+fn group(digits: &str) -> String {
+  digits
+    .as_bytes()
+    .chunks(5)
+    .map(|x| std::str::from_utf8(x).unwrap())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Returns the fingerprint of one of the devices of the user with the given ID: 30 digits in
+/// groups of 5.
+pub fn device_fingerprint(user_id: &str, device: &Device) -> String {
+  let keys = [
+    device.device_id.as_bytes(),
+    device.public_key.as_bytes(),
+    device.signing_key.as_bytes(),
+  ];
+  group(&fingerprint(user_id, &keys))
+}
+
+/// Returns the safety number of a conversation between two users: 60 digits in groups of 5, which
+/// is the same for both of them. It covers every device of both users, so it changes when either
+/// of them adds a device. If either of them sees a different number, one of the keys isn't what it
+/// should be.
+pub fn safety_number(ours: (&str, &[Device]), theirs: (&str, &[Device])) -> String {
+  let user_fingerprint = |(user_id, devices): (&str, &[Device])| {
+    let mut keys: Vec<&[u8]> = devices
+      .iter()
+      .map(|x| &x.public_key.as_bytes()[..])
+      .collect();
+    keys.sort();
+    fingerprint(user_id, &keys)
+  };
+
+  let mut fingerprints = [user_fingerprint(ours), user_fingerprint(theirs)];
+  fingerprints.sort();
+
+  group(&fingerprints.concat())
+}
@@ -0,0 +1,246 @@
+use serde_json::Value;
+use ts_rs::TS;
+
+use crate::{accounts::AccountKey, user::User};
+
+/// Something that delivers events to whoever is using the client, e.g. the frontend.
+pub trait EventSink: Send + Sync {
+  /// Emits the event named `event` with the given payload.
+  fn emit(&self, event: &str, payload: Value);
+}
+
+impl<F> EventSink for F
+where
+  F: Fn(&str, Value) + Send + Sync,
+{
+  fn emit(&self, event: &str, payload: Value) {
+    self(event, payload)
+  }
+}
+
+/// Serializes the payload of `event` and emits it through `sink`.
+pub fn emit(sink: &dyn EventSink, event: Event) {
+  let name = event.name();
+  match event.payload() {
+    Ok(x) => sink.emit(name, x),
+    // our payloads are plain data, so this shouldn't happen
+    Err(e) => eprintln!("couldn't serialize {} event: {}", name, e),
+  }
+}
+
+/// Declares `Event`, with a variant for each event, and `EventMap`, which maps their names to
+/// their payloads for the frontend. Variants are named after the event in `UpperCamelCase`.
+macro_rules! events {
+  ($($name:ident => $variant:ident($payload:ty),)*) => {
+    /// Every event that the client emits.
+    #[derive(Clone)]
+    pub enum Event {
+      $($variant($payload),)*
+    }
+
+    impl Event {
+      /// Returns the name that the event is emitted with.
+      pub fn name(&self) -> &'static str {
+        match self {
+          $(Event::$variant(_) => stringify!($name),)*
+        }
+      }
+
+      /// Serializes the payload of the event.
+      pub fn payload(&self) -> serde_json::Result<Value> {
+        match self {
+          $(Event::$variant(x) => serde_json::to_value(x),)*
+        }
+      }
+    }
+
+    /// The payload of every event, keyed by name. Only its TypeScript declaration is used, which
+    /// lets the frontend's `listen` calls be type-checked.
+    #[derive(TS)]
+    #[ts(export, export_to = "../../src/events/map.d.ts")]
+    #[allow(dead_code)]
+    pub struct EventMap {
+      $($name: $payload,)*
+    }
+  };
+}
+
+events! {
+  latency => Latency(LatencyEventPayload),
+  message => Message(MessageEventPayload),
+  direct_message => DirectMessage(DirectMessageEventPayload),
+  typing => Typing(TypingEventPayload),
+  presence => Presence(PresenceEventPayload),
+  friend_request => FriendRequest(FriendRequestEventPayload),
+  profile_updated => ProfileUpdated(ProfileUpdatedEventPayload),
+  device_warning => DeviceWarning(DeviceWarningEventPayload),
+  auth_state => AuthState(AuthStateEventPayload),
+  session_changed => SessionChanged(SessionChangedEventPayload),
+  account_switched => AccountSwitched(AccountSwitchedEventPayload),
+  notification => Notification(NotificationEventPayload),
+}
+
+/// The payload that carries the current latency to the WebSocket server.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/Latency.d.ts")]
+pub struct LatencyEventPayload {
+  pub latency: u32,
+}
+
+/// The payload that carries messages from the WebSocket server.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/Message.d.ts")]
+pub struct MessageEventPayload {
+  pub message: String,
+  /// Who sent the message, if they were logged in.
+  #[serde(rename = "userId")]
+  pub user_id: Option<String>,
+  /// When the message was sent according to the server's clock, in milliseconds since the Unix
+  /// epoch. Plain messages from logged out users don't have it.
+  #[serde(rename = "sentAt")]
+  #[ts(type = "number | null")]
+  pub sent_at: Option<u64>,
+}
+
+/// The payload that carries a decrypted direct message.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/DirectMessage.d.ts")]
+pub struct DirectMessageEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+  pub message: String,
+  /// When the message was sent according to the server's clock, in milliseconds since the Unix
+  /// epoch.
+  #[serde(rename = "sentAt")]
+  #[ts(type = "number")]
+  pub sent_at: u64,
+}
+
+/// The payload that says that a user is typing.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/Typing.d.ts")]
+pub struct TypingEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+}
+
+/// The payload that says that a user came online or went offline.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/Presence.d.ts")]
+pub struct PresenceEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+  pub online: bool,
+}
+
+/// The payload that carries a friend request from another user.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/FriendRequest.d.ts")]
+pub struct FriendRequestEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+  pub username: String,
+}
+
+/// The payload that says that a user's profile changed, so it should be fetched again.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/ProfileUpdated.d.ts")]
+pub struct ProfileUpdatedEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+}
+
+/// The payload that warns that one of a user's devices changed unexpectedly, so messages to them
+/// might be readable by someone else.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/DeviceWarning.d.ts")]
+pub struct DeviceWarningEventPayload {
+  #[serde(rename = "userId")]
+  pub user_id: String,
+  #[serde(rename = "deviceId")]
+  pub device_id: String,
+  pub reason: DeviceWarningReason,
+}
+
+/// The payload that says whether the saved session could be used at startup.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/AuthState.d.ts")]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum AuthStateEventPayload {
+  /// The active account was logged in again, and the server accepted its token.
+  Restored { account: AccountKey, user: User },
+  /// The server rejected the active account's token, so it has to log in again.
+  Expired { account: AccountKey },
+  /// The server couldn't be reached, so the active account was logged in again without checking
+  /// its token.
+  Offline { account: AccountKey },
+  /// No account is logged in automatically.
+  None,
+}
+
+/// The payload that says that someone logged in or out.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/SessionChanged.d.ts")]
+pub struct SessionChangedEventPayload {
+  /// Who's logged in now, or `None` if nobody is.
+  #[serde(rename = "userId")]
+  pub user_id: Option<String>,
+}
+
+/// The payload that says which account commands and events belong to now.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/AccountSwitched.d.ts")]
+pub struct AccountSwitchedEventPayload {
+  /// `None` if no account is active, i.e. we're logged out.
+  pub account: Option<AccountKey>,
+}
+
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/events/Notification.d.ts")]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NotificationEventPayload {
+  LostConnection {
+    /// Why the connection was lost.
+    reason: LostConnectionReason,
+  },
+  Connected {
+    /// Whether or not this is the first time we've connected to the server.
+    /// If this is false, it means this is a reconnect event.
+    #[serde(rename = "firstConnection")]
+    first_connection: bool,
+  },
+  Broadcast {
+    message: String,
+  },
+  /// Our clock is too far off from the server's clock, so message timestamps might be wrong.
+  ClockSkew {
+    /// How far the server's clock is ahead of ours, in milliseconds.
+    #[ts(type = "number")]
+    offset: i64,
+  },
+}
+
+/// The reason that the connection to the WebSocket server was lost.
+#[derive(Clone, serde::Serialize, TS)]
+#[ts(
+  export,
+  export_to = "../../src/types/websocket/lost-connection-reason.d.ts"
+)]
+#[serde(rename_all = "camelCase")]
+pub enum LostConnectionReason {
+  /// The server closed the connection, or the connection errored.
+  Closed,
+  /// The server stopped answering pings.
+  PongTimeout,
+}
+
+/// Why a device was warned about.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, TS)]
+#[ts(export, export_to = "../../src/types/e2e/device-warning-reason.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum DeviceWarningReason {
+  /// None of the user's devices that we already knew vouched for it.
+  NewDevice,
+  /// Its keys aren't the ones that we saw before.
+  KeyChanged,
+}
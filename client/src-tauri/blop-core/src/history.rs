@@ -0,0 +1,86 @@
+//! The messages that an account received. They're appended to a file in the account's data
+//! directory, one JSON object per line, so that conversations survive restarts.
+
+use std::{
+  fs::{self, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// The name of the file that messages are saved to, inside of the data directory.
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/history/history-entry.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+  /// Who sent the message, if they were logged in.
+  pub user_id: Option<String>,
+  pub message: String,
+  /// It was an end-to-end encrypted direct message.
+  pub direct: bool,
+  /// When we received it, in milliseconds since the Unix epoch.
+  #[ts(type = "number")]
+  pub received_at: u64,
+}
+
+/// The messages that were received so far, oldest first, along with the file that they're saved
+/// to.
+pub struct MessageHistory {
+  path: PathBuf,
+  entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl MessageHistory {
+  /// Loads the messages that were saved in `data_dir`, if there are any. Lines that can't be read,
+  /// e.g. because we were stopped while writing one, are skipped.
+  pub fn load(data_dir: &Path) -> MessageHistory {
+    let path = data_dir.join(HISTORY_FILE_NAME);
+    let entries = fs::read_to_string(&path)
+      .unwrap_or_default()
+      .lines()
+      .filter_map(|x| serde_json::from_str(x).ok())
+      .collect();
+
+    MessageHistory {
+      path,
+      entries: Mutex::new(entries),
+    }
+  }
+
+  /// Returns the last `limit` messages, oldest first.
+  pub fn recent(&self, limit: usize) -> Vec<HistoryEntry> {
+    let entries = self.entries.lock().unwrap();
+    entries[entries.len().saturating_sub(limit)..].to_vec()
+  }
+
+  /// Adds a message, and appends it to the file.
+  pub fn record(&self, entry: HistoryEntry) {
+    let mut entries = self.entries.lock().unwrap();
+
+    let append = || -> io::Result<()> {
+      if let Some(parent) = self.path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+
+      let mut line = serde_json::to_vec(&entry)?;
+      line.push(b'\n');
+      OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&self.path)?
+        .write_all(&line)
+    };
+
+    // it's still shown until we restart
+    if let Err(e) = append() {
+      eprintln!("couldn't save message: {}", e);
+    }
+
+    entries.push(entry);
+  }
+}
@@ -0,0 +1,16 @@
+//! The parts of Blop that don't depend on Tauri: configuration, accounts, authentication, the API
+//! client, the WebSocket client and end-to-end encryption. Events are delivered through an
+//! [`events::EventSink`], so this crate can be driven by the desktop app, bots or tests alike.
+
+pub mod accounts;
+pub mod api;
+pub mod clock;
+pub mod common;
+pub mod config;
+pub mod e2e;
+pub mod events;
+pub mod history;
+pub mod user;
+pub mod websocket;
+
+pub use config::Config;
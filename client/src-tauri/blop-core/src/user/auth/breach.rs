@@ -0,0 +1,230 @@
+//! An offline check for passwords that are known to have been compromised.
+//!
+//! Lists are sets of SHA-1 hashes, like the ones that Have I Been Pwned publishes for k-anonymity
+//! lookups. Only the first 64 bits of each hash are kept, which keeps false positives negligible,
+//! and the hashes are indexed by their first few bits so that a lookup only searches one bucket.
+//! A small list of common passwords is bundled, and a bigger one can be imported into the data
+//! directory with `blop-cli import-breaches`.
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use sha1::{Digest, Sha1};
+
+const MAGIC: &[u8; 8] = b"BLOPBRCH";
+const VERSION: u8 = 1;
+/// Buckets are indexed by at most this many bits, i.e. there are at most 65536 of them.
+const MAX_PREFIX_BITS: u8 = 16;
+const HEADER_LENGTH: usize = MAGIC.len() + 2 + 8;
+
+/// The name of the imported list in the data directory.
+const FILE_NAME: &str = "breached-passwords.bin";
+
+static BUNDLED: Lazy<HashList> = Lazy::new(|| {
+  HashList::from_hashes(
+    include_str!("strength/words/passwords.txt")
+      .lines()
+      .map(hash),
+  )
+});
+
+/// Returns the first 64 bits of the SHA-1 hash of `password`.
+fn hash(password: &str) -> u64 {
+  let digest = Sha1::digest(password.as_bytes());
+  let mut key = [0; 8];
+  key.copy_from_slice(&digest[..8]);
+  u64::from_be_bytes(key)
+}
+
+/// Parses a line of a Have I Been Pwned hash list, i.e. a hex SHA-1 hash that's optionally
+/// followed by a colon and a count, and returns its first 64 bits.
+pub fn parse_hash_line(line: &str) -> Option<u64> {
+  let hash = line.split(':').next()?.trim();
+  if hash.len() != 40 || !hash.chars().all(|x| x.is_ascii_hexdigit()) {
+    return None;
+  }
+
+  u64::from_str_radix(&hash[..16], 16).ok()
+}
+
+/// A sorted set of truncated hashes, indexed by prefix.
+pub struct HashList {
+  prefix_bits: u8,
+  /// The index into `keys` at which each bucket starts, followed by the number of keys.
+  offsets: Vec<u64>,
+  keys: Vec<u64>,
+}
+
+impl HashList {
+  /// Creates a list from truncated hashes, as returned by `parse_hash_line`.
+  pub fn from_hashes(hashes: impl IntoIterator<Item = u64>) -> HashList {
+    let mut keys: Vec<u64> = hashes.into_iter().collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    // about one key per bucket
+    let prefix_bits = (64 - keys.len().leading_zeros()).min(MAX_PREFIX_BITS as u32) as u8;
+
+    let mut offsets = Vec::with_capacity((1 << prefix_bits) + 1);
+    let mut i = 0;
+    for bucket in 0..1u64 << prefix_bits {
+      while i < keys.len() && bucket_of(keys[i], prefix_bits) < bucket {
+        i += 1;
+      }
+      offsets.push(i as u64);
+    }
+    offsets.push(keys.len() as u64);
+
+    HashList {
+      prefix_bits,
+      offsets,
+      keys,
+    }
+  }
+
+  /// Parses a list that was written with `to_bytes`.
+  pub fn from_bytes(bytes: &[u8]) -> Result<HashList, String> {
+    if bytes.len() < HEADER_LENGTH || bytes[..MAGIC.len()] != MAGIC[..] {
+      return Err("not a breached password list".into());
+    }
+
+    if bytes[8] != VERSION {
+      return Err(format!("unsupported list version {}", bytes[8]));
+    }
+
+    let prefix_bits = bytes[9];
+    if prefix_bits > MAX_PREFIX_BITS {
+      return Err("invalid prefix length".into());
+    }
+
+    let mut words = bytes[HEADER_LENGTH..]
+      .chunks_exact(8)
+      .map(|x| u64::from_le_bytes([x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7]]));
+    let count = read_u64(&bytes[10..18]) as usize;
+    let buckets = (1 << prefix_bits) + 1;
+
+    let expected = count
+      .checked_add(buckets)
+      .and_then(|x| x.checked_mul(8))
+      .and_then(|x| x.checked_add(HEADER_LENGTH));
+    if expected != Some(bytes.len()) {
+      return Err("truncated list".into());
+    }
+
+    let offsets: Vec<u64> = words.by_ref().take(buckets).collect();
+    let keys: Vec<u64> = words.collect();
+
+    // a malformed index could make lookups panic
+    let sorted = offsets.windows(2).all(|x| x[0] <= x[1]);
+    if !sorted || offsets[0] != 0 || offsets[buckets - 1] != count as u64 {
+      return Err("invalid index".into());
+    }
+
+    Ok(HashList {
+      prefix_bits,
+      offsets,
+      keys,
+    })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + (self.offsets.len() + self.keys.len()) * 8);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(self.prefix_bits);
+    bytes.extend_from_slice(&(self.keys.len() as u64).to_le_bytes());
+
+    for x in self.offsets.iter().chain(&self.keys) {
+      bytes.extend_from_slice(&x.to_le_bytes());
+    }
+
+    bytes
+  }
+
+  /// Returns the number of hashes in the list.
+  pub fn len(&self) -> usize {
+    self.keys.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  /// Returns `true` if the list contains the truncated hash `key`.
+  pub fn contains_hash(&self, key: u64) -> bool {
+    let bucket = bucket_of(key, self.prefix_bits) as usize;
+    let start = self.offsets[bucket] as usize;
+    let end = self.offsets[bucket + 1] as usize;
+
+    self.keys[start..end].binary_search(&key).is_ok()
+  }
+}
+
+fn bucket_of(key: u64, prefix_bits: u8) -> u64 {
+  key.checked_shr(64 - prefix_bits as u32).unwrap_or(0)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+  let mut x = [0; 8];
+  x.copy_from_slice(bytes);
+  u64::from_le_bytes(x)
+}
+
+/// The bundled list of breached passwords, along with the one imported into the data directory.
+pub struct BreachedPasswords {
+  imported: Option<HashList>,
+}
+
+impl BreachedPasswords {
+  /// Returns the bundled list only.
+  pub fn bundled() -> BreachedPasswords {
+    BreachedPasswords { imported: None }
+  }
+
+  /// Returns the bundled list, and the one imported into `data_dir` if there is one.
+  pub fn load(data_dir: &Path) -> BreachedPasswords {
+    let imported = match fs::read(Self::path(data_dir)) {
+      Ok(x) => match HashList::from_bytes(&x) {
+        Ok(x) => Some(x),
+        Err(e) => {
+          eprintln!("ignoring breached password list: {}", e);
+          None
+        }
+      },
+      // there's nothing to import, which is fine
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => {
+        eprintln!("couldn't read breached password list: {}", e);
+        None
+      }
+    };
+
+    BreachedPasswords { imported }
+  }
+
+  /// Returns where the imported list is stored in `data_dir`.
+  pub fn path(data_dir: &Path) -> PathBuf {
+    data_dir.join(FILE_NAME)
+  }
+
+  /// Writes `list` to `data_dir`, replacing any list that was imported before.
+  pub fn import(data_dir: &Path, list: &HashList) -> io::Result<()> {
+    fs::create_dir_all(data_dir)?;
+    fs::write(Self::path(data_dir), list.to_bytes())
+  }
+
+  /// Returns `true` if `password` is known to have been compromised.
+  pub fn contains(&self, password: &str) -> bool {
+    let key = hash(password);
+
+    BUNDLED.contains_hash(key)
+      || self
+        .imported
+        .as_ref()
+        .map_or(false, |x| x.contains_hash(key))
+  }
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use self::secret::Secret;
+
+pub mod breach;
+pub mod password;
+pub mod persist;
+pub mod secret;
+pub mod strength;
+pub mod throttle;
+pub mod totp;
+pub mod username;
+
+/// Who is logged in. The token is shared rather than copied, so that it's only zeroized once
+/// nothing is using it.
+#[derive(Clone)]
+pub struct Session {
+  pub token: Arc<Secret>,
+  pub user_id: String,
+}
+
+impl Session {
+  /// Returns true if `a` and `b` are the same login, rather than the same user logging in again.
+  pub fn same(a: &Option<Session>, b: &Option<Session>) -> bool {
+    match (a, b) {
+      (Some(a), Some(b)) => Arc::ptr_eq(&a.token, &b.token),
+      (None, None) => true,
+      _ => false,
+    }
+  }
+}
+
+/// Contains state related to the currently logged in user. The token and user ID are replaced
+/// together, so nobody can see one without the other, and subscribers hear about every change.
+pub struct AuthenticationState {
+  session: watch::Sender<Option<Session>>,
+}
+
+impl AuthenticationState {
+  /// Returns a copy of the session, if someone is logged in.
+  pub fn session(&self) -> Option<Session> {
+    self.session.borrow().clone()
+  }
+
+  /// Returns the JWT.
+  pub fn get_token(&self) -> Option<Arc<Secret>> {
+    self.session.borrow().as_ref().map(|x| x.token.clone())
+  }
+
+  /// Returns a copy of the user ID.
+  pub fn get_user_id(&self) -> Option<String> {
+    self.session.borrow().as_ref().map(|x| x.user_id.clone())
+  }
+
+  pub fn is_logged_in(&self) -> bool {
+    self.session.borrow().is_some()
+  }
+
+  /// Sets the token and user ID.
+  pub fn login(&self, token: Secret, user_id: String) {
+    self.session.send_replace(Some(Session {
+      token: Arc::new(token),
+      user_id,
+    }));
+  }
+
+  /// Clears the token and user ID.
+  pub fn logout(&self) {
+    self.session.send_replace(None);
+  }
+
+  /// Returns a receiver that's notified whenever someone logs in or out. Logging out while nobody
+  /// is logged in notifies it too, so compare with `Session::same` to skip those.
+  pub fn subscribe(&self) -> watch::Receiver<Option<Session>> {
+    self.session.subscribe()
+  }
+}
+
+impl Default for AuthenticationState {
+  fn default() -> Self {
+    AuthenticationState {
+      session: watch::channel(None).0,
+    }
+  }
+}
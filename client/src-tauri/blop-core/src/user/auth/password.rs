@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{
+  breach::BreachedPasswords,
+  strength::{estimate_strength, PasswordStrength},
+};
+
+/// The rules that a password has to follow. Every rule is optional, so that a server can enforce
+/// as few of them as it likes. The default matches the Go backend's default policy.
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/password-policy.d.ts")]
+#[serde(rename_all = "camelCase", default)]
+pub struct PasswordPolicy {
+  /// The minimum number of characters.
+  pub min_length: Option<u32>,
+  /// The maximum number of characters.
+  pub max_length: Option<u32>,
+  pub require_upper: bool,
+  pub require_lower: bool,
+  pub require_digit: bool,
+  /// Requires a character that is neither alphanumeric nor whitespace.
+  pub require_symbol: bool,
+  /// Only allows printable ASCII characters and spaces.
+  pub ascii_only: bool,
+  /// The minimum estimated strength, from 0 to 4. Only clients enforce this, since estimating it
+  /// needs the word lists.
+  pub min_strength: Option<u8>,
+  /// Rejects passwords that are known to have been compromised. Only clients enforce this too.
+  pub reject_breached: bool,
+}
+
+impl Default for PasswordPolicy {
+  fn default() -> Self {
+    PasswordPolicy {
+      min_length: Some(8),
+      max_length: Some(512), // if your password is longer than 512 characters, sorry
+      require_upper: false,
+      require_lower: false,
+      require_digit: false,
+      require_symbol: false,
+      ascii_only: false,
+      min_strength: Some(2),
+      reject_breached: true,
+    }
+  }
+}
+
+impl PasswordPolicy {
+  /// Returns the validation for `password` under this policy, checking `breached` for known
+  /// compromised passwords.
+  pub fn validate(&self, password: &str, breached: &BreachedPasswords) -> PasswordValidation {
+    let length = password.chars().count() as u32;
+    let strength = estimate_strength(password);
+    let mut rules = Vec::new();
+
+    if let Some(min) = self.min_length {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::MinLength,
+        format!("be at least {} characters long", min),
+        length >= min,
+      ));
+    }
+
+    if let Some(max) = self.max_length {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::MaxLength,
+        format!("be at most {} characters long", max),
+        length <= max,
+      ));
+    }
+
+    if self.ascii_only {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::AsciiOnly,
+        "only use letters, digits and symbols from a US keyboard".into(),
+        password.chars().all(|c| c.is_ascii_graphic() || c == ' '),
+      ));
+    }
+
+    if self.require_upper {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::Upper,
+        "have an uppercase letter".into(),
+        password.chars().any(char::is_uppercase),
+      ));
+    }
+
+    if self.require_lower {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::Lower,
+        "have a lowercase letter".into(),
+        password.chars().any(char::is_lowercase),
+      ));
+    }
+
+    if self.require_digit {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::Digit,
+        "have a digit".into(),
+        password.chars().any(char::is_numeric),
+      ));
+    }
+
+    if self.require_symbol {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::Symbol,
+        "have a symbol".into(),
+        password
+          .chars()
+          .any(|c| !c.is_alphanumeric() && !c.is_whitespace()),
+      ));
+    }
+
+    if let Some(min) = self.min_strength {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::Strength,
+        "be harder to guess".into(),
+        strength.score >= min,
+      ));
+    }
+
+    if self.reject_breached {
+      rules.push(PasswordRuleResult::new(
+        PasswordRule::NotBreached,
+        "not be a known compromised password".into(),
+        !breached.contains(password),
+      ));
+    }
+
+    PasswordValidation::new(PasswordCriteria { rules, strength })
+  }
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(
+  export,
+  export_to = "../../src/types/user/error/password-validation.d.ts"
+)]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum PasswordValidation {
+  Valid(PasswordCriteria),
+  Invalid(PasswordCriteria),
+}
+
+impl PasswordValidation {
+  /// Creates a new `PasswordValidation` struct.
+  pub fn new(criteria: PasswordCriteria) -> PasswordValidation {
+    if criteria.rules.iter().all(|x| x.passed) {
+      PasswordValidation::Valid(criteria)
+    } else {
+      PasswordValidation::Invalid(criteria)
+    }
+  }
+
+  /// Returns `true` if this validation is `Valid`.
+  #[inline]
+  pub fn is_valid(&self) -> bool {
+    matches!(*self, Self::Valid(_))
+  }
+}
+
+/// A rule from a `PasswordPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/error/password-rule.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum PasswordRule {
+  MinLength,
+  MaxLength,
+  AsciiOnly,
+  Upper,
+  Lower,
+  Digit,
+  Symbol,
+  Strength,
+  NotBreached,
+}
+
+/// Whether a password follows one of the rules of a policy.
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(
+  export,
+  export_to = "../../src/types/user/error/password-rule-result.d.ts"
+)]
+pub struct PasswordRuleResult {
+  pub id: PasswordRule,
+  /// Describes the rule, worded to follow "Your password must".
+  pub message: String,
+  pub passed: bool,
+}
+
+impl PasswordRuleResult {
+  fn new(id: PasswordRule, message: String, passed: bool) -> PasswordRuleResult {
+    PasswordRuleResult {
+      id,
+      message,
+      passed,
+    }
+  }
+}
+
+/// The result of every rule in the policy that a password was validated against.
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(
+  export,
+  export_to = "../../src/types/user/error/validate-password.d.ts"
+)]
+pub struct PasswordCriteria {
+  pub rules: Vec<PasswordRuleResult>,
+  pub strength: PasswordStrength,
+}
+
+/// Returns the validation for `password` under `policy`.
+pub fn validate_password(
+  password: &str,
+  policy: &PasswordPolicy,
+  breached: &BreachedPasswords,
+) -> PasswordValidation {
+  policy.validate(password, breached)
+}
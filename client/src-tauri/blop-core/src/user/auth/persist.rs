@@ -0,0 +1,142 @@
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+  fs::{self, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+  e2e::keys::Identity,
+  user::auth::secret::{serialize_exposed, Secret},
+};
+
+/// The name of the file that the session is saved to, inside of the data directory.
+const SESSION_FILE_NAME: &str = "session.json";
+
+/// The name of the directory that device keys are kept in, inside of the data directory.
+const IDENTITIES_DIR_NAME: &str = "identities";
+
+/// A login that is saved to disk so that it survives restarts.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistedSession {
+  #[serde(serialize_with = "serialize_exposed")]
+  pub token: Secret,
+  pub user_id: String,
+  /// This device's keys. Sessions that were saved before there were device keys don't have them.
+  #[serde(default)]
+  pub identity: Option<Identity>,
+}
+
+/// A JSON file that holds the persisted session. The desktop app and `blop-cli` share it, so
+/// logging in with one logs in the other.
+pub struct SessionFile {
+  path: PathBuf,
+}
+
+impl SessionFile {
+  /// Creates a handle to the session file inside of `data_dir`. Nothing is read or written yet.
+  pub fn new(data_dir: &Path) -> SessionFile {
+    SessionFile {
+      path: data_dir.join(SESSION_FILE_NAME),
+    }
+  }
+
+  /// Reads the session. Returns `None` if there is no session, or if it can't be read.
+  pub fn load(&self) -> Option<PersistedSession> {
+    let contents = Zeroizing::new(fs::read(&self.path).ok()?);
+    serde_json::from_slice(&contents).ok()
+  }
+
+  /// Writes the session, creating the data directory if needed.
+  pub fn save(&self, session: &PersistedSession) -> io::Result<()> {
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    // the file has the token in it, so the buffer shouldn't outlive it
+    let contents = Zeroizing::new(serde_json::to_vec(session)?);
+    write_private(&self.path, &contents)
+  }
+
+  /// Deletes the session. It's fine if there wasn't one.
+  pub fn clear(&self) -> io::Result<()> {
+    match fs::remove_file(&self.path) {
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+      other => other,
+    }
+  }
+}
+
+/// This device's keys for every user that logged in on it, in
+/// `<data dir>/identities/<profile>/<user ID>.json`. They outlive sessions, so that logging in
+/// again is the same device to everyone else. Every account shares them, since logins go through
+/// the signed out client.
+pub struct IdentityStore {
+  dir: PathBuf,
+}
+
+impl IdentityStore {
+  /// Creates a handle to the keys of `profile` inside of `data_dir`. Nothing is read or written yet.
+  pub fn new(data_dir: &Path, profile: &str) -> IdentityStore {
+    IdentityStore {
+      dir: data_dir.join(IDENTITIES_DIR_NAME).join(profile),
+    }
+  }
+
+  fn path(&self, user_id: &str) -> PathBuf {
+    self.dir.join(format!("{}.json", user_id))
+  }
+
+  /// Reads the keys of the user with the given ID. Returns `None` if this device doesn't have any,
+  /// or if they can't be read.
+  pub fn load(&self, user_id: &str) -> Option<Identity> {
+    let contents = Zeroizing::new(fs::read(self.path(user_id)).ok()?);
+    let identity: Identity = serde_json::from_slice(&contents).ok()?;
+    Some(identity).filter(|x| x.user_id == user_id)
+  }
+
+  /// Writes the keys of the user that they belong to, creating the directory if needed.
+  pub fn save(&self, identity: &Identity) -> io::Result<()> {
+    fs::create_dir_all(&self.dir)?;
+
+    let contents = Zeroizing::new(serde_json::to_vec(identity)?);
+    write_private(&self.path(&identity.user_id), &contents)
+  }
+
+  /// Deletes the keys of the user with the given ID. It's fine if there weren't any.
+  pub fn remove(&self, user_id: &str) -> io::Result<()> {
+    match fs::remove_file(self.path(user_id)) {
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+      other => other,
+    }
+  }
+}
+
+/// Writes `contents` to `path` so that only the current user can read it. The contents go to a
+/// temporary file first, which then replaces `path`, so that a file that was written with looser
+/// permissions before doesn't keep them, and so that a crash can't leave half of a file behind.
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+  let mut temp = path.as_os_str().to_owned();
+  temp.push(".tmp");
+  let temp = PathBuf::from(temp);
+
+  let mut options = OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  #[cfg(unix)]
+  options.mode(0o600);
+
+  let mut file = options.open(&temp)?;
+  // the mode only applies to new files, and a temporary file might be left over from a crash
+  #[cfg(unix)]
+  file.set_permissions(fs::Permissions::from_mode(0o600))?;
+  file.write_all(contents)?;
+  file.sync_all()?;
+  drop(file);
+
+  fs::rename(&temp, path)
+}
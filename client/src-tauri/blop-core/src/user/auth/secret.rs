@@ -0,0 +1,72 @@
+//! Passwords and tokens. They're wiped from memory when they're dropped, and they don't show up in
+//! logs or errors unless they're read on purpose.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serializer};
+use zeroize::Zeroizing;
+
+/// A string that's zeroized when it's dropped, and that's redacted when it's formatted. The only
+/// way to read it is `expose`.
+///
+/// It can be deserialized, e.g. from command arguments, but it can't be serialized by accident:
+/// fields that have to be written somewhere use `serialize_exposed`.
+#[derive(Clone)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+  pub fn new(value: String) -> Secret {
+    Secret(Zeroizing::new(value))
+  }
+
+  /// Returns the secret itself. Don't hold on to it for longer than it takes to use it, and don't
+  /// copy it into strings that aren't zeroized.
+  pub fn expose(&self) -> &str {
+    &self.0
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl From<String> for Secret {
+  fn from(value: String) -> Self {
+    Secret::new(value)
+  }
+}
+
+impl From<Zeroizing<String>> for Secret {
+  fn from(value: Zeroizing<String>) -> Self {
+    Secret(value)
+  }
+}
+
+impl From<&str> for Secret {
+  fn from(value: &str) -> Self {
+    Secret::new(value.into())
+  }
+}
+
+impl fmt::Debug for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Secret([redacted])")
+  }
+}
+
+impl fmt::Display for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("[redacted]")
+  }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer).map(Secret::new)
+  }
+}
+
+/// Serializes the secret itself, for use with `#[serde(serialize_with = "...")]`.
+pub fn serialize_exposed<S: Serializer>(secret: &Secret, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(secret.expose())
+}
@@ -0,0 +1,569 @@
+use std::{
+  cmp::Ordering,
+  collections::HashMap,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+
+/// Words that are longer than this aren't looked up in the dictionaries.
+const MAX_WORD_LENGTH: usize = 20;
+/// The number of ways to unsubstitute a token that are tried before giving up.
+const MAX_L33T_CANDIDATES: usize = 16;
+/// Dates closer than this many years to now are all considered equally guessable.
+const MIN_YEAR_SPACE: i64 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dictionary {
+  Passwords,
+  Words,
+  Names,
+}
+
+/// Ranked word lists, most common first.
+static DICTIONARIES: Lazy<Vec<(Dictionary, HashMap<&'static str, usize>)>> = Lazy::new(|| {
+  [
+    (Dictionary::Passwords, include_str!("words/passwords.txt")),
+    (Dictionary::Words, include_str!("words/words.txt")),
+    (Dictionary::Names, include_str!("words/names.txt")),
+  ]
+  .into_iter()
+  .map(|(dictionary, list)| {
+    let ranks = list.lines().zip(1..).collect();
+    (dictionary, ranks)
+  })
+  .collect()
+});
+
+/// Characters that people substitute for letters, and the letters that they stand for.
+const L33T_TABLE: &[(char, &[char])] = &[
+  ('4', &['a']),
+  ('@', &['a']),
+  ('8', &['b']),
+  ('(', &['c']),
+  ('3', &['e']),
+  ('6', &['g']),
+  ('9', &['g']),
+  ('1', &['i', 'l']),
+  ('!', &['i']),
+  ('|', &['i', 'l']),
+  ('0', &['o']),
+  ('$', &['s']),
+  ('5', &['s']),
+  ('7', &['t']),
+  ('+', &['t']),
+  ('2', &['z']),
+];
+
+/// The rows of a QWERTY keyboard, unshifted and shifted. Each row is offset by half a key more
+/// than the one above it.
+const QWERTY: [(&str, &str); 4] = [
+  ("`1234567890-=", "~!@#$%^&*()_+"),
+  ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+  ("asdfghjkl;'", "ASDFGHJKL:\""),
+  ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+
+/// The position of each key on the keyboard, as its row and its horizontal position in half keys,
+/// along with whether it's shifted.
+static KEYBOARD: Lazy<HashMap<char, (i32, i32, bool)>> = Lazy::new(|| {
+  let mut keys = HashMap::new();
+
+  for (row, (unshifted, shifted)) in QWERTY.iter().enumerate() {
+    let row = row as i32;
+    let offset = if row == 0 { 0 } else { row + 2 };
+
+    for (col, (a, b)) in unshifted.chars().zip(shifted.chars()).enumerate() {
+      let x = offset + col as i32 * 2;
+      keys.insert(a, (row, x, false));
+      keys.insert(b, (row, x, true));
+    }
+  }
+
+  keys
+});
+
+/// The number of keys, and the average number of neighbours that each key has.
+static KEYBOARD_STATS: Lazy<(f64, f64)> = Lazy::new(|| {
+  let keys: Vec<_> = KEYBOARD.values().filter(|x| !x.2).collect();
+  let neighbours = keys
+    .iter()
+    .map(|a| keys.iter().filter(|b| adjacent(a, b)).count())
+    .sum::<usize>();
+
+  (keys.len() as f64, neighbours as f64 / keys.len() as f64)
+});
+
+fn adjacent(a: &(i32, i32, bool), b: &(i32, i32, bool)) -> bool {
+  let dx = (a.1 - b.1).abs();
+  match (a.0 - b.0).abs() {
+    0 => dx == 2,
+    1 => dx == 1,
+    _ => false,
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+  Dictionary {
+    dictionary: Dictionary,
+    rank: usize,
+    reversed: bool,
+    l33t: bool,
+  },
+  Spatial {
+    turns: usize,
+  },
+  Repeat {
+    base: String,
+    count: usize,
+  },
+  Sequence,
+  Date,
+  Bruteforce,
+}
+
+/// A part of a password that matches a pattern.
+#[derive(Clone, Debug)]
+pub struct Match {
+  /// The index of the first character.
+  pub i: usize,
+  /// The index of the last character.
+  pub j: usize,
+  pub token: String,
+  pub pattern: Pattern,
+  pub guesses: f64,
+}
+
+impl Match {
+  fn new(chars: &[char], i: usize, j: usize, pattern: Pattern, guesses: f64) -> Match {
+    Match {
+      i,
+      j,
+      token: chars[i..=j].iter().collect(),
+      pattern,
+      guesses,
+    }
+  }
+
+  /// Creates a match with no pattern, i.e. one that has to be brute forced.
+  pub fn bruteforce(chars: &[char], i: usize, j: usize) -> Match {
+    let length = (j - i + 1) as i32;
+    Match::new(chars, i, j, Pattern::Bruteforce, 10f64.powi(length))
+  }
+}
+
+/// Returns every match in `chars`. `estimate` is used to estimate the guesses of repeated tokens.
+pub fn find_matches(chars: &[char], estimate: impl Fn(&[char]) -> f64) -> Vec<Match> {
+  let mut matches = Vec::new();
+
+  dictionary_matches(chars, &mut matches);
+  spatial_matches(chars, &mut matches);
+  repeat_matches(chars, estimate, &mut matches);
+  sequence_matches(chars, &mut matches);
+  date_matches(chars, &mut matches);
+
+  matches
+}
+
+/// Returns `n` choose `k`.
+fn binomial(n: usize, k: usize) -> f64 {
+  if k > n {
+    return 0.0;
+  }
+
+  (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+/// Returns the number of ways to pick which of `a + b` characters are the `a` variants, assuming
+/// that variants are used sparingly.
+fn variations(a: usize, b: usize) -> f64 {
+  if a == 0 || b == 0 {
+    return if a + b == 0 { 1.0 } else { 2.0 };
+  }
+
+  (1..=a.min(b)).map(|i| binomial(a + b, i)).sum()
+}
+
+/// Returns the number of ways that a word could have been capitalized to get `token`.
+fn uppercase_variations(token: &[char]) -> f64 {
+  let upper = token.iter().filter(|x| x.is_uppercase()).count();
+  let lower = token.iter().filter(|x| x.is_lowercase()).count();
+
+  if upper == 0 {
+    return 1.0;
+  }
+
+  // capitalizing the first or last letter, or everything, are the first things that are tried
+  let first = token[0].is_uppercase() && upper == 1;
+  let last = token[token.len() - 1].is_uppercase() && upper == 1;
+  if first || last || lower == 0 {
+    return 2.0;
+  }
+
+  variations(upper, lower)
+}
+
+/// Returns the candidates for `token` with its l33t characters unsubstituted.
+fn unl33t(token: &[char]) -> Vec<Vec<char>> {
+  let mut candidates = vec![Vec::new()];
+
+  for c in token {
+    let letters = L33T_TABLE
+      .iter()
+      .find(|(x, _)| x == c)
+      .map(|(_, letters)| *letters);
+
+    candidates = match letters {
+      None => candidates
+        .into_iter()
+        .map(|mut x| {
+          x.push(*c);
+          x
+        })
+        .collect(),
+      Some(letters) => candidates
+        .iter()
+        .flat_map(|x| {
+          letters.iter().map(move |letter| {
+            let mut x = x.clone();
+            x.push(*letter);
+            x
+          })
+        })
+        .take(MAX_L33T_CANDIDATES)
+        .collect(),
+    };
+  }
+
+  candidates
+}
+
+/// Returns the number of ways that `word` could have been l33ted to get `token`.
+fn l33t_variations(token: &[char], word: &[char]) -> f64 {
+  let mut subbed: HashMap<char, (usize, usize)> = HashMap::new();
+
+  for (c, letter) in token.iter().zip(word) {
+    let c = c.to_lowercase().next().unwrap_or(*c);
+    if c != *letter {
+      subbed.entry(*letter).or_default().0 += 1;
+    }
+  }
+
+  for (c, letter) in token.iter().zip(word) {
+    let c = c.to_lowercase().next().unwrap_or(*c);
+    if let Some(x) = subbed.get_mut(letter) {
+      if c == *letter {
+        x.1 += 1;
+      }
+    }
+  }
+
+  subbed
+    .values()
+    .map(|(subbed, unsubbed)| variations(*subbed, *unsubbed))
+    .product()
+}
+
+/// Returns the dictionary and rank of the most common word that `word` is.
+fn lookup(word: &[char]) -> Option<(Dictionary, usize)> {
+  let word: String = word.iter().collect();
+
+  DICTIONARIES
+    .iter()
+    .filter_map(|(dictionary, ranks)| ranks.get(word.as_str()).map(|x| (*dictionary, *x)))
+    .min_by_key(|x| x.1)
+}
+
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let lower: Vec<char> = chars
+    .iter()
+    .map(|c| c.to_lowercase().next().unwrap_or(*c))
+    .collect();
+  let reversed: Vec<char> = lower.iter().rev().copied().collect();
+  let n = chars.len();
+
+  for i in 0..n {
+    for j in i..n.min(i + MAX_WORD_LENGTH) {
+      let token = &chars[i..=j];
+      let uppercase = uppercase_variations(token);
+
+      if let Some((dictionary, rank)) = lookup(&lower[i..=j]) {
+        let pattern = Pattern::Dictionary {
+          dictionary,
+          rank,
+          reversed: false,
+          l33t: false,
+        };
+        matches.push(Match::new(chars, i, j, pattern, rank as f64 * uppercase));
+      }
+
+      // the same token, read backwards
+      if let Some((dictionary, rank)) = lookup(&reversed[n - 1 - j..=n - 1 - i]) {
+        let pattern = Pattern::Dictionary {
+          dictionary,
+          rank,
+          reversed: true,
+          l33t: false,
+        };
+        let guesses = rank as f64 * uppercase * 2.0;
+        matches.push(Match::new(chars, i, j, pattern, guesses));
+      }
+
+      if !token.iter().any(|c| L33T_TABLE.iter().any(|(x, _)| x == c)) {
+        continue;
+      }
+
+      // the best of the ways to read the token's substitutions
+      let best = unl33t(&lower[i..=j])
+        .into_iter()
+        .filter_map(|word| {
+          let (dictionary, rank) = lookup(&word)?;
+          let guesses = rank as f64 * uppercase * l33t_variations(token, &word);
+          Some((dictionary, rank, guesses))
+        })
+        .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+
+      if let Some((dictionary, rank, guesses)) = best {
+        let pattern = Pattern::Dictionary {
+          dictionary,
+          rank,
+          reversed: false,
+          l33t: true,
+        };
+        matches.push(Match::new(chars, i, j, pattern, guesses));
+      }
+    }
+  }
+}
+
+/// Returns the guesses needed for a keyboard walk with the given length and number of turns.
+fn spatial_guesses(length: usize, turns: usize, shifted: usize) -> f64 {
+  let (keys, degree) = *KEYBOARD_STATS;
+  let mut guesses = 0.0;
+
+  for i in 2..=length {
+    for j in 1..=turns.min(i - 1) {
+      guesses += binomial(i - 1, j - 1) * keys * degree.powi(j as i32);
+    }
+  }
+
+  guesses * variations(shifted, length - shifted)
+}
+
+fn spatial_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let mut i = 0;
+
+  while i + 2 < chars.len() {
+    let mut j = i;
+    let mut turns = 0;
+    let mut direction = None;
+
+    while let (Some(a), Some(b)) = (
+      chars.get(j).and_then(|x| KEYBOARD.get(x)),
+      chars.get(j + 1).and_then(|x| KEYBOARD.get(x)),
+    ) {
+      if !adjacent(a, b) {
+        break;
+      }
+
+      let next = Some((b.0 - a.0, b.1 - a.1));
+      if next != direction {
+        turns += 1;
+        direction = next;
+      }
+      j += 1;
+    }
+
+    if j - i + 1 >= 3 {
+      let shifted = chars[i..=j]
+        .iter()
+        .filter(|x| KEYBOARD.get(x).map_or(false, |x| x.2))
+        .count();
+      let guesses = spatial_guesses(j - i + 1, turns, shifted);
+      matches.push(Match::new(chars, i, j, Pattern::Spatial { turns }, guesses));
+      i = j;
+    } else {
+      i += 1;
+    }
+  }
+}
+
+fn repeat_matches(chars: &[char], estimate: impl Fn(&[char]) -> f64, matches: &mut Vec<Match>) {
+  let n = chars.len();
+  let mut i = 0;
+
+  while i < n {
+    // the repeated unit that covers the most characters, preferring short units
+    let mut best: Option<(usize, usize)> = None;
+
+    for unit in 1..=(n - i) / 2 {
+      let base = &chars[i..i + unit];
+      let count = chars[i..]
+        .chunks_exact(unit)
+        .take_while(|x| *x == base)
+        .count();
+
+      let enough = if unit == 1 { count >= 3 } else { count >= 2 };
+      if enough && best.map_or(true, |(u, c)| unit * count > u * c) {
+        best = Some((unit, count));
+      }
+    }
+
+    match best {
+      Some((unit, count)) => {
+        let base = &chars[i..i + unit];
+        let guesses = estimate(base) * count as f64;
+        let pattern = Pattern::Repeat {
+          base: base.iter().collect(),
+          count,
+        };
+        matches.push(Match::new(chars, i, i + unit * count - 1, pattern, guesses));
+        i += unit * count;
+      }
+      None => i += 1,
+    }
+  }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let delta = |i: usize| chars[i + 1] as i64 - chars[i] as i64;
+  let mut i = 0;
+
+  while i + 2 < chars.len() {
+    let step = delta(i);
+    if step == 0 || step.abs() > 5 {
+      i += 1;
+      continue;
+    }
+
+    let mut j = i + 1;
+    while j + 1 < chars.len() && delta(j) == step {
+      j += 1;
+    }
+
+    if j - i + 1 < 3 {
+      i += 1;
+      continue;
+    }
+
+    let first = chars[i];
+    let base = if "aAzZ019".contains(first) {
+      4.0
+    } else if first.is_ascii_digit() {
+      10.0
+    } else {
+      26.0
+    };
+    let direction = if step < 0 { 2.0 } else { 1.0 };
+
+    let guesses = base * direction * (j - i + 1) as f64;
+    matches.push(Match::new(chars, i, j, Pattern::Sequence, guesses));
+    i = j;
+  }
+}
+
+/// Returns the current year.
+fn reference_year() -> i64 {
+  let secs = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|x| x.as_secs())
+    .unwrap_or_default();
+
+  1970 + secs as i64 / 31_556_952
+}
+
+/// Returns the year that `digits` stands for, if it looks like one.
+fn parse_year(digits: &[u32]) -> Option<i64> {
+  let year = digits.iter().fold(0, |acc, x| acc * 10 + *x as i64);
+
+  match digits.len() {
+    2 if year > 50 => Some(1900 + year),
+    2 => Some(2000 + year),
+    4 if (1000..=2050).contains(&year) => Some(year),
+    _ => None,
+  }
+}
+
+/// Returns the year of the most guessable date that `parts` could be read as.
+fn parse_date(parts: [&[u32]; 3]) -> Option<i64> {
+  let number = |x: &[u32]| x.iter().fold(0, |acc, x| acc * 10 + *x);
+  let day = |x: &[u32]| x.len() <= 2 && (1..=31).contains(&number(x));
+  let month = |x: &[u32]| x.len() <= 2 && (1..=12).contains(&number(x));
+
+  let [a, b, c] = parts;
+  let readings = [
+    // year first, then either order of month and day
+    (a, (b, c)),
+    (a, (c, b)),
+    // year last
+    (c, (a, b)),
+    (c, (b, a)),
+  ];
+
+  readings
+    .iter()
+    .filter(|(_, (m, d))| month(m) && day(d))
+    .filter_map(|(y, _)| parse_year(y))
+    .min_by_key(|x| (x - reference_year()).abs())
+}
+
+fn date_guesses(year: i64, separator: bool) -> f64 {
+  let space = (year - reference_year()).abs().max(MIN_YEAR_SPACE) as f64;
+  space * 365.0 * if separator { 4.0 } else { 1.0 }
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let n = chars.len();
+
+  for i in 0..n {
+    for j in i + 3..n.min(i + 10) {
+      let token = &chars[i..=j];
+
+      // dates without separators, like 13071998
+      if token.len() <= 8 && token.iter().all(|c| c.is_ascii_digit()) {
+        let digits: Vec<u32> = token.iter().filter_map(|x| x.to_digit(10)).collect();
+
+        if token.len() == 4 {
+          if let Some(year) = parse_year(&digits).filter(|x| (1900..=2050).contains(x)) {
+            let guesses = date_guesses(year, false) / 365.0;
+            matches.push(Match::new(chars, i, j, Pattern::Date, guesses));
+          }
+        }
+
+        let year = (1..digits.len())
+          .flat_map(|k| (k + 1..digits.len()).map(move |l| (k, l)))
+          .filter_map(|(k, l)| parse_date([&digits[..k], &digits[k..l], &digits[l..]]))
+          .min_by_key(|x| (x - reference_year()).abs());
+
+        if let Some(year) = year {
+          let guesses = date_guesses(year, false);
+          matches.push(Match::new(chars, i, j, Pattern::Date, guesses));
+        }
+
+        continue;
+      }
+
+      // dates with separators, like 13/7/98
+      let separator = match token.iter().find(|c| !c.is_ascii_digit()) {
+        Some(x) if " /\\_.-".contains(*x) => *x,
+        _ => continue,
+      };
+
+      let parts: Vec<Vec<u32>> = token
+        .split(|c| *c == separator)
+        .map(|x| x.iter().filter_map(|x| x.to_digit(10)).collect())
+        .collect();
+      let digits = parts.iter().map(|x| x.len()).sum::<usize>();
+
+      if parts.len() != 3 || digits != token.len() - 2 || parts.iter().any(|x| x.is_empty()) {
+        continue;
+      }
+
+      if let Some(year) = parse_date([&parts[0], &parts[1], &parts[2]]) {
+        let guesses = date_guesses(year, true);
+        matches.push(Match::new(chars, i, j, Pattern::Date, guesses));
+      }
+    }
+  }
+}
@@ -0,0 +1,260 @@
+//! Estimates how many guesses it would take to crack a password, by finding the cheapest way to
+//! build it out of dictionary words, keyboard walks, repeats, sequences, dates and brute force.
+//! This is a simplified version of the approach that zxcvbn takes, and runs entirely offline.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use ts_rs::TS;
+
+use self::matching::{find_matches, Dictionary, Match, Pattern};
+
+mod matching;
+
+/// Only this many characters are analyzed. Anything longer is plenty strong anyway.
+const MAX_ANALYZED_LENGTH: usize = 100;
+/// Every pattern after the first is assumed to cost at least this many guesses.
+const MIN_GUESSES_PER_EXTRA_MATCH: f64 = 10000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+/// The number of guesses below which a password gets each score.
+const SCORE_THRESHOLDS: [f64; 4] = [1e3, 1e6, 1e8, 1e10];
+
+#[derive(Clone, Debug, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/password-strength.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordStrength {
+  /// The estimated number of guesses needed to crack the password.
+  pub guesses: f64,
+  /// The base 2 logarithm of `guesses`.
+  pub entropy: f64,
+  /// From 0, which is easy to guess, to 4, which is very hard to guess.
+  pub score: u8,
+  /// Why the password is easy to guess, if it is.
+  pub warning: Option<String>,
+  pub suggestions: Vec<String>,
+}
+
+/// A step of the cheapest way found so far to build a prefix out of a number of matches.
+#[derive(Clone)]
+struct Step {
+  m: Match,
+  /// The product of the guesses of this match and the ones before it.
+  product: f64,
+  guesses: f64,
+}
+
+fn factorial(n: usize) -> f64 {
+  (1..=n).map(|x| x as f64).product()
+}
+
+/// Returns the cheapest sequence of matches that covers `chars`, and how many guesses it takes.
+fn most_guessable(chars: &[char]) -> (f64, Vec<Match>) {
+  let n = chars.len();
+  if n == 0 {
+    return (1.0, vec![]);
+  }
+
+  let mut matches = find_matches(chars, |x| most_guessable(x).0);
+  for m in &mut matches {
+    if m.j - m.i + 1 < n {
+      let min = if m.i == m.j {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+      } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR
+      };
+      m.guesses = m.guesses.max(min);
+    }
+  }
+
+  // the best step that ends at each index, keyed by the number of matches used so far
+  let mut optimal: Vec<HashMap<usize, Step>> = vec![HashMap::new(); n];
+
+  let update = |optimal: &mut Vec<HashMap<usize, Step>>, m: Match, length: usize, product: f64| {
+    let k = m.j;
+    let product = product * m.guesses;
+    let guesses = factorial(length) * product + MIN_GUESSES_PER_EXTRA_MATCH.powi(length as i32 - 1);
+
+    // a shorter sequence that's at least as good makes this one pointless
+    if optimal[k]
+      .iter()
+      .any(|(l, x)| *l <= length && x.guesses <= guesses)
+    {
+      return;
+    }
+
+    optimal[k].insert(
+      length,
+      Step {
+        m,
+        product,
+        guesses,
+      },
+    );
+  };
+
+  for k in 0..n {
+    for m in matches.iter().filter(|x| x.j == k) {
+      if m.i == 0 {
+        update(&mut optimal, m.clone(), 1, 1.0);
+        continue;
+      }
+
+      for (length, step) in optimal[m.i - 1].clone() {
+        update(&mut optimal, m.clone(), length + 1, step.product);
+      }
+    }
+
+    for i in 0..=k {
+      let m = Match::bruteforce(chars, i, k);
+      if i == 0 {
+        update(&mut optimal, m, 1, 1.0);
+        continue;
+      }
+
+      for (length, step) in optimal[i - 1].clone() {
+        // consecutive brute force matches are the same as one longer one
+        if step.m.pattern != Pattern::Bruteforce {
+          update(&mut optimal, m.clone(), length + 1, step.product);
+        }
+      }
+    }
+  }
+
+  let (mut length, best) = optimal[n - 1]
+    .iter()
+    .min_by(|a, b| a.1.guesses.partial_cmp(&b.1.guesses).unwrap())
+    .map(|(l, x)| (*l, x.clone()))
+    .unwrap();
+
+  // walk back through the steps to find the sequence
+  let mut sequence = vec![best.m.clone()];
+  let mut i = best.m.i;
+  while i > 0 {
+    length -= 1;
+    let step = &optimal[i - 1][&length];
+    sequence.push(step.m.clone());
+    i = step.m.i;
+  }
+  sequence.reverse();
+
+  (best.guesses, sequence)
+}
+
+/// Returns the warning and suggestions for a password with the given matches.
+fn feedback(score: u8, sequence: &[Match]) -> (Option<String>, Vec<String>) {
+  if sequence.is_empty() {
+    return (
+      None,
+      vec![
+        "Use a few words, and avoid common phrases.".into(),
+        "No need for symbols, digits, or uppercase letters.".into(),
+      ],
+    );
+  }
+
+  if score > 2 {
+    return (None, vec![]);
+  }
+
+  let longest = sequence
+    .iter()
+    .max_by_key(|x| x.token.chars().count())
+    .unwrap();
+  let mut suggestions = vec!["Add another word or two. Uncommon words are better.".into()];
+
+  let warning = match &longest.pattern {
+    Pattern::Dictionary {
+      dictionary,
+      rank,
+      reversed,
+      l33t,
+    } => {
+      let alone = sequence.len() == 1;
+      let first = longest.token.chars().next().unwrap();
+
+      if first.is_uppercase() {
+        if longest.token.chars().all(|x| !x.is_lowercase()) {
+          suggestions.push("All-uppercase is almost as easy to guess as all-lowercase.".into());
+        } else {
+          suggestions.push("Capitalization doesn't help very much.".into());
+        }
+      }
+
+      if *reversed && longest.token.chars().count() >= 4 {
+        suggestions.push("Reversed words aren't much harder to guess.".into());
+      }
+
+      if *l33t {
+        suggestions
+          .push("Predictable substitutions like '@' instead of 'a' don't help very much.".into());
+      }
+
+      match dictionary {
+        Dictionary::Passwords if alone && !reversed && !l33t => Some(if *rank <= 10 {
+          "This is a top-10 common password.".into()
+        } else if *rank <= 100 {
+          "This is a top-100 common password.".into()
+        } else {
+          "This is a very common password.".into()
+        }),
+        Dictionary::Passwords => Some("This is similar to a commonly used password.".into()),
+        Dictionary::Words if alone => Some("A word by itself is easy to guess.".into()),
+        Dictionary::Words => None,
+        Dictionary::Names if alone => {
+          Some("Names and surnames by themselves are easy to guess.".into())
+        }
+        Dictionary::Names => Some("Common names and surnames are easy to guess.".into()),
+      }
+    }
+    Pattern::Spatial { turns } => {
+      suggestions.push("Use a longer keyboard pattern with more turns.".into());
+
+      Some(if *turns == 1 {
+        "Straight rows of keys are easy to guess.".into()
+      } else {
+        "Short keyboard patterns are easy to guess.".into()
+      })
+    }
+    Pattern::Repeat { base, .. } => {
+      suggestions.push("Avoid repeated words and characters.".into());
+
+      Some(if base.chars().count() == 1 {
+        "Repeats like \"aaa\" are easy to guess.".into()
+      } else {
+        "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\".".into()
+      })
+    }
+    Pattern::Sequence => {
+      suggestions.push("Avoid sequences.".into());
+      Some("Sequences like abc or 6543 are easy to guess.".into())
+    }
+    Pattern::Date => {
+      suggestions.push("Avoid dates and years that are associated with you.".into());
+      Some("Dates are often easy to guess.".into())
+    }
+    Pattern::Bruteforce => None,
+  };
+
+  (warning, suggestions)
+}
+
+/// Returns the estimated strength of `password`.
+pub fn estimate_strength(password: &str) -> PasswordStrength {
+  let chars: Vec<char> = password.chars().take(MAX_ANALYZED_LENGTH).collect();
+  let (guesses, sequence) = most_guessable(&chars);
+
+  let score = SCORE_THRESHOLDS
+    .iter()
+    .position(|x| guesses < *x)
+    .unwrap_or(SCORE_THRESHOLDS.len()) as u8;
+  let (warning, suggestions) = feedback(score, &sequence);
+
+  PasswordStrength {
+    guesses,
+    entropy: guesses.log2(),
+    score,
+    warning,
+    suggestions,
+  }
+}
@@ -0,0 +1,108 @@
+//! Keeps track of failed logins, so that guessing passwords gets slower with every wrong one
+//! instead of hammering the server.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use reqwest::{header::RETRY_AFTER, Response};
+use tokio::time::Instant;
+
+/// Failed logins for one username.
+struct Attempts {
+  failures: u32,
+  /// When the next login is allowed.
+  until: Instant,
+}
+
+pub struct LoginThrottle {
+  /// How many logins can fail in a row before there's a cooldown.
+  free_attempts: u32,
+  /// The cooldown after the first login past the free ones fails. Every failure after that doubles
+  /// it.
+  base: Duration,
+  max: Duration,
+  /// Keyed by lowercase username, so that changing the case doesn't get around the cooldown.
+  attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl LoginThrottle {
+  pub fn new(free_attempts: u32, base: Duration, max: Duration) -> LoginThrottle {
+    LoginThrottle {
+      free_attempts,
+      base,
+      max,
+      attempts: Default::default(),
+    }
+  }
+
+  /// Returns how long to wait before `username` can try to log in again, or `None` if they can
+  /// try now.
+  pub fn retry_after(&self, username: &str) -> Option<Duration> {
+    let attempts = self.attempts.lock().unwrap();
+    let until = attempts.get(&username.to_lowercase())?.until;
+
+    let now = Instant::now();
+    if until > now {
+      Some(until - now)
+    } else {
+      None
+    }
+  }
+
+  /// Records a failed login for `username`, and returns the cooldown before the next one.
+  pub fn fail(&self, username: &str) -> Duration {
+    let mut attempts = self.attempts.lock().unwrap();
+    let now = Instant::now();
+
+    // failures are forgotten once there hasn't been one for as long as the longest cooldown
+    let max = self.max;
+    attempts.retain(|_, x| x.until + max > now);
+
+    let entry = attempts.entry(username.to_lowercase()).or_insert(Attempts {
+      failures: 0,
+      until: now,
+    });
+    entry.failures += 1;
+
+    let cooldown = self.cooldown(entry.failures);
+    entry.until = now + cooldown;
+    cooldown
+  }
+
+  /// Makes `username` wait for `delay` before logging in again, e.g. because the server said so.
+  /// This doesn't shorten a longer cooldown.
+  pub fn lock(&self, username: &str, delay: Duration) {
+    let mut attempts = self.attempts.lock().unwrap();
+    let until = Instant::now() + delay;
+
+    let entry = attempts
+      .entry(username.to_lowercase())
+      .or_insert(Attempts { failures: 0, until });
+    entry.until = entry.until.max(until);
+  }
+
+  /// Forgets the failed logins for `username`, e.g. because they got their password right.
+  pub fn clear(&self, username: &str) {
+    self
+      .attempts
+      .lock()
+      .unwrap()
+      .remove(&username.to_lowercase());
+  }
+
+  fn cooldown(&self, failures: u32) -> Duration {
+    if failures <= self.free_attempts {
+      return Duration::ZERO;
+    }
+
+    // capped so that the shift can't overflow; the max takes over long before then anyway
+    let doublings = (failures - self.free_attempts - 1).min(16);
+    self.base.saturating_mul(1 << doublings).min(self.max)
+  }
+}
+
+/// Returns how long a `429 Too Many Requests` response says to wait. Only the delay in seconds
+/// form of `Retry-After` is understood, not the HTTP date one.
+pub fn parse_retry_after(response: &Response) -> Option<Duration> {
+  let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+  value.trim().parse().ok().map(Duration::from_secs)
+}
@@ -0,0 +1,322 @@
+//! Time-based one-time passwords (RFC 6238), the second step of logging in to accounts that have
+//! two-factor authentication. Codes are generated and checked here, so that enrollment can check
+//! the first code before the secret is sent to the server.
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use ts_rs::TS;
+use url::Url;
+use zeroize::Zeroizing;
+
+use crate::{
+  common::BadRequestResponseBody,
+  user::{auth::secret::Secret, AuthenticationSuccessResponse},
+};
+
+/// The name that authenticator apps show next to the code.
+const ISSUER: &str = "Blop";
+/// How many seconds every code is valid for.
+pub const STEP: u64 = 30;
+/// The number of digits in a code.
+pub const DIGITS: u32 = 6;
+/// The number of bytes in a secret. RFC 4226 recommends 160 bits.
+const SECRET_LENGTH: usize = 20;
+/// How many steps a code can be early or late by, to allow for clock drift and slow typing.
+const WINDOW: u64 = 1;
+/// The number of recovery codes that are generated when two-factor authentication is set up.
+const RECOVERY_CODE_COUNT: usize = 10;
+/// The number of characters in a recovery code, not counting the dash in the middle.
+const RECOVERY_CODE_LENGTH: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// The hash function that codes are made with. Authenticator apps generally only support SHA-1,
+/// which is what we use, but RFC 6238 defines the others too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+  Sha1,
+  Sha256,
+  Sha512,
+}
+
+impl TotpAlgorithm {
+  fn mac(&self, key: &[u8], message: &[u8]) -> Vec<u8> {
+    fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+      let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+      mac.update(message);
+      mac.finalize().into_bytes().to_vec()
+    }
+
+    match self {
+      TotpAlgorithm::Sha1 => mac::<Hmac<Sha1>>(key, message),
+      TotpAlgorithm::Sha256 => mac::<Hmac<Sha256>>(key, message),
+      TotpAlgorithm::Sha512 => mac::<Hmac<Sha512>>(key, message),
+    }
+  }
+}
+
+/// Returns the HOTP code (RFC 4226) for `counter`, zero-padded to `digits` digits.
+pub fn hotp(secret: &[u8], counter: u64, algorithm: TotpAlgorithm, digits: u32) -> String {
+  let hash = algorithm.mac(secret, &counter.to_be_bytes());
+
+  // dynamic truncation: the last nibble picks where the 31 bits are taken from
+  let offset = (hash[hash.len() - 1] & 0xf) as usize;
+  let binary = u32::from_be_bytes([
+    hash[offset] & 0x7f,
+    hash[offset + 1],
+    hash[offset + 2],
+    hash[offset + 3],
+  ]);
+
+  let code = binary as u64 % 10u64.pow(digits);
+  format!("{:0width$}", code, width = digits as usize)
+}
+
+/// Returns the TOTP code for the time `unix_secs`, in seconds since the Unix epoch.
+pub fn totp(secret: &[u8], unix_secs: u64, algorithm: TotpAlgorithm, digits: u32) -> String {
+  hotp(secret, unix_secs / STEP, algorithm, digits)
+}
+
+/// Returns whether `code` is the 6 digit SHA-1 code for `unix_secs`, or for a step next to it.
+pub fn verify(secret: &[u8], code: &str, unix_secs: u64) -> bool {
+  let code = code.trim();
+  let counter = unix_secs / STEP;
+
+  // every step is checked, so that the time it takes doesn't say which one matched
+  (counter.saturating_sub(WINDOW)..=counter + WINDOW)
+    .map(|x| {
+      let expected = Zeroizing::new(hotp(secret, x, TotpAlgorithm::Sha1, DIGITS));
+      constant_time_eq(expected.as_bytes(), code.as_bytes())
+    })
+    .fold(false, |matched, x| matched | x)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns a new random secret.
+pub fn generate_secret() -> Zeroizing<Vec<u8>> {
+  let mut secret = Zeroizing::new(vec![0; SECRET_LENGTH]);
+  OsRng.fill_bytes(&mut secret);
+  secret
+}
+
+/// Encodes `data` as unpadded base32 (RFC 4648), which is how secrets are shown to people and
+/// authenticator apps.
+pub fn base32_encode(data: &[u8]) -> String {
+  let mut encoded = String::with_capacity((data.len() * 8 + 4) / 5);
+  let mut buffer = 0u32;
+  let mut bits = 0;
+
+  for &byte in data {
+    buffer = (buffer << 8) | byte as u32;
+    bits += 8;
+    while bits >= 5 {
+      bits -= 5;
+      encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+    }
+  }
+
+  if bits > 0 {
+    encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+  }
+
+  encoded
+}
+
+/// Decodes base32, ignoring case, padding and spaces. Returns `None` if it isn't base32.
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+  let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+  let mut buffer = 0u32;
+  let mut bits = 0;
+
+  for c in encoded.chars().filter(|x| !matches!(x, '=' | ' ')) {
+    let value = BASE32_ALPHABET
+      .iter()
+      .position(|&x| x as char == c.to_ascii_uppercase())?;
+    buffer = (buffer << 5) | value as u32;
+    bits += 5;
+    if bits >= 8 {
+      bits -= 8;
+      decoded.push((buffer >> bits) as u8);
+    }
+  }
+
+  Some(decoded)
+}
+
+/// Returns the `otpauth://` URI that authenticator apps scan from a QR code.
+pub fn otpauth_uri(account: &str, secret: &[u8]) -> String {
+  let mut uri = Url::parse("otpauth://totp/").expect("the base URI is valid");
+  uri.set_path(&format!("{}:{}", ISSUER, account));
+  uri
+    .query_pairs_mut()
+    .append_pair("secret", &base32_encode(secret))
+    .append_pair("issuer", ISSUER)
+    .append_pair("algorithm", "SHA1")
+    .append_pair("digits", &DIGITS.to_string())
+    .append_pair("period", &STEP.to_string());
+  uri.into()
+}
+
+/// Returns new recovery codes, which can each be used once instead of a code when the
+/// authenticator app isn't around. They look like `abcde-fghij`.
+pub fn generate_recovery_codes() -> Vec<Secret> {
+  (0..RECOVERY_CODE_COUNT)
+    .map(|_| {
+      let mut bytes = Zeroizing::new([0u8; RECOVERY_CODE_LENGTH]);
+      OsRng.fill_bytes(&mut *bytes);
+
+      let code: Zeroizing<String> = Zeroizing::new(
+        bytes
+          .iter()
+          .map(|x| BASE32_ALPHABET[(x & 0x1f) as usize].to_ascii_lowercase() as char)
+          .collect(),
+      );
+      let (first, second) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+      Secret::new(format!("{}-{}", first, second))
+    })
+    .collect()
+}
+
+/// A secret and recovery codes that were generated for the logged in user, but that the server
+/// doesn't know about until the first code is confirmed.
+pub struct PendingEnrollment {
+  pub secret: Zeroizing<Vec<u8>>,
+  pub recovery_codes: Vec<Secret>,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/totp-enrollment.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum TotpEnrollmentResult {
+  /// Everything that has to be shown to the user. Nothing changes until a code is confirmed.
+  Success {
+    /// The secret, in base32, for typing into an authenticator app.
+    secret: String,
+    /// The same secret as an `otpauth://` URI, for showing as a QR code.
+    uri: String,
+    #[serde(rename = "recoveryCodes")]
+    recovery_codes: Vec<String>,
+  },
+  NotLoggedIn,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/confirm-totp.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ConfirmTotpResult {
+  /// Logging in needs a code from now on.
+  Enabled,
+  /// The code doesn't match the secret. The enrollment can be confirmed again.
+  WrongCode,
+  /// There's no enrollment to confirm.
+  NotStarted,
+  AlreadyEnabled,
+  NotLoggedIn,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/submit-totp.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum SubmitTotpResult {
+  Authorized,
+  /// Neither a valid code nor an unused recovery code. It can be tried again, a few times.
+  WrongCode,
+  /// The challenge timed out or had too many wrong codes, so the password has to be given again.
+  ChallengeExpired,
+}
+
+/// The response to a login that needs a code.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpChallengeResponse {
+  pub challenge_id: String,
+}
+
+/// Finishes a login with a code or a recovery code. On success, the session is returned.
+pub async fn submit_totp(
+  http: &Client,
+  url: &str,
+  challenge_id: &str,
+  code: &Secret,
+) -> Result<Result<AuthenticationSuccessResponse, SubmitTotpResult>, String> {
+  #[derive(Serialize)]
+  #[serde(rename_all = "camelCase")]
+  struct Body<'a> {
+    challenge_id: &'a str,
+    code: &'a str,
+  }
+
+  let body = Body {
+    challenge_id,
+    code: code.expose(),
+  };
+
+  match http.post(url).json(&body).send().await {
+    Ok(x) => match x.status() {
+      StatusCode::OK => match x.json().await {
+        Ok(x) => Ok(Ok(x)),
+        Err(_) => Err("malformed server response".into()),
+      },
+      StatusCode::UNAUTHORIZED => Ok(Err(SubmitTotpResult::WrongCode)),
+      StatusCode::NOT_FOUND => Ok(Err(SubmitTotpResult::ChallengeExpired)),
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+/// Turns on two-factor authentication for the user that `token` belongs to. The server checks
+/// `code` too, in case the secret got mangled on the way.
+pub async fn enable_totp(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  enrollment: &PendingEnrollment,
+  code: &Secret,
+) -> Result<ConfirmTotpResult, String> {
+  #[derive(Serialize)]
+  #[serde(rename_all = "camelCase")]
+  struct Body<'a> {
+    secret: &'a str,
+    code: &'a str,
+    recovery_codes: Vec<&'a str>,
+  }
+
+  let secret = Zeroizing::new(base32_encode(&enrollment.secret));
+  let body = Body {
+    secret: &secret,
+    code: code.expose(),
+    recovery_codes: enrollment
+      .recovery_codes
+      .iter()
+      .map(|x| x.expose())
+      .collect(),
+  };
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => Ok(ConfirmTotpResult::Enabled),
+      StatusCode::UNAUTHORIZED => Ok(ConfirmTotpResult::NotLoggedIn),
+      StatusCode::FORBIDDEN => Ok(ConfirmTotpResult::WrongCode),
+      StatusCode::BAD_REQUEST => match x.json::<BadRequestResponseBody>().await {
+        Ok(x) if x.typ == "ENABLED" => Ok(ConfirmTotpResult::AlreadyEnabled),
+        Ok(_) => Err("invalid request".into()),
+        Err(_) => Err("malformed server response".into()),
+      },
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
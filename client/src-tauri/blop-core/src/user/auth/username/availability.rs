@@ -0,0 +1,145 @@
+//! Checks whether usernames are taken while they're being typed, without flooding the server.
+//! Checks wait a moment before asking, a new check cancels the one before it, and answers are
+//! cached for a while. Usernames that only differ by case or lookalike characters are the same
+//! username, so they share their answers.
+
+use std::{
+  collections::HashMap,
+  future::Future,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+  },
+  time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+  sync::Notify,
+  time::{sleep, Instant},
+};
+use ts_rs::TS;
+
+use super::{UsernameCriteria, UsernameValidation};
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/availability.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum Availability {
+  Available,
+  Taken,
+  /// Nobody can take the username, so the server wasn't asked.
+  Invalid(UsernameCriteria),
+  /// We couldn't find out, e.g. because the server is unreachable.
+  Unknown {
+    error: String,
+  },
+  /// A newer check started before this one finished, so this one was cancelled and its result
+  /// should be ignored.
+  Superseded,
+}
+
+impl Availability {
+  fn from_exists(exists: bool) -> Availability {
+    if exists {
+      Availability::Taken
+    } else {
+      Availability::Available
+    }
+  }
+}
+
+pub struct AvailabilityChecker {
+  /// How long a check waits for another one before asking the server.
+  debounce: Duration,
+  /// How long answers are cached.
+  ttl: Duration,
+  /// Incremented by every check, so that a check can tell when it's been superseded.
+  generation: AtomicU64,
+  superseded: Notify,
+  /// Whether each canonical username exists, and when we found out.
+  cache: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl AvailabilityChecker {
+  pub fn new(debounce: Duration, ttl: Duration) -> AvailabilityChecker {
+    AvailabilityChecker {
+      debounce,
+      ttl,
+      generation: AtomicU64::new(0),
+      superseded: Notify::new(),
+      cache: Default::default(),
+    }
+  }
+
+  /// Returns whether the username that was validated as `validation` is available. Unless it's
+  /// invalid or the answer is cached, this waits for the debounce delay and then calls `exists` to
+  /// ask the server, giving up if another check starts in the meantime.
+  pub async fn check<F, Fut>(&self, validation: UsernameValidation, exists: F) -> Availability
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<bool, String>>,
+  {
+    let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    self.superseded.notify_waiters();
+
+    let canonical = match validation {
+      UsernameValidation::Valid(x) => x.canonical,
+      UsernameValidation::Invalid(x) => return Availability::Invalid(x),
+    };
+
+    if let Some(x) = self.cached(&canonical) {
+      return Availability::from_exists(x);
+    }
+
+    let lookup = async {
+      sleep(self.debounce).await;
+      exists().await
+    };
+
+    tokio::select! {
+      result = lookup => match result {
+        Ok(x) => {
+          self.insert(&canonical, x);
+          Availability::from_exists(x)
+        }
+        Err(error) => Availability::Unknown { error },
+      },
+      _ = self.wait_until_superseded(generation) => Availability::Superseded,
+    }
+  }
+
+  /// Forgets every cached answer.
+  pub fn clear(&self) {
+    self.cache.lock().unwrap().clear();
+  }
+
+  fn cached(&self, username: &str) -> Option<bool> {
+    match self.cache.lock().unwrap().get(username) {
+      Some((exists, at)) if at.elapsed() < self.ttl => Some(*exists),
+      _ => None,
+    }
+  }
+
+  fn insert(&self, username: &str, exists: bool) {
+    let mut cache = self.cache.lock().unwrap();
+
+    // expired answers are only useful for taking up memory
+    let ttl = self.ttl;
+    cache.retain(|_, (_, at)| at.elapsed() < ttl);
+
+    cache.insert(username.into(), (exists, Instant::now()));
+  }
+
+  async fn wait_until_superseded(&self, generation: u64) {
+    loop {
+      // this has to exist before the check, or a notification could slip in between
+      let notified = self.superseded.notified();
+      if self.generation.load(Ordering::SeqCst) != generation {
+        return;
+      }
+
+      notified.await;
+    }
+  }
+}
@@ -0,0 +1,72 @@
+//! Checks usernames against reserved words and an embedded list of profanity. Usernames are
+//! compared in a folded form, so that case, separators, leetspeak and lookalike characters don't
+//! get around either list.
+
+use once_cell::sync::Lazy;
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::skeleton;
+
+use super::SPECIAL_USERNAME_CHARS;
+
+/// The folded profanity, and whether each word only matches a whole part of a username. Words
+/// that start with `=` in the list are too short or too common inside other words to match
+/// anywhere, e.g. "ass" in "classic".
+static PROFANITY: Lazy<Vec<(String, bool)>> = Lazy::new(|| {
+  include_str!("profanity.txt")
+    .lines()
+    .map(|x| match x.strip_prefix('=') {
+      Some(x) => (fold(x), true),
+      None => (fold(x), false),
+    })
+    .collect()
+});
+
+/// Returns the letter that `c` commonly stands in for, if it's a digit or symbol.
+fn unleet(c: char) -> char {
+  match c {
+    '0' => 'o',
+    '1' | '!' | '|' => 'i',
+    '3' => 'e',
+    '4' | '@' => 'a',
+    '5' | '$' => 's',
+    '7' | '+' => 't',
+    '8' => 'b',
+    '9' => 'g',
+    x => x,
+  }
+}
+
+/// Returns the form of `s` that's compared against the lists.
+fn fold(s: &str) -> String {
+  let unleeted: String = s
+    .nfkc()
+    .flat_map(char::to_lowercase)
+    .map(unleet)
+    .filter(|x| x.is_alphanumeric())
+    .collect();
+
+  skeleton(&unleeted).flat_map(char::to_lowercase).collect()
+}
+
+/// Returns `true` if `username` is one of the `reserved` words.
+pub fn is_reserved(username: &str, reserved: &[String]) -> bool {
+  let folded = fold(username);
+  reserved.iter().any(|x| fold(x) == folded)
+}
+
+/// Returns `true` if `username` contains profanity.
+pub fn is_profane(username: &str) -> bool {
+  let folded = fold(username);
+  let parts: Vec<String> = username
+    .split(|x| SPECIAL_USERNAME_CHARS.contains(x))
+    .map(fold)
+    .collect();
+
+  PROFANITY.iter().any(|(word, whole)| {
+    if *whole {
+      folded == *word || parts.contains(word)
+    } else {
+      folded.contains(word.as_str())
+    }
+  })
+}
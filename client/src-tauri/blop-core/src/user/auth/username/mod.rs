@@ -0,0 +1,187 @@
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{skeleton, GeneralSecurityProfile, MixedScript};
+use unicode_segmentation::UnicodeSegmentation;
+
+use self::filter::{is_profane, is_reserved};
+
+pub mod availability;
+mod filter;
+
+const SPECIAL_USERNAME_CHARS: &str = "-_";
+const MIN_USERNAME_LENGTH: usize = 4;
+const MAX_USERNAME_LENGTH: usize = 24;
+
+/// Returns true if `c` is a valid character for a username.
+fn is_valid_username_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || SPECIAL_USERNAME_CHARS.contains(c)
+}
+
+/// Returns true if `c` is a valid character for a username in Unicode mode, i.e. a letter, digit
+/// or mark from a script that's recommended for identifiers.
+fn is_valid_unicode_username_char(c: char) -> bool {
+  c.identifier_allowed() || SPECIAL_USERNAME_CHARS.contains(c)
+}
+
+/// The usernames that are reserved by default.
+const RESERVED_USERNAMES: &[&str] = &[
+  "admin",
+  "administrator",
+  "anonymous",
+  "blop",
+  "deleted",
+  "everyone",
+  "moderator",
+  "null",
+  "official",
+  "root",
+  "security",
+  "server",
+  "staff",
+  "support",
+  "system",
+  "undefined",
+  "unknown",
+  "username",
+];
+
+/// How usernames are validated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UsernamePolicy {
+  /// Allows letters and digits from any script, rather than just ASCII. Usernames are NFKC
+  /// normalized first, their length is counted in graphemes, and ones that mix scripts or could
+  /// be mistaken for a Latin username are rejected. The server has to allow them too.
+  pub unicode: bool,
+  /// Usernames that nobody can take. Case, separators, leetspeak and lookalike characters are
+  /// ignored, so "Adm1n" is reserved if "admin" is. This should match the server's list.
+  pub reserved: Vec<String>,
+}
+
+impl Default for UsernamePolicy {
+  fn default() -> Self {
+    UsernamePolicy {
+      unicode: false,
+      reserved: RESERVED_USERNAMES.iter().map(|x| x.to_string()).collect(),
+    }
+  }
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(
+  export,
+  export_to = "../../src/types/user/error/username-validation.d.ts"
+)]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum UsernameValidation {
+  Valid(UsernameCriteria),
+  Invalid(UsernameCriteria),
+}
+
+impl UsernameValidation {
+  /// Creates a new `UsernameValidation` struct.
+  pub fn new(criteria: UsernameCriteria) -> UsernameValidation {
+    if criteria.length
+      && criteria.charset
+      && criteria.single_script
+      && criteria.not_confusable
+      && criteria.not_reserved
+      && criteria.not_profane
+    {
+      UsernameValidation::Valid(criteria)
+    } else {
+      UsernameValidation::Invalid(criteria)
+    }
+  }
+
+  /// Returns `true` if this validation is `Valid`.
+  #[inline]
+  pub fn is_valid(&self) -> bool {
+    matches!(*self, Self::Valid(_))
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize)]
+#[ts(
+  export,
+  export_to = "../../src/types/user/error/validate-username.d.ts"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct UsernameCriteria {
+  pub length: bool,
+  pub charset: bool,
+  /// Whether the username doesn't mix scripts, like Latin and Cyrillic.
+  pub single_script: bool,
+  /// Whether the username can't be mistaken for a Latin one, like "рау" in Cyrillic.
+  pub not_confusable: bool,
+  pub not_reserved: bool,
+  /// Whether the username doesn't contain profanity, even disguised with leetspeak.
+  pub not_profane: bool,
+  /// The form of the username that's compared to check if it's taken. See `canonical_username`.
+  pub canonical: String,
+}
+
+/// Returns the form of `username` that's used to compare usernames. Usernames that only differ
+/// by case, compatibility characters or confusable characters have the same canonical form.
+pub fn canonical_username(username: &str) -> String {
+  let folded: String = username.nfkc().collect::<String>().to_lowercase();
+  skeleton(&folded).collect()
+}
+
+pub fn validate_username(username: &str, policy: &UsernamePolicy) -> UsernameValidation {
+  let canonical = canonical_username(username);
+
+  if !policy.unicode {
+    return UsernameValidation::new(UsernameCriteria {
+      length: (MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&username.len()),
+      charset: username.chars().all(is_valid_username_char),
+      single_script: true,
+      not_confusable: true,
+      not_reserved: !is_reserved(username, &policy.reserved),
+      not_profane: !is_profane(username),
+      canonical,
+    });
+  }
+
+  let normalized: String = username.nfkc().collect();
+  let length = normalized.graphemes(true).count();
+
+  UsernameValidation::new(UsernameCriteria {
+    length: (MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&length),
+    charset: normalized.chars().all(is_valid_unicode_username_char),
+    single_script: normalized.is_single_script(),
+    // a username that isn't ASCII, but whose skeleton is, is made up entirely of lookalikes
+    not_confusable: normalized.is_ascii() || !canonical.is_ascii(),
+    not_reserved: !is_reserved(username, &policy.reserved),
+    not_profane: !is_profane(username),
+    canonical,
+  })
+}
+
+/// Returns true if the user named `username` exists. Invalid usernames can't exist, so the
+/// server isn't asked about them.
+pub async fn user_exists(
+  http: &Client,
+  url: &str,
+  username: &str,
+  policy: &UsernamePolicy,
+) -> Result<bool, String> {
+  if !validate_username(username, policy).is_valid() {
+    return Ok(false);
+  }
+
+  let resp = http
+    .get(url)
+    .query(&[("username", username)])
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => Ok(true),
+    StatusCode::NOT_FOUND => Ok(false),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
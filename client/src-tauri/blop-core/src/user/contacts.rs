@@ -0,0 +1,258 @@
+//! Friends and blocked users. The server is the source of truth, but the last list that we saw is
+//! kept in the data directory, so that contacts can be shown and blocked users filtered while
+//! offline.
+
+use std::{
+  collections::HashMap,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ts_rs::TS;
+
+use crate::{common::BadRequestResponseBody, user::auth::secret::Secret};
+
+/// The name of the file that contacts are saved to, inside of the data directory.
+const CONTACTS_FILE_NAME: &str = "contacts.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/contact-status.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum ContactStatus {
+  Friend,
+  /// They sent us a friend request.
+  Incoming,
+  /// We sent them a friend request.
+  Outgoing,
+  /// We blocked them.
+  Blocked,
+}
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/contact.d.ts")]
+pub struct Contact {
+  pub id: String,
+  pub username: String,
+  pub status: ContactStatus,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/friend-request.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum FriendRequestResult {
+  Sent,
+  /// They had already sent us a request, so now we're friends.
+  Accepted,
+  AlreadyFriends,
+  /// One of us blocked the other.
+  Blocked,
+  UserDoesNotExist,
+  NotLoggedIn,
+}
+
+/// The result of responding to a friend request, removing a friend, or (un)blocking someone.
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/contact-action.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ContactActionResult {
+  Success,
+  NotLoggedIn,
+  /// There was no such request, friend or user.
+  NotFound,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/list-contacts.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ListContactsResult {
+  Success {
+    contacts: Vec<Contact>,
+    /// The server couldn't be reached, so these are the contacts that we saw last.
+    offline: bool,
+  },
+  NotLoggedIn,
+}
+
+#[derive(Deserialize)]
+struct FriendRequestResponse {
+  /// Either `sent` or `accepted`.
+  result: String,
+}
+
+/// The contacts of the logged in user, keyed by user ID, along with the file that they're saved
+/// to.
+pub struct ContactStore {
+  path: PathBuf,
+  contacts: Mutex<HashMap<String, Contact>>,
+}
+
+impl ContactStore {
+  /// Loads the contacts that were saved in `data_dir`, if there are any.
+  pub fn load(data_dir: &Path) -> ContactStore {
+    let path = data_dir.join(CONTACTS_FILE_NAME);
+    let contacts: Vec<Contact> = fs::read(&path)
+      .ok()
+      .and_then(|x| serde_json::from_slice(&x).ok())
+      .unwrap_or_default();
+
+    ContactStore {
+      path,
+      contacts: Mutex::new(contacts.into_iter().map(|x| (x.id.clone(), x)).collect()),
+    }
+  }
+
+  /// Returns every contact, sorted by username.
+  pub fn list(&self) -> Vec<Contact> {
+    let mut contacts: Vec<Contact> = self.contacts.lock().unwrap().values().cloned().collect();
+    contacts.sort_by(|a, b| a.username.cmp(&b.username));
+    contacts
+  }
+
+  pub fn get(&self, user_id: &str) -> Option<Contact> {
+    self.contacts.lock().unwrap().get(user_id).cloned()
+  }
+
+  /// Returns `true` if we blocked the user with the given ID.
+  pub fn is_blocked(&self, user_id: &str) -> bool {
+    self
+      .get(user_id)
+      .map_or(false, |x| x.status == ContactStatus::Blocked)
+  }
+
+  /// Replaces every contact, e.g. with the list from the server.
+  pub fn replace(&self, contacts: Vec<Contact>) {
+    let mut guard = self.contacts.lock().unwrap();
+    *guard = contacts.into_iter().map(|x| (x.id.clone(), x)).collect();
+    self.save(&guard);
+  }
+
+  /// Adds or updates a contact.
+  pub fn set(&self, contact: Contact) {
+    let mut guard = self.contacts.lock().unwrap();
+    guard.insert(contact.id.clone(), contact);
+    self.save(&guard);
+  }
+
+  pub fn remove(&self, user_id: &str) {
+    let mut guard = self.contacts.lock().unwrap();
+    if guard.remove(user_id).is_some() {
+      self.save(&guard);
+    }
+  }
+
+  /// Forgets every contact, e.g. because we logged out.
+  pub fn clear(&self) {
+    self.contacts.lock().unwrap().clear();
+
+    match fs::remove_file(&self.path) {
+      Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+      Err(e) => eprintln!("couldn't clear contacts: {}", e),
+      Ok(_) => (),
+    }
+  }
+
+  fn save(&self, contacts: &HashMap<String, Contact>) {
+    let write = || -> io::Result<()> {
+      if let Some(parent) = self.path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+
+      let contacts: Vec<&Contact> = contacts.values().collect();
+      fs::write(&self.path, serde_json::to_vec(&contacts)?)
+    };
+
+    // they'll be fetched again next time, so this isn't a big deal
+    if let Err(e) = write() {
+      eprintln!("couldn't save contacts: {}", e);
+    }
+  }
+}
+
+/// Fetches the logged in user's contacts. Returns `None` if the token isn't valid.
+pub async fn fetch_contacts(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+) -> Result<Option<Vec<Contact>>, String> {
+  let resp = http
+    .get(url)
+    .bearer_auth(token.expose())
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => resp
+      .json()
+      .await
+      .map(Some)
+      .map_err(|_| "malformed server response".into()),
+    StatusCode::UNAUTHORIZED => Ok(None),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
+
+pub async fn send_friend_request(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  user_id: &str,
+) -> Result<FriendRequestResult, String> {
+  let body = json!({
+    "userId": user_id,
+  });
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => match x.json::<FriendRequestResponse>().await {
+        Ok(x) if x.result == "accepted" => Ok(FriendRequestResult::Accepted),
+        Ok(_) => Ok(FriendRequestResult::Sent),
+        Err(_) => Err("malformed server response".into()),
+      },
+      StatusCode::UNAUTHORIZED => Ok(FriendRequestResult::NotLoggedIn),
+      StatusCode::FORBIDDEN => Ok(FriendRequestResult::Blocked),
+      StatusCode::NOT_FOUND => Ok(FriendRequestResult::UserDoesNotExist),
+      StatusCode::BAD_REQUEST => match x.json::<BadRequestResponseBody>().await {
+        Ok(x) if x.typ == "ALREADYFRIENDS" => Ok(FriendRequestResult::AlreadyFriends),
+        Ok(_) => Err("invalid request".into()),
+        Err(_) => Err("malformed server response".into()),
+      },
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+/// Posts `body` to one of the contact endpoints that only succeed or don't find anything.
+pub async fn contact_action(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  body: serde_json::Value,
+) -> Result<ContactActionResult, String> {
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => Ok(ContactActionResult::Success),
+      StatusCode::UNAUTHORIZED => Ok(ContactActionResult::NotLoggedIn),
+      StatusCode::NOT_FOUND => Ok(ContactActionResult::NotFound),
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
@@ -0,0 +1,254 @@
+//! Resolves user IDs to users without making a request for each one. Lookups that arrive within a
+//! short window are collected into a single request, answers are cached for a while, and IDs that
+//! don't belong to anyone are cached too, so that they aren't asked about again.
+
+use std::{
+  collections::{HashMap, HashSet},
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use lru::LruCache;
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use tokio::{
+  sync::watch,
+  time::{sleep, Instant},
+};
+
+use super::User;
+
+/// The most IDs that are sent in one request. Bigger batches are split up.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The users that a batch resolved, keyed by ID. IDs that don't belong to anyone map to `None`.
+type Resolved = HashMap<String, Option<User>>;
+
+struct Entry<T> {
+  value: T,
+  at: Instant,
+}
+
+struct Cache {
+  by_id: LruCache<String, Entry<Option<User>>>,
+  /// User IDs, keyed by username.
+  by_username: LruCache<String, Entry<String>>,
+}
+
+/// IDs that are waiting to be sent in one request.
+struct Batch {
+  ids: Mutex<HashSet<String>>,
+  /// Set once the request has finished.
+  done: watch::Sender<Option<Result<Arc<Resolved>, String>>>,
+}
+
+pub struct UserDirectory {
+  http: Client,
+  /// The URL of the batch lookup endpoint.
+  url: String,
+  /// How long answers are cached.
+  ttl: Duration,
+  /// How long a batch waits for more IDs before it's sent.
+  batch_window: Duration,
+  cache: Mutex<Cache>,
+  /// The batch that new lookups join, if one hasn't been sent yet.
+  pending: Mutex<Option<Arc<Batch>>>,
+}
+
+impl UserDirectory {
+  /// Creates a directory that looks users up at `url`, and caches up to `capacity` of them.
+  pub fn new(
+    http: Client,
+    url: String,
+    capacity: usize,
+    ttl: Duration,
+    batch_window: Duration,
+  ) -> UserDirectory {
+    UserDirectory {
+      http,
+      url,
+      ttl,
+      batch_window,
+      cache: Mutex::new(Cache {
+        by_id: LruCache::new(capacity),
+        by_username: LruCache::new(capacity),
+      }),
+      pending: Default::default(),
+    }
+  }
+
+  /// Returns the cached user with the given ID. The outer `Option` is `None` if the ID isn't
+  /// cached, and the inner one is `None` if no user has it.
+  pub fn get(&self, id: &str) -> Option<Option<User>> {
+    let mut cache = self.cache.lock().unwrap();
+
+    match cache.by_id.get(id) {
+      Some(x) if x.at.elapsed() < self.ttl => Some(x.value.clone()),
+      Some(_) => {
+        cache.by_id.pop(id);
+        None
+      }
+      None => None,
+    }
+  }
+
+  /// Returns the cached user with the given username.
+  pub fn get_by_username(&self, username: &str) -> Option<User> {
+    let id = {
+      let mut cache = self.cache.lock().unwrap();
+      match cache.by_username.get(username) {
+        Some(x) if x.at.elapsed() < self.ttl => x.value.clone(),
+        _ => return None,
+      }
+    };
+
+    // the user might have changed their username since
+    self.get(&id).flatten().filter(|x| x.username == username)
+  }
+
+  /// Forgets the user with the given ID, e.g. because they changed their username.
+  pub fn invalidate(&self, id: &str) {
+    self.cache.lock().unwrap().by_id.pop(id);
+  }
+
+  pub fn clear(&self) {
+    let mut cache = self.cache.lock().unwrap();
+    cache.by_id.clear();
+    cache.by_username.clear();
+  }
+
+  fn insert(&self, resolved: &Resolved) {
+    let mut cache = self.cache.lock().unwrap();
+    let at = Instant::now();
+
+    for (id, user) in resolved {
+      if let Some(user) = user {
+        let value = id.clone();
+        cache
+          .by_username
+          .put(user.username.clone(), Entry { value, at });
+      }
+
+      let value = user.clone();
+      cache.by_id.put(id.clone(), Entry { value, at });
+    }
+  }
+
+  /// Resolves user IDs to users. IDs that aren't cached are looked up along with any others that
+  /// are requested within the batch window.
+  pub async fn resolve(self: &Arc<Self>, ids: &[String]) -> Result<Resolved, String> {
+    let mut resolved = HashMap::new();
+    let mut missing = HashSet::new();
+
+    for id in ids {
+      match self.get(id) {
+        Some(x) => {
+          resolved.insert(id.clone(), x);
+        }
+        None => {
+          missing.insert(id.clone());
+        }
+      }
+    }
+
+    if missing.is_empty() {
+      return Ok(resolved);
+    }
+
+    let mut done = {
+      let mut pending = self.pending.lock().unwrap();
+
+      let batch = match &*pending {
+        Some(x) => x.clone(),
+        None => {
+          let batch = Arc::new(Batch {
+            ids: Default::default(),
+            done: watch::channel(None).0,
+          });
+          *pending = Some(batch.clone());
+
+          // the batch is sent even if whoever started it stops waiting
+          let directory = self.clone();
+          tokio::spawn(async move { directory.send_batch().await });
+
+          batch
+        }
+      };
+
+      // this has to happen while `pending` is locked, so the batch can't be sent in between
+      batch.ids.lock().unwrap().extend(missing.iter().cloned());
+      batch.done.subscribe()
+    };
+
+    let batch_resolved = loop {
+      if let Some(x) = &*done.borrow() {
+        break x.clone()?;
+      }
+
+      if done.changed().await.is_err() {
+        return Err("user lookup was cancelled".into());
+      }
+    };
+
+    for id in missing {
+      let user = batch_resolved.get(&id).cloned().flatten();
+      resolved.insert(id, user);
+    }
+
+    Ok(resolved)
+  }
+
+  /// Waits for the batch window, and then looks up every ID in the pending batch.
+  async fn send_batch(&self) {
+    sleep(self.batch_window).await;
+
+    let batch = match self.pending.lock().unwrap().take() {
+      Some(x) => x,
+      None => return,
+    };
+    let ids: Vec<String> = batch.ids.lock().unwrap().drain().collect();
+
+    let mut resolved = HashMap::new();
+    let mut result = Ok(());
+
+    for chunk in ids.chunks(MAX_BATCH_SIZE) {
+      match fetch_users(&self.http, &self.url, chunk).await {
+        Ok(users) => {
+          // anyone that the server didn't mention doesn't exist
+          resolved.extend(chunk.iter().map(|x| (x.clone(), None)));
+          resolved.extend(users.into_iter().map(|x| (x.id.clone(), Some(x))));
+        }
+        Err(e) => {
+          result = Err(e);
+          break;
+        }
+      }
+    }
+
+    // failures aren't cached, so that they're tried again next time
+    let result = result.map(|_| {
+      self.insert(&resolved);
+      Arc::new(resolved)
+    });
+
+    let _ = batch.done.send(Some(result));
+  }
+}
+
+/// Looks up the users with the given IDs. Users that don't exist are left out.
+pub async fn fetch_users(http: &Client, url: &str, ids: &[String]) -> Result<Vec<User>, String> {
+  let resp = http
+    .post(url)
+    .json(&json!({ "ids": ids }))
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => resp
+      .json()
+      .await
+      .map_err(|_| "malformed server response".into()),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
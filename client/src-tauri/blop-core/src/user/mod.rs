@@ -0,0 +1,283 @@
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ts_rs::TS;
+
+use crate::{
+  common::BadRequestResponseBody,
+  user::auth::{
+    breach::BreachedPasswords,
+    password::{validate_password, PasswordCriteria, PasswordPolicy, PasswordValidation},
+    secret::Secret,
+    username::{validate_username, UsernameCriteria, UsernamePolicy, UsernameValidation},
+  },
+};
+
+pub mod auth;
+pub mod contacts;
+pub mod directory;
+pub mod profile;
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/user.d.ts")]
+pub struct User {
+  pub username: String,
+  pub id: String,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/create-user.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum CreateUserResult {
+  Success(AuthenticationSuccessResponse),
+  AlreadyLoggedIn,
+  UsernameAlreadyExists,
+  InvalidPassword(PasswordCriteria),
+  InvalidUsername(UsernameCriteria),
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/change-password.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ChangePasswordResult {
+  Success,
+  NotLoggedIn,
+  /// The old password was wrong.
+  WrongPassword,
+  InvalidPassword(PasswordCriteria),
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/change-username.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum ChangeUsernameResult {
+  Success,
+  NotLoggedIn,
+  UsernameAlreadyExists,
+  InvalidUsername(UsernameCriteria),
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/delete-account.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum DeleteAccountResult {
+  Success,
+  NotLoggedIn,
+  /// The password was wrong.
+  WrongPassword,
+}
+
+#[derive(Deserialize, Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/auth/success-response.d.ts")]
+pub struct AuthenticationSuccessResponse {
+  pub id: String,
+  /// The frontend never needs it, so it's kept out of the results that are sent there.
+  #[serde(skip_serializing)]
+  #[ts(skip)]
+  pub token: Secret,
+}
+
+/// The body of the requests that log in and create users. It borrows the password, so that it
+/// isn't copied into a `serde_json::Value` that wouldn't be zeroized.
+#[derive(Serialize)]
+pub(crate) struct Credentials<'a> {
+  pub username: &'a str,
+  pub password: &'a str,
+}
+
+pub async fn create_user(
+  http: &Client,
+  url: &str,
+  username: String,
+  password: Secret,
+  password_policy: &PasswordPolicy,
+  breached: &BreachedPasswords,
+  username_policy: &UsernamePolicy,
+) -> Result<CreateUserResult, String> {
+  // validate password, just in case
+  if let PasswordValidation::Invalid(crit) =
+    validate_password(password.expose(), password_policy, breached)
+  {
+    return Ok(CreateUserResult::InvalidPassword(crit));
+  }
+
+  // validate username, just in case
+  if let UsernameValidation::Invalid(crit) = validate_username(&username, username_policy) {
+    return Ok(CreateUserResult::InvalidUsername(crit));
+  }
+
+  // create request body
+  let body = Credentials {
+    username: &username,
+    password: password.expose(),
+  };
+
+  match http.post(url).json(&body).send().await {
+    // we got a response
+    Ok(x) => {
+      match x.status() {
+        StatusCode::OK => match x.json::<AuthenticationSuccessResponse>().await {
+          Ok(x) => Ok(CreateUserResult::Success(x)),
+          Err(_) => Err("malformed server response".into()),
+        },
+        StatusCode::BAD_REQUEST => {
+          // parse response body
+          let body = match x.json::<BadRequestResponseBody>().await {
+            Ok(x) => x,
+            Err(_) => {
+              return Err("malformed server response".into());
+            }
+          };
+
+          // someone else took the username after it was validated
+          if matches!(body.typ.as_str(), "USERALREADYEXISTS" | "DUPLICATE") {
+            return Ok(CreateUserResult::UsernameAlreadyExists);
+          }
+
+          // match reason for bad request response
+          Err(
+            match body.typ.as_str() {
+              "JSON" => "invalid request",
+
+              /*
+              We have InvalidUsername/Password for these, but all of these errors are
+              unexpected, i.e. we have checks that should prevent these errors from
+              happening. We shouldn't rely on this step for useful error types.
+              */
+              "USERNAME" => "invalid username",
+              "PASSWORD" => "invalid password",
+
+              _ => "invalid server response",
+            }
+            .into(),
+          )
+        }
+        _ => Err("invalid server response".into()),
+      }
+    }
+    // we didn't get a response
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+pub async fn change_password(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  old_password: Secret,
+  new_password: Secret,
+  policy: &PasswordPolicy,
+  breached: &BreachedPasswords,
+) -> Result<ChangePasswordResult, String> {
+  if let PasswordValidation::Invalid(crit) =
+    validate_password(new_password.expose(), policy, breached)
+  {
+    return Ok(ChangePasswordResult::InvalidPassword(crit));
+  }
+
+  #[derive(Serialize)]
+  #[serde(rename_all = "camelCase")]
+  struct Body<'a> {
+    old_password: &'a str,
+    new_password: &'a str,
+  }
+
+  let body = Body {
+    old_password: old_password.expose(),
+    new_password: new_password.expose(),
+  };
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => Ok(ChangePasswordResult::Success),
+      StatusCode::UNAUTHORIZED => Ok(ChangePasswordResult::NotLoggedIn),
+      StatusCode::FORBIDDEN => Ok(ChangePasswordResult::WrongPassword),
+      StatusCode::BAD_REQUEST => match x.json::<BadRequestResponseBody>().await {
+        // like with create_user, we should have caught this already
+        Ok(x) if x.typ == "PASSWORD" => Err("invalid password".into()),
+        Ok(_) => Err("invalid request".into()),
+        Err(_) => Err("malformed server response".into()),
+      },
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+pub async fn change_username(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  username: String,
+  policy: &UsernamePolicy,
+) -> Result<ChangeUsernameResult, String> {
+  if let UsernameValidation::Invalid(crit) = validate_username(&username, policy) {
+    return Ok(ChangeUsernameResult::InvalidUsername(crit));
+  }
+
+  let body = json!({
+    "username": username,
+  });
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => Ok(ChangeUsernameResult::Success),
+      StatusCode::UNAUTHORIZED => Ok(ChangeUsernameResult::NotLoggedIn),
+      StatusCode::BAD_REQUEST => match x.json::<BadRequestResponseBody>().await {
+        Ok(x) => match x.typ.as_str() {
+          "USERALREADYEXISTS" | "DUPLICATE" => Ok(ChangeUsernameResult::UsernameAlreadyExists),
+          // like with create_user, we should have caught this already
+          "USERNAME" => Err("invalid username".into()),
+          _ => Err("invalid request".into()),
+        },
+        Err(_) => Err("malformed server response".into()),
+      },
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+pub async fn delete_account(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  password: Secret,
+) -> Result<DeleteAccountResult, String> {
+  #[derive(Serialize)]
+  struct Body<'a> {
+    password: &'a str,
+  }
+
+  let body = Body {
+    password: password.expose(),
+  };
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(&body)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => Ok(DeleteAccountResult::Success),
+      StatusCode::UNAUTHORIZED => Ok(DeleteAccountResult::NotLoggedIn),
+      StatusCode::FORBIDDEN => Ok(DeleteAccountResult::WrongPassword),
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
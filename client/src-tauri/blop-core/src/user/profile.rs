@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{common::BadRequestResponseBody, user::auth::secret::Secret};
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+const MAX_BIO_LENGTH: usize = 300;
+const MAX_STATUS_LENGTH: usize = 80;
+/// The maximum length of an uploaded avatar, in base64. That's 192 KiB of image.
+const MAX_AVATAR_BLOB_LENGTH: usize = 256 * 1024;
+
+/// Everything that other users can see about a user.
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/profile.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+  pub id: String,
+  pub username: String,
+  /// Shown instead of the username, if it's set.
+  #[serde(default)]
+  pub display_name: Option<String>,
+  #[serde(default)]
+  pub avatar: Option<Avatar>,
+  #[serde(default)]
+  pub bio: Option<String>,
+  /// A short custom status, like "on vacation".
+  #[serde(default)]
+  pub status: Option<String>,
+  /// When the user was created, in milliseconds since the Unix epoch. Users created before
+  /// profiles existed have 0.
+  #[serde(default)]
+  #[ts(type = "number")]
+  pub created_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/avatar.d.ts")]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Avatar {
+  /// An image that's hosted somewhere else.
+  Url { url: String },
+  /// An image that was uploaded.
+  Blob {
+    /// The MIME type of the image, e.g. `image/png`.
+    #[serde(rename = "contentType")]
+    content_type: String,
+    /// The image, in base64.
+    data: String,
+  },
+}
+
+/// The parts of a profile that its user can change. Updates replace the whole profile, so fields
+/// that are `None` are cleared.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export, export_to = "../../src/types/user/profile-update.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileUpdate {
+  pub display_name: Option<String>,
+  pub avatar: Option<Avatar>,
+  pub bio: Option<String>,
+  pub status: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/profile-field.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileField {
+  DisplayName,
+  Avatar,
+  Bio,
+  Status,
+}
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export, export_to = "../../src/types/user/update-profile.d.ts")]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum UpdateProfileResult {
+  Success(Profile),
+  NotLoggedIn,
+  /// A field was too long, or an avatar wasn't an image.
+  InvalidField {
+    field: ProfileField,
+  },
+}
+
+impl ProfileUpdate {
+  /// Returns the first field that the server would reject, if any.
+  pub fn invalid_field(&self) -> Option<ProfileField> {
+    let too_long =
+      |x: &Option<String>, max: usize| x.as_ref().map_or(false, |x| x.chars().count() > max);
+
+    if too_long(&self.display_name, MAX_DISPLAY_NAME_LENGTH) {
+      return Some(ProfileField::DisplayName);
+    }
+
+    let avatar_valid = match &self.avatar {
+      None => true,
+      Some(Avatar::Url { url }) => url.starts_with("https://") || url.starts_with("http://"),
+      Some(Avatar::Blob { content_type, data }) => {
+        content_type.starts_with("image/") && data.len() <= MAX_AVATAR_BLOB_LENGTH
+      }
+    };
+    if !avatar_valid {
+      return Some(ProfileField::Avatar);
+    }
+
+    if too_long(&self.bio, MAX_BIO_LENGTH) {
+      return Some(ProfileField::Bio);
+    }
+
+    if too_long(&self.status, MAX_STATUS_LENGTH) {
+      return Some(ProfileField::Status);
+    }
+
+    None
+  }
+}
+
+/// Profiles that we've fetched, keyed by user ID. Entries are dropped when the WebSocket server
+/// says that they changed.
+#[derive(Default)]
+pub struct ProfileCache {
+  profiles: Mutex<HashMap<String, Profile>>,
+}
+
+impl ProfileCache {
+  pub fn get(&self, user_id: &str) -> Option<Profile> {
+    self.profiles.lock().unwrap().get(user_id).cloned()
+  }
+
+  pub fn insert(&self, profile: Profile) {
+    self
+      .profiles
+      .lock()
+      .unwrap()
+      .insert(profile.id.clone(), profile);
+  }
+
+  /// Forgets the profile of the user with the given ID, so that it's fetched again next time.
+  pub fn invalidate(&self, user_id: &str) {
+    self.profiles.lock().unwrap().remove(user_id);
+  }
+
+  pub fn clear(&self) {
+    self.profiles.lock().unwrap().clear();
+  }
+}
+
+/// Fetches the profile of the user with the given ID, or returns `None` if there's no such user.
+pub async fn fetch_profile(
+  http: &Client,
+  url: &str,
+  user_id: &str,
+) -> Result<Option<Profile>, String> {
+  let resp = http
+    .get(url)
+    .query(&[("id", user_id)])
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match resp.status() {
+    StatusCode::OK => resp
+      .json()
+      .await
+      .map(Some)
+      .map_err(|_| "malformed server response".into()),
+    StatusCode::NOT_FOUND => Ok(None),
+    other => Err(format!("unexpected status {}", other)),
+  }
+}
+
+pub async fn update_profile(
+  http: &Client,
+  url: &str,
+  token: &Secret,
+  update: &ProfileUpdate,
+) -> Result<UpdateProfileResult, String> {
+  if let Some(field) = update.invalid_field() {
+    return Ok(UpdateProfileResult::InvalidField { field });
+  }
+
+  match http
+    .post(url)
+    .bearer_auth(token.expose())
+    .json(update)
+    .send()
+    .await
+  {
+    Ok(x) => match x.status() {
+      StatusCode::OK => match x.json().await {
+        Ok(x) => Ok(UpdateProfileResult::Success(x)),
+        Err(_) => Err("malformed server response".into()),
+      },
+      StatusCode::UNAUTHORIZED => Ok(UpdateProfileResult::NotLoggedIn),
+      StatusCode::BAD_REQUEST => match x.json::<BadRequestResponseBody>().await {
+        // we should have caught this already
+        Ok(x) => Err(format!("invalid profile: {}", x.typ)),
+        Err(_) => Err("malformed server response".into()),
+      },
+      _ => Err("invalid server response".into()),
+    },
+    Err(e) => Err(e.to_string()),
+  }
+}
@@ -0,0 +1,842 @@
+use std::{
+  sync::{Arc, Mutex as StdMutex},
+  time::Duration,
+};
+
+use futures::{lock::Mutex, stream::SplitSink, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+  net::TcpStream,
+  sync::{watch, Notify},
+  task::JoinHandle,
+  time::Instant,
+};
+use tokio_tungstenite::{
+  connect_async,
+  tungstenite::{
+    client::IntoClientRequest,
+    http::{header::AUTHORIZATION, HeaderValue},
+    Message,
+  },
+  MaybeTlsStream, WebSocketStream,
+};
+use ts_rs::TS;
+use url::Url;
+use zeroize::Zeroizing;
+
+use crate::{
+  clock::{unix_millis, ClockEstimator, ClockSample},
+  e2e::{keyring::Keyring, Envelope},
+  events::{
+    emit, DirectMessageEventPayload, Event, EventSink, FriendRequestEventPayload,
+    LatencyEventPayload, LostConnectionReason, MessageEventPayload, NotificationEventPayload,
+    PresenceEventPayload, ProfileUpdatedEventPayload, SessionChangedEventPayload,
+    TypingEventPayload,
+  },
+  history::{HistoryEntry, MessageHistory},
+  user::{
+    auth::{secret::Secret, AuthenticationState, Session},
+    contacts::{Contact, ContactStatus, ContactStore},
+    profile::ProfileCache,
+  },
+  Config,
+};
+
+/// How long we wait for the close handshake when giving up on a dead connection.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The contents of a ping, which the server echoes back in its pong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingPayload {
+  /// The sequence number of the ping.
+  pub seq: u64,
+  /// When the ping was sent, in milliseconds since the Unix epoch.
+  pub sent_at: u64,
+}
+
+impl PingPayload {
+  /// Encodes the payload as `<seq>:<sent_at>`.
+  pub fn encode(&self) -> Vec<u8> {
+    format!("{}:{}", self.seq, self.sent_at).into_bytes()
+  }
+
+  /// Parses a payload that was encoded with `PingPayload::encode`.
+  pub fn parse(data: &[u8]) -> Option<PingPayload> {
+    let data = std::str::from_utf8(data).ok()?;
+    let (seq, sent_at) = data.split_once(':')?;
+
+    Some(PingPayload {
+      seq: seq.parse().ok()?,
+      sent_at: sent_at.parse().ok()?,
+    })
+  }
+}
+
+/// The contents of a pong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pong {
+  /// The payload of the ping that this pong answers.
+  pub ping: PingPayload,
+  /// The server's time when it sent the pong, in milliseconds since the Unix epoch.
+  pub server_time: Option<u64>,
+}
+
+impl Pong {
+  /// Parses a pong, which is the ping's payload optionally followed by `:<server_time>`.
+  pub fn parse(data: &[u8]) -> Option<Pong> {
+    let data = std::str::from_utf8(data).ok()?;
+
+    match data.rsplit_once(':') {
+      // the server appended its time
+      Some((ping, server_time)) if ping.contains(':') => Some(Pong {
+        ping: PingPayload::parse(ping.as_bytes())?,
+        server_time: Some(server_time.parse().ok()?),
+      }),
+      _ => Some(Pong {
+        ping: PingPayload::parse(data.as_bytes())?,
+        server_time: None,
+      }),
+    }
+  }
+}
+
+/// A message that we send to the WebSocket server.
+#[derive(Serialize, TS)]
+#[ts(export, export_to = "../../src/types/websocket/envelope.d.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct OutgoingEnvelope {
+  pub message: String,
+  /// When the message was sent according to the server's clock, in milliseconds since the Unix
+  /// epoch.
+  #[ts(type = "number")]
+  pub sent_at: u64,
+}
+
+/// A structured frame from the WebSocket server. Anything else is a plain message.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerFrame {
+  /// A user changed their profile.
+  ProfileUpdated {
+    #[serde(rename = "userId")]
+    user_id: String,
+  },
+  /// A message from a logged in user.
+  Message {
+    #[serde(rename = "userId")]
+    user_id: String,
+    message: String,
+    #[serde(rename = "sentAt", default)]
+    sent_at: Option<u64>,
+  },
+  /// A user is typing.
+  Typing {
+    #[serde(rename = "userId")]
+    user_id: String,
+  },
+  /// A user came online or went offline.
+  Presence {
+    #[serde(rename = "userId")]
+    user_id: String,
+    online: bool,
+  },
+  /// A user sent us a friend request.
+  FriendRequest {
+    #[serde(rename = "userId")]
+    user_id: String,
+    username: String,
+  },
+  /// An encrypted message that was sent to us only.
+  Dm {
+    #[serde(rename = "userId")]
+    user_id: String,
+    envelope: Envelope,
+    #[serde(rename = "sentAt")]
+    sent_at: u64,
+  },
+}
+
+impl ServerFrame {
+  /// Returns the user that the frame came from, if it should be hidden when they're blocked.
+  fn sender(&self) -> Option<&str> {
+    match self {
+      ServerFrame::ProfileUpdated { .. } => None,
+      ServerFrame::Message { user_id, .. }
+      | ServerFrame::Typing { user_id }
+      | ServerFrame::Presence { user_id, .. }
+      | ServerFrame::FriendRequest { user_id, .. }
+      | ServerFrame::Dm { user_id, .. } => Some(user_id),
+    }
+  }
+}
+
+/// Counters for the pongs that we've received.
+#[derive(Clone, Copy, Default, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/websocket/ping-stats.d.ts")]
+pub struct PingStats {
+  /// The number of pings sent.
+  pub sent: u32,
+  /// The number of pongs that answered the outstanding ping.
+  pub matched: u32,
+  /// The number of pongs that answered a ping we had already given up on.
+  pub stale: u32,
+  /// The number of pongs that didn't answer any ping we sent.
+  pub unmatched: u32,
+}
+
+/// How a pong relates to the pings that we've sent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PongMatch {
+  /// The pong answered the outstanding ping. Contains the round trip time.
+  Matched(Duration),
+  /// The pong answered a ping that we had already given up on.
+  Stale,
+  /// The pong didn't answer any ping that we sent.
+  Unmatched,
+}
+
+struct SentPing {
+  payload: PingPayload,
+  sent_time: Instant,
+}
+
+#[derive(Default)]
+pub struct Ping {
+  /// The sequence number of the next ping. It never wraps, so that every number below it has been
+  /// used.
+  next_seq: u64,
+  /// The ping that is waiting for a pong, if any.
+  outstanding: Option<SentPing>,
+  /// The number of ping intervals that have passed without receiving a pong.
+  missed: u32,
+  stats: PingStats,
+}
+
+/// What the pinger should do after waiting for an interval.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PingAction {
+  /// There's no outstanding ping, so send a new one.
+  Send,
+  /// Keep waiting for the outstanding ping's pong.
+  Wait,
+  /// Too many pongs were missed, so the connection should be considered dead.
+  Timeout,
+}
+
+impl Ping {
+  /// Advances the ping state by one interval and returns what should be done.
+  /// `max_missed` is the number of intervals a pong may be late before we time out.
+  pub fn tick(&mut self, max_missed: u32) -> PingAction {
+    if self.outstanding.is_none() {
+      return PingAction::Send;
+    }
+
+    self.missed += 1;
+    if self.missed >= max_missed {
+      PingAction::Timeout
+    } else {
+      PingAction::Wait
+    }
+  }
+
+  /// Creates the payload for the next ping.
+  pub fn next_payload(&mut self) -> PingPayload {
+    let payload = PingPayload {
+      seq: self.next_seq,
+      sent_at: unix_millis(),
+    };
+    self.next_seq += 1;
+    payload
+  }
+
+  /// Marks the ping with the given payload as sent.
+  pub fn sent(&mut self, payload: PingPayload) {
+    self.outstanding = Some(SentPing {
+      payload,
+      sent_time: Instant::now(),
+    });
+    self.stats.sent += 1;
+  }
+
+  /// Matches the payload of a pong to the ping that it answers. If it answers the outstanding
+  /// ping, that ping is no longer outstanding.
+  pub fn ponged(&mut self, payload: Option<PingPayload>) -> PongMatch {
+    let result = match (payload, &self.outstanding) {
+      (Some(payload), Some(sent)) if payload == sent.payload => {
+        PongMatch::Matched(Instant::now() - sent.sent_time)
+      }
+      // the sequence number is one that we've already used
+      (Some(payload), _) if payload.seq < self.next_seq => PongMatch::Stale,
+      _ => PongMatch::Unmatched,
+    };
+
+    match result {
+      PongMatch::Matched(_) => {
+        self.outstanding = None;
+        self.missed = 0;
+        self.stats.matched += 1;
+      }
+      PongMatch::Stale => self.stats.stale += 1,
+      PongMatch::Unmatched => self.stats.unmatched += 1,
+    }
+
+    result
+  }
+
+  /// Returns the pong counters.
+  pub fn stats(&self) -> PingStats {
+    self.stats
+  }
+
+  /// Forgets about any outstanding ping. Sequence numbers and stats are kept, so that pongs
+  /// for forgotten pings are still recognized as stale.
+  pub fn reset(&mut self) {
+    self.outstanding = None;
+    self.missed = 0;
+  }
+}
+
+type WebSocketSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+/// A connection to the WebSocket server that reconnects whenever it is lost.
+pub struct WebSocketClient {
+  config: Arc<Config>,
+  events: Arc<dyn EventSink>,
+  write: Mutex<Option<WebSocketSink>>,
+  ping: Mutex<Ping>,
+  /// Notified by the pinger when it gives up on the current connection.
+  dead: Notify,
+  clock: Mutex<ClockEstimator>,
+  /// The profiles to invalidate when the server says that they changed.
+  profiles: Option<Arc<ProfileCache>>,
+  /// The contacts to add friend requests to, and to filter blocked users with.
+  contacts: Option<Arc<ContactStore>>,
+  /// Whose token to connect with. Without it, the connection is anonymous.
+  auth: Option<Arc<AuthenticationState>>,
+  /// The keys to encrypt and decrypt direct messages with. Without it, they can't be sent, and
+  /// ones that arrive are dropped.
+  keyring: Option<Arc<Keyring>>,
+  /// Where messages that arrive are recorded.
+  history: Option<Arc<MessageHistory>>,
+  /// The tasks that `start` spawned, so that `stop` can end them.
+  tasks: StdMutex<Vec<JoinHandle<()>>>,
+}
+
+impl WebSocketClient {
+  /// Creates a client that isn't connected yet. Events are emitted through `events`.
+  pub fn new(config: Arc<Config>, events: Arc<dyn EventSink>) -> WebSocketClient {
+    WebSocketClient {
+      config,
+      events,
+      write: Mutex::from(None),
+      ping: Default::default(),
+      dead: Default::default(),
+      clock: Default::default(),
+      profiles: None,
+      contacts: None,
+      auth: None,
+      keyring: None,
+      history: None,
+      tasks: Default::default(),
+    }
+  }
+
+  /// Makes the client invalidate profiles in `profiles` when they change.
+  pub fn with_profiles(mut self, profiles: Arc<ProfileCache>) -> WebSocketClient {
+    self.profiles = Some(profiles);
+    self
+  }
+
+  /// Makes the client record friend requests in `contacts`, and ignore users that it says are
+  /// blocked.
+  pub fn with_contacts(mut self, contacts: Arc<ContactStore>) -> WebSocketClient {
+    self.contacts = Some(contacts);
+    self
+  }
+
+  /// Makes the client connect as the user that's logged in to `auth`, if any. Once it's started, it
+  /// reconnects whenever they log in or out.
+  pub fn with_auth(mut self, auth: Arc<AuthenticationState>) -> WebSocketClient {
+    self.auth = Some(auth);
+    self
+  }
+
+  /// Makes the client encrypt and decrypt direct messages with the keys in `keyring`. Its device
+  /// warnings are emitted through this client's sink.
+  pub fn with_keyring(mut self, keyring: Arc<Keyring>) -> WebSocketClient {
+    keyring.set_event_sink(self.events.clone());
+    self.keyring = Some(keyring);
+    self
+  }
+
+  /// Makes the client record the messages that arrive in `history`.
+  pub fn with_history(mut self, history: Arc<MessageHistory>) -> WebSocketClient {
+    self.history = Some(history);
+    self
+  }
+
+  /// Spawns the tasks that keep the client connected, i.e. `listen` and `pinger`.
+  pub fn start(self: &Arc<Self>) {
+    let listener = self.clone();
+    let listen = tokio::spawn(async move { listener.listen().await });
+
+    let pinger = self.clone();
+    let ping = tokio::spawn(async move { pinger.pinger().await });
+
+    self.tasks.lock().unwrap().extend([listen, ping]);
+
+    if let Some(auth) = &self.auth {
+      let watcher = self.clone();
+      let mut changes = auth.subscribe();
+      let watch = tokio::spawn(async move { watcher.watch_session(&mut changes).await });
+      self.tasks.lock().unwrap().push(watch);
+    }
+  }
+
+  /// Ends the tasks that `start` spawned and closes the connection, e.g. because the account that
+  /// it belongs to was removed. The client can't be started again.
+  pub async fn stop(&self) {
+    for task in self.tasks.lock().unwrap().drain(..) {
+      task.abort();
+    }
+
+    if let Some(mut write) = self.write.lock().await.take() {
+      let _ = tokio::time::timeout(CLOSE_TIMEOUT, write.close()).await;
+    }
+  }
+
+  /// Returns our best guess of the server's current time in milliseconds since the Unix epoch.
+  pub async fn server_now(&self) -> u64 {
+    self.clock.lock().await.server_now()
+  }
+
+  /// Returns the pong counters.
+  pub async fn ping_stats(&self) -> PingStats {
+    self.ping.lock().await.stats()
+  }
+
+  /// Drops the current connection and connects again, e.g. because the user logged in.
+  pub async fn reconnect(&self) {
+    // hold the ping lock too, so that the pinger doesn't see a half-closed connection
+    let _ping = self.ping.lock().await;
+
+    if let Some(mut write) = self.write.lock().await.take() {
+      let _ = tokio::time::timeout(CLOSE_TIMEOUT, write.close()).await;
+      self.dead.notify_one();
+    }
+  }
+
+  /// Sends a message to the WebSocket server, timestamped with the server's clock.
+  pub async fn send_message(&self, message: String) -> Result<(), String> {
+    let envelope = OutgoingEnvelope {
+      message,
+      sent_at: self.server_now().await,
+    };
+    let envelope = serde_json::to_string(&envelope).map_err(|e| e.to_string())?;
+
+    self.send_text(envelope).await
+  }
+
+  /// Encrypts a message for the user with the given ID, and sends it to them only.
+  pub async fn send_direct_message(&self, user_id: &str, message: &str) -> Result<(), String> {
+    let keyring = self
+      .keyring
+      .as_ref()
+      .ok_or("direct messages aren't enabled")?;
+    let envelope = keyring.encrypt(user_id, message).await?;
+
+    let frame = json!({
+      "type": "dm",
+      "to": user_id,
+      "envelope": envelope,
+      "sentAt": self.server_now().await,
+    });
+
+    self.send_text(frame.to_string()).await
+  }
+
+  async fn send_text(&self, text: String) -> Result<(), String> {
+    let mut guard = self.write.lock().await;
+
+    // unwrap option inside MutexGuard
+    let conn = match &mut *guard {
+      Some(x) => x,
+      None => return Err("not connected to WebSocket server".into()),
+    };
+
+    conn.feed(text.into()).await.map_err(|e| e.to_string())?;
+    conn.flush().await.map_err(|e| e.to_string())?;
+
+    Ok(())
+  }
+
+  /// Pings the server at a regular interval to remind the server that this connection is alive.
+  /// This function does not handle pongs, but if `Config::max_missed_pongs` intervals pass without
+  /// one, the connection is closed and `listen` is told to reconnect.
+  pub async fn pinger(&self) -> ! {
+    loop {
+      tokio::time::sleep(self.config.ping_interval).await;
+
+      let mut ping = self.ping.lock().await;
+      let mut write_guard = self.write.lock().await;
+
+      // if we haven't connected yet (write is None), wait
+      if write_guard.is_none() {
+        continue;
+      }
+
+      match ping.tick(self.config.max_missed_pongs) {
+        // don't send multiple pings
+        PingAction::Wait => continue,
+        PingAction::Timeout => {
+          eprintln!("missed {} pongs, dropping connection", ping.missed);
+          ping.reset();
+
+          // the server might never answer the close message, so don't wait on it for long
+          if let Some(mut write) = write_guard.take() {
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, write.close()).await;
+          }
+
+          emit(
+            &*self.events,
+            Event::Notification(NotificationEventPayload::LostConnection {
+              reason: LostConnectionReason::PongTimeout,
+            }),
+          );
+
+          // wake up listen() so it stops reading from the dead connection
+          self.dead.notify_one();
+          continue;
+        }
+        PingAction::Send => (),
+      }
+
+      let write = write_guard.as_mut().unwrap();
+      let payload = ping.next_payload();
+
+      let sent = async {
+        write.feed(Message::Ping(payload.encode())).await?;
+        write.flush().await
+      };
+
+      match sent.await {
+        Ok(_) => ping.sent(payload),
+        // listen() will notice that the connection is gone
+        Err(e) => eprintln!("couldn't send ping: {}", e),
+      }
+    }
+  }
+
+  /// Reconnects as whoever is logged in whenever the session changes, and tells the sink.
+  async fn watch_session(&self, changes: &mut watch::Receiver<Option<Session>>) {
+    let mut current = changes.borrow_and_update().clone();
+
+    while changes.changed().await.is_ok() {
+      let session = changes.borrow_and_update().clone();
+      if Session::same(&current, &session) {
+        continue;
+      }
+
+      let payload = SessionChangedEventPayload {
+        user_id: session.as_ref().map(|x| x.user_id.clone()),
+      };
+      current = session;
+
+      self.reconnect().await;
+      emit(&*self.events, Event::SessionChanged(payload));
+    }
+  }
+
+  /// Connects to the WebSocket server and emits the messages that it sends, reconnecting whenever
+  /// the connection is lost.
+  pub async fn listen(&self) {
+    let mut first_connection = true;
+
+    loop {
+      eprintln!("connecting to ws server...");
+      // acquire write lock ASAP
+      let mut write = self.write.lock().await;
+      eprintln!("write lock acquired");
+
+      let token = match &self.auth {
+        Some(x) => x.get_token(),
+        None => None,
+      };
+
+      // try to connect again
+      let new_ws_stream = match try_connect(
+        self.config.ws_url.clone(),
+        token,
+        self.config.reconnect_delay,
+      )
+      .await
+      {
+        Err(e) => panic!("{}", e), // panic for now
+        Ok(x) => x,
+      };
+
+      emit(
+        &*self.events,
+        Event::Notification(NotificationEventPayload::Connected { first_connection }),
+      );
+      first_connection = false;
+
+      let (new_write, new_read) = new_ws_stream.split();
+
+      // update websocket connection to use new stream/sink
+      let read = new_read;
+      *write = Some(new_write);
+      eprintln!("listen() releasing write lock");
+
+      // release lock
+      drop(write);
+
+      // pings sent over the old connection will never be answered
+      self.ping.lock().await.reset();
+
+      eprintln!("ws server connected");
+
+      // read from websocket stream until server sends close message, or until the pinger decides
+      // that the connection is dead
+      let read_all = read.for_each(|message_result| async {
+        let message = match message_result {
+          Err(e) => {
+            eprintln!("error: {}", e);
+            return;
+          }
+          Ok(x) => x,
+        };
+
+        if message.is_pong() {
+          let received_at = unix_millis();
+          let pong = Pong::parse(&message.into_data());
+
+          // the payload carries its own send time, so pongs that don't match are still useful here.
+          // older servers don't send their time
+          if let Some(Pong {
+            ping,
+            server_time: Some(server_time),
+          }) = pong
+          {
+            let mut clock = self.clock.lock().await;
+            clock.add_sample(ClockSample::new(ping.sent_at, server_time, received_at));
+
+            if let Some(offset) = clock.check_skew(self.config.max_clock_skew) {
+              emit(
+                &*self.events,
+                Event::Notification(NotificationEventPayload::ClockSkew { offset }),
+              );
+            }
+          }
+
+          let latency = match self.ping.lock().await.ponged(pong.map(|x| x.ping)) {
+            PongMatch::Matched(x) => x,
+            // late or bundled pongs would give us the wrong latency
+            other => {
+              eprintln!("ignoring pong: {:?}", other);
+              return;
+            }
+          };
+          emit(
+            &*self.events,
+            Event::Latency(LatencyEventPayload {
+              latency: latency.as_millis() as u32,
+            }),
+          );
+
+          return;
+        }
+
+        let data = message.into_data();
+        let str_data = std::str::from_utf8(&data).unwrap();
+
+        if let Ok(frame) = serde_json::from_str(str_data) {
+          self.handle_frame(frame).await;
+          return;
+        }
+
+        // emit message to frontend
+        self.record(None, str_data, false);
+        emit(
+          &*self.events,
+          Event::Message(MessageEventPayload {
+            message: str_data.into(),
+            user_id: None,
+            sent_at: None,
+          }),
+        );
+      });
+
+      tokio::select! {
+        _ = read_all => {
+          // if the pinger or `reconnect` closed the connection, it's about to notify us, and that
+          // notification shouldn't end the next connection
+          if self.write.lock().await.is_none() {
+            self.dead.notified().await;
+          } else {
+            emit(
+              &*self.events,
+              Event::Notification(NotificationEventPayload::LostConnection {
+                reason: LostConnectionReason::Closed,
+              }),
+            );
+          }
+        }
+        // the pinger already emitted the notification, or we're reconnecting on purpose
+        _ = self.dead.notified() => (),
+      }
+
+      eprintln!("lost connection");
+    }
+  }
+}
+
+impl WebSocketClient {
+  /// Adds a message that arrived to the history, if there is one.
+  fn record(&self, user_id: Option<&str>, message: &str, direct: bool) {
+    if let Some(history) = &self.history {
+      history.record(HistoryEntry {
+        user_id: user_id.map(Into::into),
+        message: message.into(),
+        direct,
+        received_at: unix_millis(),
+      });
+    }
+  }
+
+  /// Emits a structured frame, unless it's from someone that we blocked.
+  async fn handle_frame(&self, frame: ServerFrame) {
+    if let (Some(contacts), Some(sender)) = (&self.contacts, frame.sender()) {
+      if contacts.is_blocked(sender) {
+        return;
+      }
+    }
+
+    match frame {
+      ServerFrame::ProfileUpdated { user_id } => {
+        if let Some(profiles) = &self.profiles {
+          profiles.invalidate(&user_id);
+        }
+
+        emit(
+          &*self.events,
+          Event::ProfileUpdated(ProfileUpdatedEventPayload { user_id }),
+        );
+      }
+      ServerFrame::Message {
+        user_id,
+        message,
+        sent_at,
+      } => {
+        self.record(Some(&user_id), &message, false);
+        emit(
+          &*self.events,
+          Event::Message(MessageEventPayload {
+            message,
+            user_id: Some(user_id),
+            sent_at,
+          }),
+        )
+      }
+      ServerFrame::Typing { user_id } => {
+        emit(&*self.events, Event::Typing(TypingEventPayload { user_id }))
+      }
+      ServerFrame::Presence { user_id, online } => emit(
+        &*self.events,
+        Event::Presence(PresenceEventPayload { user_id, online }),
+      ),
+      ServerFrame::FriendRequest { user_id, username } => {
+        if let Some(contacts) = &self.contacts {
+          contacts.set(Contact {
+            id: user_id.clone(),
+            username: username.clone(),
+            status: ContactStatus::Incoming,
+          });
+        }
+
+        emit(
+          &*self.events,
+          Event::FriendRequest(FriendRequestEventPayload { user_id, username }),
+        );
+      }
+      ServerFrame::Dm {
+        user_id,
+        envelope,
+        sent_at,
+      } => {
+        let keyring = match &self.keyring {
+          Some(x) => x,
+          None => return,
+        };
+
+        match keyring.decrypt(&user_id, &envelope).await {
+          Ok(message) => {
+            self.record(Some(&user_id), &message, true);
+            emit(
+              &*self.events,
+              Event::DirectMessage(DirectMessageEventPayload {
+                user_id,
+                message,
+                sent_at,
+              }),
+            )
+          }
+          Err(e) => eprintln!("couldn't decrypt direct message from {}: {}", user_id, e),
+        }
+      }
+    }
+  }
+}
+
+pub async fn connect_websocket(url: String) -> WebSocketStream<MaybeTlsStream<TcpStream>> {
+  let ws_uri = Url::parse(&url).unwrap();
+
+  let (ws_stream, _) = connect_async(ws_uri).await.expect("failed to connect");
+
+  ws_stream
+}
+
+/// Connects to the WebSocket server at `uri`, trying again every `delay` until it works. If a
+/// token is given, the connection belongs to whoever it was issued to.
+pub async fn try_connect(
+  uri: String,
+  token: Option<Arc<Secret>>,
+  delay: Duration,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, String> {
+  let ws_uri = Url::parse(&uri).unwrap();
+
+  // requests can't be cloned, so one is built for every attempt
+  let request = || {
+    let mut request = ws_uri
+      .clone()
+      .into_client_request()
+      .map_err(|e| e.to_string())?;
+
+    if let Some(token) = &token {
+      let header = Zeroizing::new(format!("Bearer {}", token.expose()));
+      let mut value = HeaderValue::from_str(&header).map_err(|e| e.to_string())?;
+      value.set_sensitive(true);
+      request.headers_mut().insert(AUTHORIZATION, value);
+    }
+
+    Ok::<_, String>(request)
+  };
+
+  // todo: give up after some number of attempts
+  loop {
+    match connect_async(request()?).await {
+      Err(_) => {
+        eprintln!("failed to connect. trying again in {:?}.", delay);
+        tokio::time::sleep(delay).await;
+        continue;
+      }
+      Ok((ws_stream, _)) => {
+        eprintln!("reconnected");
+        return Ok(ws_stream);
+      }
+    }
+  }
+
+  // Err("couldn't connect to WS server".into())
+}
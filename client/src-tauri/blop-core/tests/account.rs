@@ -0,0 +1,124 @@
+mod support;
+
+use std::sync::Arc;
+
+use blop_core::{
+  api::{ApiClient, LoginResult},
+  user::{
+    auth::password::{PasswordPolicy, PasswordRule},
+    ChangePasswordResult, ChangeUsernameResult, DeleteAccountResult,
+  },
+};
+use support::{logged_in, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn change_password() {
+  let (_server, jackson) = logged_in().await;
+  let api = &jackson.api;
+
+  let result = api
+    .change_password("wrong password".into(), "lantern orbit pickle".into())
+    .await;
+  assert!(matches!(result, Ok(ChangePasswordResult::WrongPassword)));
+
+  let result = api
+    .change_password(PASSWORD.into(), "lantern orbit pickle".into())
+    .await;
+  assert!(matches!(result, Ok(ChangePasswordResult::Success)));
+  assert!(api.auth().get_token().is_none());
+
+  let result = api
+    .log_in("jackson".into(), "lantern orbit pickle".into())
+    .await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+}
+
+#[tokio::test]
+async fn change_password_rejects_breached_passwords() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+  server.set_password_policy(PasswordPolicy {
+    min_strength: None,
+    ..Default::default()
+  });
+
+  let result = api
+    .change_password(PASSWORD.into(), "password1".into())
+    .await;
+  match result {
+    Ok(ChangePasswordResult::InvalidPassword(criteria)) => {
+      let failed: Vec<_> = criteria.rules.iter().filter(|x| !x.passed).collect();
+      assert_eq!(failed.len(), 1);
+      assert_eq!(failed[0].id, PasswordRule::NotBreached);
+    }
+    _ => panic!("expected password to be rejected"),
+  }
+  assert_eq!(server.requests("/auth/password"), 0);
+}
+
+#[tokio::test]
+async fn change_password_logged_out() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  let result = api
+    .change_password(PASSWORD.into(), "lantern orbit pickle".into())
+    .await;
+  assert!(matches!(result, Ok(ChangePasswordResult::NotLoggedIn)));
+  assert_eq!(server.requests("/auth/password"), 0);
+}
+
+#[tokio::test]
+async fn change_username() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+  server.add_user("someone", PASSWORD);
+
+  let result = api.change_username("someone".into()).await;
+  assert!(matches!(
+    result,
+    Ok(ChangeUsernameResult::UsernameAlreadyExists)
+  ));
+
+  let result = api.change_username("admin".into()).await;
+  assert!(matches!(
+    result,
+    Ok(ChangeUsernameResult::InvalidUsername(_))
+  ));
+  assert_eq!(server.requests("/auth/username"), 1);
+
+  let result = api.change_username("jackson_2".into()).await;
+  assert!(matches!(result, Ok(ChangeUsernameResult::Success)));
+  assert!(api.auth().get_token().is_some());
+  assert_eq!(api.user_exists("jackson").await, Ok(false));
+  assert_eq!(api.user_exists("jackson_2").await, Ok(true));
+}
+
+#[tokio::test]
+async fn delete_account() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+
+  let result = api.delete_account("wrong password".into()).await;
+  assert!(matches!(result, Ok(DeleteAccountResult::WrongPassword)));
+  assert!(api.auth().get_token().is_some());
+
+  let result = api.delete_account(PASSWORD.into()).await;
+  assert!(matches!(result, Ok(DeleteAccountResult::Success)));
+  assert!(api.auth().get_token().is_none());
+  assert_eq!(api.user_exists("jackson").await, Ok(false));
+  assert_eq!(server.requests("/auth/delete"), 2);
+}
+
+#[tokio::test]
+async fn expired_session_logs_out() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+  server.expire_tokens();
+
+  let result = api.change_username("jackson_2".into()).await;
+  assert!(matches!(result, Ok(ChangeUsernameResult::NotLoggedIn)));
+  assert!(api.auth().get_token().is_none());
+}
@@ -0,0 +1,324 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use blop_core::{
+  accounts::{account_dir, AccountActionResult, AccountKey, AccountManager},
+  api::{ApiClient, LoginResult, MyInfoResult},
+  events::AuthStateEventPayload,
+  user::{auth::AuthenticationState, DeleteAccountResult},
+  websocket::WebSocketClient,
+};
+use support::{recorder, wait_for, wait_for_notification, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+fn key(user_id: &str) -> AccountKey {
+  AccountKey {
+    profile: "default".into(),
+    user_id: user_id.into(),
+  }
+}
+
+async fn my_id(accounts: &AccountManager) -> Option<String> {
+  match accounts.active().api.my_info().await {
+    Ok(MyInfoResult::Success(profile)) => Some(profile.id),
+    _ => None,
+  }
+}
+
+async fn log_in(accounts: &AccountManager, username: &str) {
+  let result = accounts
+    .log_in(username.into(), PASSWORD.into(), true)
+    .await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+}
+
+#[tokio::test]
+async fn switching_accounts_repoints_commands() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+  let bob = server.add_user("bob", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let accounts = AccountManager::new(Arc::new(server.config(data_dir.path())));
+  accounts.restore().await;
+  assert_eq!(my_id(&accounts).await, None);
+
+  // logging in adds an account and switches to it
+  log_in(&accounts, "alice").await;
+  log_in(&accounts, "bob").await;
+  assert_eq!(my_id(&accounts).await, Some(bob.clone()));
+
+  let listed = accounts.list_accounts().await;
+  let ids: Vec<&str> = listed.iter().map(|x| x.user_id.as_str()).collect();
+  assert_eq!(ids, [alice.as_str(), bob.as_str()]);
+  assert!(!listed[0].active && listed[1].active);
+  assert!(listed.iter().all(|x| x.logged_in));
+
+  let result = accounts.switch_account(&key(&alice));
+  assert!(matches!(result, AccountActionResult::Success));
+  assert_eq!(my_id(&accounts).await, Some(alice.clone()));
+
+  let result = accounts.switch_account(&key("nobody"));
+  assert!(matches!(result, AccountActionResult::UnknownAccount));
+  assert_eq!(my_id(&accounts).await, Some(alice.clone()));
+
+  // every account keeps its own session
+  for id in [&alice, &bob] {
+    assert!(account_dir(data_dir.path(), &key(id))
+      .join("session.json")
+      .exists());
+  }
+}
+
+#[tokio::test]
+async fn only_the_active_account_emits_events() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+  let bob = server.add_user("bob", PASSWORD);
+  server.add_user("carol", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let accounts = AccountManager::new(Arc::new(server.config(data_dir.path())));
+  accounts.restore().await;
+  log_in(&accounts, "alice").await;
+  log_in(&accounts, "bob").await;
+
+  // wait for both of them to connect, while they're active so that we can tell
+  let (sink, mut events) = recorder();
+  accounts.switch_account(&key(&alice));
+  accounts.start(sink);
+  wait_for_notification(&mut events, "connected").await;
+  accounts.switch_account(&key(&bob));
+  accounts.active().ws.reconnect().await;
+  wait_for_notification(&mut events, "connected").await;
+
+  // carol messages both of them from their own client
+  let carol_dir = TempDir::new().unwrap();
+  let carol_auth = Arc::new(AuthenticationState::default());
+  let carol_config = Arc::new(server.config(carol_dir.path()));
+  let carol = ApiClient::new(carol_config.clone(), carol_auth.clone());
+  carol.log_in("carol".into(), PASSWORD.into()).await.unwrap();
+  let (carol_sink, mut carol_events) = recorder();
+  let carol_ws = Arc::new(
+    WebSocketClient::new(carol_config, carol_sink)
+      .with_auth(carol_auth)
+      .with_keyring(carol.keyring()),
+  );
+  carol_ws.start();
+  wait_for_notification(&mut carol_events, "connected").await;
+
+  for (to, message) in [(&alice, "to alice"), (&bob, "to bob")] {
+    carol_ws.send_direct_message(to, message).await.unwrap();
+  }
+
+  // bob is active, so alice's message never shows up
+  let payload = wait_for(&mut events, |event, _| event == "direct_message").await;
+  assert_eq!(payload["message"], "to bob");
+
+  accounts.switch_account(&key(&alice));
+  let payload = wait_for(&mut events, |event, _| event == "account_switched").await;
+  assert_eq!(payload["account"]["userId"], alice.as_str());
+
+  for (to, message) in [(&bob, "to bob again"), (&alice, "to alice again")] {
+    carol_ws.send_direct_message(to, message).await.unwrap();
+  }
+
+  let payload = wait_for(&mut events, |event, _| event == "direct_message").await;
+  assert_eq!(payload["message"], "to alice again");
+
+  // but every account keeps its own history, even while it isn't active
+  let history: Vec<String> = accounts
+    .active()
+    .history
+    .recent(10)
+    .into_iter()
+    .map(|x| x.message)
+    .collect();
+  assert_eq!(history, ["to alice", "to alice again"]);
+}
+
+#[tokio::test]
+async fn accounts_survive_restarts_until_removed() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+  let bob = server.add_user("bob", PASSWORD);
+
+  // a session from before there were several accounts
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let api = ApiClient::new(config.clone(), Default::default());
+  api.log_in("alice".into(), PASSWORD.into()).await.unwrap();
+
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  assert_eq!(my_id(&accounts).await, Some(alice.clone()));
+  log_in(&accounts, "bob").await;
+  accounts.switch_account(&key(&alice));
+
+  let restarted = AccountManager::new(config.clone());
+  restarted.restore().await;
+  assert_eq!(restarted.list_accounts().await.len(), 2);
+  assert_eq!(my_id(&restarted).await, Some(alice.clone()));
+
+  // removing the active account logs it out everywhere
+  let result = restarted.remove_account(&key(&alice)).await;
+  assert!(matches!(result, AccountActionResult::Success));
+  assert!(server.device_keys(&alice).is_empty());
+  assert!(!account_dir(data_dir.path(), &key(&alice)).exists());
+  assert_eq!(my_id(&restarted).await, None);
+
+  let result = restarted.remove_account(&key(&alice)).await;
+  assert!(matches!(result, AccountActionResult::UnknownAccount));
+
+  let restarted = AccountManager::new(config);
+  restarted.restore().await;
+  let listed = restarted.list_accounts().await;
+  assert_eq!(listed.len(), 1);
+  assert_eq!(listed[0].user_id, bob);
+  assert!(!listed[0].active);
+}
+
+#[tokio::test]
+async fn deleted_accounts_are_removed() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+  let bob = server.add_user("bob", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  log_in(&accounts, "alice").await;
+  log_in(&accounts, "bob").await;
+
+  let result = accounts.delete_account("wrong password".into()).await;
+  assert!(matches!(result, Ok(DeleteAccountResult::WrongPassword)));
+  assert_eq!(accounts.list_accounts().await.len(), 2);
+
+  let result = accounts.delete_account(PASSWORD.into()).await;
+  assert!(matches!(result, Ok(DeleteAccountResult::Success)));
+  assert!(server.device_keys(&bob).is_empty());
+  assert!(!account_dir(data_dir.path(), &key(&bob)).exists());
+  assert_eq!(my_id(&accounts).await, None);
+
+  // it doesn't come back as a logged out account
+  let restarted = AccountManager::new(config);
+  restarted.restore().await;
+  let listed = restarted.list_accounts().await;
+  assert_eq!(listed.len(), 1);
+  assert_eq!(listed[0].user_id, alice);
+}
+
+#[tokio::test]
+async fn startup_restores_remembered_accounts() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+  let bob = server.add_user("bob", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  assert!(matches!(
+    accounts.auto_login().await,
+    AuthStateEventPayload::None
+  ));
+
+  log_in(&accounts, "alice").await;
+  let result = accounts.log_in("bob".into(), PASSWORD.into(), false).await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+
+  // bob didn't want to stay logged in, so only alice is left, and they aren't active
+  let restarted = AccountManager::new(config.clone());
+  restarted.restore().await;
+  assert!(matches!(
+    restarted.auto_login().await,
+    AuthStateEventPayload::None
+  ));
+  let listed = restarted.list_accounts().await;
+  assert_eq!(listed.len(), 1);
+  assert_eq!(listed[0].user_id, alice);
+  assert!(listed[0].auto_login);
+  // they're only logged out, so this is still one of their devices
+  assert_eq!(server.device_keys(&bob).len(), 1);
+
+  restarted.switch_account(&key(&alice));
+  let restarted = AccountManager::new(config.clone());
+  restarted.restore().await;
+  match restarted.auto_login().await {
+    AuthStateEventPayload::Restored { account, user } => {
+      assert_eq!(account, key(&alice));
+      assert_eq!(user.id, alice);
+      assert_eq!(user.username, "alice");
+    }
+    _ => panic!("alice's session wasn't restored"),
+  }
+
+  server.expire_tokens();
+  let restarted = AccountManager::new(config);
+  restarted.restore().await;
+  match restarted.auto_login().await {
+    AuthStateEventPayload::Expired { account } => assert_eq!(account, key(&alice)),
+    _ => panic!("alice's token didn't expire"),
+  }
+}
+
+#[tokio::test]
+async fn start_active_connects_as_the_active_account() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  log_in(&accounts, "alice").await;
+
+  // like the CLI, which restores the accounts that the app saved
+  let restarted = AccountManager::new(config);
+  restarted.restore().await;
+  let (sink, mut events) = recorder();
+  let account = restarted.start_active(sink);
+  assert_eq!(account.key, Some(key(&alice)));
+  wait_for_notification(&mut events, "connected").await;
+
+  account.ws.send_message("hello".into()).await.unwrap();
+  let payload = wait_for(&mut events, |event, _| event == "message").await;
+  assert_eq!(payload["userId"], alice.as_str());
+}
+
+#[tokio::test]
+async fn startup_connects_accounts_restored_after_starting() {
+  let server = MockServer::start().await;
+  let alice = server.add_user("alice", PASSWORD);
+
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  log_in(&accounts, "alice").await;
+
+  // like the app, which starts before the accounts have been loaded
+  let restarted = AccountManager::new(config);
+  let (sink, mut events) = recorder();
+  restarted.start(sink);
+  restarted.restore().await;
+  assert!(matches!(
+    restarted.auto_login().await,
+    AuthStateEventPayload::Restored { .. }
+  ));
+
+  let ws = restarted.active().ws.clone();
+  let send = async {
+    while ws.send_message("hello".into()).await.is_err() {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+  };
+  tokio::time::timeout(Duration::from_secs(5), send)
+    .await
+    .expect("alice didn't connect");
+
+  let payload = wait_for(&mut events, |event, _| event == "message").await;
+  assert_eq!(payload["userId"], alice.as_str());
+}
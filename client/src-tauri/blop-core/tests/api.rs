@@ -0,0 +1,297 @@
+mod support;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use blop_core::{
+  api::{ApiClient, LoginResult, MyInfoResult, VerifyTokenResult},
+  user::{
+    auth::{secret::Secret, AuthenticationState},
+    CreateUserResult,
+  },
+  Config,
+};
+use support::{Failure, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+/// Starts a mock server and returns a client for it, along with the client's data directory.
+async fn setup() -> (MockServer, ApiClient, TempDir) {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  (server, api, data_dir)
+}
+
+#[tokio::test]
+async fn create_user_logs_in() {
+  let (_server, api, _data_dir) = setup().await;
+
+  let result = api.create_user("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::Success(_))));
+
+  assert!(matches!(
+    api.verify_token().await,
+    Ok(VerifyTokenResult::Authorized)
+  ));
+  match api.my_info().await {
+    Ok(MyInfoResult::Success(user)) => assert_eq!(user.username, "jackson"),
+    _ => panic!("expected my_info to succeed"),
+  }
+}
+
+#[tokio::test]
+async fn create_user_while_logged_in() {
+  let (_server, api, _data_dir) = setup().await;
+
+  api
+    .create_user("jackson".into(), PASSWORD.into())
+    .await
+    .unwrap();
+
+  let result = api.create_user("someone".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::AlreadyLoggedIn)));
+}
+
+#[tokio::test]
+async fn create_user_duplicate() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+
+  let result = api.create_user("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(
+    result,
+    Ok(CreateUserResult::UsernameAlreadyExists)
+  ));
+}
+
+#[tokio::test]
+async fn create_user_is_validated_before_sending() {
+  let (server, api, _data_dir) = setup().await;
+
+  let result = api.create_user("jackson".into(), "short".into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::InvalidPassword(_))));
+
+  let result = api.create_user("j".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::InvalidUsername(_))));
+
+  assert_eq!(server.requests("/auth/create"), 0);
+}
+
+#[tokio::test]
+async fn create_user_bad_requests() {
+  let (server, api, _data_dir) = setup().await;
+
+  for (typ, error) in [
+    ("JSON", "invalid request"),
+    ("USERNAME", "invalid username"),
+    ("PASSWORD", "invalid password"),
+    ("SOMETHING", "invalid server response"),
+  ] {
+    server.fail_next("/auth/create", Failure::BadRequest(typ));
+
+    let result = api.create_user("jackson".into(), PASSWORD.into()).await;
+    assert_eq!(result.err().as_deref(), Some(error), "type {}", typ);
+  }
+}
+
+#[tokio::test]
+async fn create_user_malformed_response() {
+  let (server, api, _data_dir) = setup().await;
+  server.fail_next("/auth/create", Failure::MalformedJson);
+
+  let result = api.create_user("jackson".into(), PASSWORD.into()).await;
+  assert!(result.is_err());
+  assert!(matches!(
+    api.verify_token().await,
+    Ok(VerifyTokenResult::NotLoggedIn)
+  ));
+}
+
+#[tokio::test]
+async fn log_in() {
+  let (server, api, _data_dir) = setup().await;
+  let id = server.add_user("jackson", PASSWORD);
+
+  let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+  assert_eq!(api.auth().get_user_id(), Some(id));
+}
+
+#[tokio::test]
+async fn log_in_wrong_password() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+
+  let result = api.log_in("jackson".into(), "Password2!".into()).await;
+  assert!(matches!(result, Ok(LoginResult::Unauthorized)));
+  assert!(api.auth().get_token().is_none());
+}
+
+#[tokio::test]
+async fn log_in_unknown_user() {
+  let (_server, api, _data_dir) = setup().await;
+
+  let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(LoginResult::UserDoesNotExist)));
+}
+
+#[tokio::test]
+async fn log_in_failures() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+
+  for failure in [
+    Failure::BadRequest("JSON"),
+    Failure::MalformedJson,
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+    // no standard reason phrase
+    Failure::Status(StatusCode::from_u16(599).unwrap()),
+  ] {
+    server.fail_next("/auth/login", failure);
+
+    let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+    assert!(result.is_err(), "{:?}", failure);
+  }
+
+  assert!(api.auth().get_token().is_none());
+}
+
+#[tokio::test]
+async fn log_in_unreachable() {
+  let data_dir = TempDir::new().unwrap();
+
+  // nothing listens on the discard port locally
+  let config = Config {
+    api_url: "http://127.0.0.1:9/".into(),
+    data_dir: data_dir.path().into(),
+    ..Default::default()
+  };
+  let api = ApiClient::new(Arc::new(config), Default::default());
+
+  assert!(api.log_in("jackson".into(), PASSWORD.into()).await.is_err());
+}
+
+#[tokio::test]
+async fn verify_token_not_logged_in() {
+  let (_server, api, _data_dir) = setup().await;
+
+  assert!(matches!(
+    api.verify_token().await,
+    Ok(VerifyTokenResult::NotLoggedIn)
+  ));
+}
+
+#[tokio::test]
+async fn verify_token_expired() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+
+  server.expire_tokens();
+
+  assert!(matches!(
+    api.verify_token().await,
+    Ok(VerifyTokenResult::Expired)
+  ));
+  assert!(api.auth().get_token().is_none());
+}
+
+#[tokio::test]
+async fn verify_token_malformed() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+
+  server.fail_next("/auth/verify", Failure::Status(StatusCode::BAD_REQUEST));
+
+  assert!(api.verify_token().await.is_err());
+}
+
+#[tokio::test]
+async fn my_info_not_logged_in() {
+  let (server, api, _data_dir) = setup().await;
+
+  assert!(matches!(api.my_info().await, Ok(MyInfoResult::NotLoggedIn)));
+  assert_eq!(server.requests("/user/me"), 0);
+}
+
+#[tokio::test]
+async fn my_info_failures() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+
+  server.fail_next("/user/me", Failure::Unauthorized);
+  assert!(api.my_info().await.is_err());
+
+  server.fail_next("/user/me", Failure::MalformedJson);
+  assert!(api.my_info().await.is_err());
+
+  // no standard reason phrase
+  server.fail_next(
+    "/user/me",
+    Failure::Status(StatusCode::from_u16(599).unwrap()),
+  );
+  assert!(api.my_info().await.is_err());
+}
+
+#[tokio::test]
+async fn my_info_unreachable() {
+  let data_dir = TempDir::new().unwrap();
+  let auth = Arc::new(AuthenticationState::default());
+  auth.login(Secret::from("token".to_string()), "1".into());
+
+  let config = Config {
+    api_url: "http://127.0.0.1:9/".into(),
+    data_dir: data_dir.path().into(),
+    ..Default::default()
+  };
+  let api = ApiClient::new(Arc::new(config), auth);
+
+  assert!(api.my_info().await.is_err());
+}
+
+#[tokio::test]
+async fn user_exists() {
+  let (server, api, _data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+
+  assert_eq!(api.user_exists("jackson").await, Ok(true));
+  assert_eq!(api.user_exists("someone").await, Ok(false));
+  // invalid usernames don't need to be looked up
+  assert_eq!(api.user_exists("a").await, Ok(false));
+  assert_eq!(server.requests("/user/getid"), 2);
+}
+
+#[tokio::test]
+async fn user_exists_failures() {
+  let (server, api, _data_dir) = setup().await;
+  server.fail_next(
+    "/user/getid",
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+  );
+
+  assert!(api.user_exists("jackson").await.is_err());
+}
+
+#[tokio::test]
+async fn session_is_persisted() {
+  let (server, api, data_dir) = setup().await;
+  server.add_user("jackson", PASSWORD);
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+
+  let restored = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+  assert!(restored.restore_session().await);
+  assert!(matches!(
+    restored.verify_token().await,
+    Ok(VerifyTokenResult::Authorized)
+  ));
+
+  // logging out forgets the session too
+  server.expire_tokens();
+  restored.verify_token().await.unwrap();
+
+  let restored = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+  assert!(!restored.restore_session().await);
+}
@@ -0,0 +1,132 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use blop_core::{
+  api::ApiClient,
+  user::{auth::username::availability::Availability, CreateUserResult},
+};
+use support::{Failure, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+async fn setup(ttl: Duration) -> (MockServer, Arc<ApiClient>, TempDir) {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+
+  let mut config = server.config(data_dir.path());
+  config.availability_ttl = ttl;
+  let api = Arc::new(ApiClient::new(Arc::new(config), Default::default()));
+
+  (server, api, data_dir)
+}
+
+#[tokio::test]
+async fn check_username() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+  server.add_user("jackson", PASSWORD);
+
+  assert_eq!(api.check_username("jackson").await, Availability::Taken);
+  assert_eq!(api.check_username("someone").await, Availability::Available);
+}
+
+#[tokio::test]
+async fn errors_are_reported() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+  server.fail_next(
+    "/user/getid",
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+  );
+
+  assert!(matches!(
+    api.check_username("jackson").await,
+    Availability::Unknown { .. }
+  ));
+
+  // errors aren't cached
+  assert_eq!(api.check_username("jackson").await, Availability::Available);
+  assert_eq!(server.requests("/user/getid"), 2);
+}
+
+#[tokio::test]
+async fn answers_are_cached() {
+  let (server, api, _data_dir) = setup(Duration::from_millis(300)).await;
+
+  assert_eq!(api.check_username("jackson").await, Availability::Available);
+  server.add_user("jackson", PASSWORD);
+  assert_eq!(api.check_username("jackson").await, Availability::Available);
+  assert_eq!(server.requests("/user/getid"), 1);
+
+  tokio::time::sleep(Duration::from_millis(300)).await;
+  assert_eq!(api.check_username("jackson").await, Availability::Taken);
+  assert_eq!(server.requests("/user/getid"), 2);
+}
+
+#[tokio::test]
+async fn rapid_checks_are_debounced() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+
+  let mut checks = Vec::new();
+  for username in ["jack", "jacks", "jackso", "jackson"] {
+    let api = api.clone();
+    checks.push(tokio::spawn(
+      async move { api.check_username(username).await },
+    ));
+    tokio::time::sleep(Duration::from_millis(5)).await;
+  }
+
+  let mut results = Vec::new();
+  for x in checks {
+    results.push(x.await.unwrap());
+  }
+
+  assert_eq!(
+    results,
+    [
+      Availability::Superseded,
+      Availability::Superseded,
+      Availability::Superseded,
+      Availability::Available
+    ]
+  );
+  assert_eq!(server.requests("/user/getid"), 1);
+}
+
+#[tokio::test]
+async fn creating_a_user_clears_the_cache() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+
+  assert_eq!(api.check_username("jackson").await, Availability::Available);
+
+  let result = api.create_user("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::Success(_))));
+
+  assert_eq!(api.check_username("jackson").await, Availability::Taken);
+  assert_eq!(server.requests("/user/getid"), 2);
+}
+
+#[tokio::test]
+async fn invalid_usernames_are_reported() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+
+  match api.check_username("jack son").await {
+    Availability::Invalid(x) => assert!(!x.charset && x.length),
+    other => panic!("expected invalid, got {:?}", other),
+  }
+  assert!(matches!(
+    api.check_username("admin").await,
+    Availability::Invalid(_)
+  ));
+  assert_eq!(server.requests("/user/getid"), 0);
+}
+
+#[tokio::test]
+async fn lookalikes_share_answers() {
+  let (server, api, _data_dir) = setup(Duration::from_secs(30)).await;
+  server.add_user("jackson", PASSWORD);
+
+  assert_eq!(api.check_username("jackson").await, Availability::Taken);
+  assert_eq!(api.check_username("Jackson").await, Availability::Taken);
+  assert_eq!(api.check_username("JACKSON").await, Availability::Taken);
+  assert_eq!(server.requests("/user/getid"), 1);
+}
@@ -0,0 +1,87 @@
+use std::fs;
+
+use blop_core::user::auth::breach::{parse_hash_line, BreachedPasswords, HashList};
+use tempfile::TempDir;
+
+/// The SHA-1 hash of "tractor attic velvet", as it would appear in a Have I Been Pwned list.
+const TRACTOR_LINE: &str = "E6F8923944B8F305DBE60ABA228D9D84E01394D0:12";
+
+#[test]
+fn bundled_list_has_common_passwords() {
+  let breached = BreachedPasswords::bundled();
+
+  assert!(breached.contains("password"));
+  assert!(breached.contains("123456"));
+  assert!(!breached.contains("tractor attic velvet"));
+}
+
+#[test]
+fn parses_hash_lines() {
+  let hash = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+  assert_eq!(parse_hash_line(hash), Some(0x5baa61e4c9b93f3f));
+  assert_eq!(
+    parse_hash_line(&format!("{}:3861493", hash.to_lowercase())),
+    Some(0x5baa61e4c9b93f3f)
+  );
+  assert_eq!(parse_hash_line(&hash[..16]), None);
+  assert_eq!(parse_hash_line(""), None);
+  assert_eq!(parse_hash_line(&hash.replace('5', "Z")), None);
+}
+
+#[test]
+fn lists_round_trip() {
+  for count in [0u64, 1, 2, 1000] {
+    let keys: Vec<u64> = (0..count)
+      .map(|x| x.wrapping_mul(0x9e3779b97f4a7c15))
+      .collect();
+    let list = HashList::from_hashes(keys.iter().copied());
+    let list = HashList::from_bytes(&list.to_bytes()).unwrap();
+
+    assert_eq!(list.len(), count as usize);
+    assert!(keys.iter().all(|x| list.contains_hash(*x)));
+    assert!(!list.contains_hash(1));
+  }
+}
+
+#[test]
+fn malformed_lists_are_rejected() {
+  let bytes = HashList::from_hashes(vec![1, 2, 3]).to_bytes();
+
+  assert!(HashList::from_bytes(b"not a list").is_err());
+  assert!(HashList::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+  let mut wrong_version = bytes.clone();
+  wrong_version[8] = 2;
+  assert!(HashList::from_bytes(&wrong_version).is_err());
+
+  // an offset that points past the keys
+  let mut bad_index = bytes;
+  bad_index[18] = 0xff;
+  assert!(HashList::from_bytes(&bad_index).is_err());
+}
+
+#[test]
+fn imported_list_is_loaded() {
+  let data_dir = TempDir::new().unwrap();
+  let before = BreachedPasswords::load(data_dir.path());
+  assert!(!before.contains("tractor attic velvet"));
+
+  let list = HashList::from_hashes(parse_hash_line(TRACTOR_LINE));
+  BreachedPasswords::import(data_dir.path(), &list).unwrap();
+
+  let after = BreachedPasswords::load(data_dir.path());
+  assert!(after.contains("tractor attic velvet"));
+  // the bundled list is still checked
+  assert!(after.contains("password"));
+}
+
+#[test]
+fn unreadable_list_is_ignored() {
+  let data_dir = TempDir::new().unwrap();
+  fs::write(BreachedPasswords::path(data_dir.path()), "garbage").unwrap();
+
+  let breached = BreachedPasswords::load(data_dir.path());
+  assert!(breached.contains("password"));
+  assert!(!breached.contains("tractor attic velvet"));
+}
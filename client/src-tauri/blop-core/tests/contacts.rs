@@ -0,0 +1,184 @@
+mod support;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use blop_core::{
+  api::ApiClient,
+  user::contacts::{
+    Contact, ContactActionResult, ContactStatus, FriendRequestResult, ListContactsResult,
+  },
+};
+use serde_json::json;
+use support::{account, connect, wait_for, Account, Failure, MockServer};
+
+async fn contacts(account: &Account) -> Vec<Contact> {
+  match account.api.list_contacts().await {
+    Ok(ListContactsResult::Success { contacts, offline }) => {
+      assert!(!offline);
+      contacts
+    }
+    _ => panic!("expected to list contacts"),
+  }
+}
+
+fn contact(account: &Account, username: &str, status: ContactStatus) -> Contact {
+  Contact {
+    id: account.id.clone(),
+    username: username.into(),
+    status,
+  }
+}
+
+#[tokio::test]
+async fn friend_requests() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+
+  let result = alice.api.send_friend_request(&bob.id).await;
+  assert!(matches!(result, Ok(FriendRequestResult::Sent)));
+  assert_eq!(
+    alice.api.contacts().list(),
+    vec![contact(&bob, "bob", ContactStatus::Outgoing)]
+  );
+
+  assert_eq!(
+    contacts(&bob).await,
+    vec![contact(&alice, "alice", ContactStatus::Incoming)]
+  );
+
+  let result = bob.api.respond_friend_request(&alice.id, true).await;
+  assert!(matches!(result, Ok(ContactActionResult::Success)));
+  assert_eq!(
+    contacts(&alice).await,
+    vec![contact(&bob, "bob", ContactStatus::Friend)]
+  );
+
+  let result = alice.api.send_friend_request(&bob.id).await;
+  assert!(matches!(result, Ok(FriendRequestResult::AlreadyFriends)));
+
+  let result = alice.api.remove_friend(&bob.id).await;
+  assert!(matches!(result, Ok(ContactActionResult::Success)));
+  assert_eq!(contacts(&bob).await, vec![]);
+
+  let result = bob.api.respond_friend_request(&alice.id, true).await;
+  assert!(matches!(result, Ok(ContactActionResult::NotFound)));
+}
+
+#[tokio::test]
+async fn mutual_requests_are_accepted() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+
+  alice.api.send_friend_request(&bob.id).await.unwrap();
+  let result = bob.api.send_friend_request(&alice.id).await;
+  assert!(matches!(result, Ok(FriendRequestResult::Accepted)));
+
+  assert_eq!(
+    contacts(&alice).await,
+    vec![contact(&bob, "bob", ContactStatus::Friend)]
+  );
+}
+
+#[tokio::test]
+async fn blocking() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+
+  alice.api.send_friend_request(&bob.id).await.unwrap();
+  let result = bob.api.block_user(&alice.id).await;
+  assert!(matches!(result, Ok(ContactActionResult::Success)));
+
+  // blocking ends the request
+  assert_eq!(contacts(&alice).await, vec![]);
+  assert_eq!(
+    contacts(&bob).await,
+    vec![contact(&alice, "alice", ContactStatus::Blocked)]
+  );
+
+  let result = alice.api.send_friend_request(&bob.id).await;
+  assert!(matches!(result, Ok(FriendRequestResult::Blocked)));
+
+  let result = bob.api.unblock_user(&alice.id).await;
+  assert!(matches!(result, Ok(ContactActionResult::Success)));
+  assert_eq!(contacts(&bob).await, vec![]);
+
+  let result = alice.api.send_friend_request("nobody").await;
+  assert!(matches!(result, Ok(FriendRequestResult::UserDoesNotExist)));
+}
+
+#[tokio::test]
+async fn contacts_are_kept_for_offline_use() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+
+  alice.api.send_friend_request(&bob.id).await.unwrap();
+  let expected = vec![contact(&bob, "bob", ContactStatus::Outgoing)];
+
+  server.fail_next(
+    "/user/contacts",
+    Failure::Status(StatusCode::SERVICE_UNAVAILABLE),
+  );
+  match alice.api.list_contacts().await {
+    Ok(ListContactsResult::Success { contacts, offline }) => {
+      assert!(offline);
+      assert_eq!(contacts, expected);
+    }
+    _ => panic!("expected the saved contacts"),
+  }
+
+  // they survive a restart too
+  let config = server.config(alice.data_dir.path());
+  let restarted = ApiClient::new(Arc::new(config), Default::default());
+  assert!(restarted.restore_session().await);
+  assert_eq!(restarted.contacts().list(), expected);
+
+  // but not logging out
+  server.expire_tokens();
+  restarted.list_contacts().await.unwrap();
+  assert_eq!(restarted.contacts().list(), vec![]);
+}
+
+#[tokio::test]
+async fn friend_requests_arrive_as_events() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+  let (_ws, mut events) = connect(&server, &bob).await;
+
+  alice.api.send_friend_request(&bob.id).await.unwrap();
+
+  let payload = wait_for(&mut events, |event, _| event == "friend_request").await;
+  assert_eq!(payload["userId"], alice.id.as_str());
+  assert_eq!(payload["username"], "alice");
+  assert_eq!(
+    bob.api.contacts().get(&alice.id),
+    Some(contact(&alice, "alice", ContactStatus::Incoming))
+  );
+}
+
+#[tokio::test]
+async fn blocked_users_are_filtered() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+  let carol = account(&server, "carol").await;
+  bob.api.block_user(&alice.id).await.unwrap();
+  let (_ws, mut events) = connect(&server, &bob).await;
+
+  for user in [&alice, &carol] {
+    server.broadcast(&json!({ "type": "typing", "userId": user.id }).to_string());
+    server.broadcast(&json!({ "type": "message", "userId": user.id, "message": "hi" }).to_string());
+  }
+
+  // frames arrive in order, so alice's would have come first
+  let typing = wait_for(&mut events, |event, _| event == "typing").await;
+  assert_eq!(typing["userId"], carol.id.as_str());
+  let message = wait_for(&mut events, |event, _| event == "message").await;
+  assert_eq!(message["userId"], carol.id.as_str());
+  assert_eq!(message["message"], "hi");
+}
@@ -0,0 +1,212 @@
+mod support;
+
+use blop_core::e2e::{
+  keys::{encode_public_key, Identity},
+  DeviceInfo, ListDevicesResult, VerifyDeviceResult,
+};
+use support::{connect, log_in, recorder, wait_for, Account, Event, MockServer, PASSWORD};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+fn device_id(device: &Account) -> String {
+  device.api.keyring().identity().unwrap().device_id
+}
+
+async fn list_devices(device: &Account, user_id: &str) -> Vec<DeviceInfo> {
+  match device.api.list_devices(user_id).await {
+    Ok(ListDevicesResult::Success { devices }) => devices,
+    _ => panic!("expected devices"),
+  }
+}
+
+/// Returns the device warnings that were recorded so far, as `(device ID, reason)`.
+fn warnings(events: &mut UnboundedReceiver<Event>) -> Vec<(String, String)> {
+  let mut warnings = Vec::new();
+  while let Ok((event, payload)) = events.try_recv() {
+    if event == "device_warning" {
+      warnings.push((
+        payload["deviceId"].as_str().unwrap().into(),
+        payload["reason"].as_str().unwrap().into(),
+      ));
+    }
+  }
+  warnings
+}
+
+#[tokio::test]
+async fn messages_reach_every_device() {
+  let server = MockServer::start().await;
+  let alice_id = server.add_user("alice", PASSWORD);
+  let bob_id = server.add_user("bob", PASSWORD);
+
+  let alice = log_in(&server, "alice").await;
+  let laptop = log_in(&server, "bob").await;
+  let desktop = log_in(&server, "bob").await;
+  assert_eq!(server.device_keys(&bob_id).len(), 2);
+
+  let (alice_ws, _alice_events) = connect(&server, &alice).await;
+  let (_laptop_ws, mut laptop_events) = connect(&server, &laptop).await;
+  let (_desktop_ws, mut desktop_events) = connect(&server, &desktop).await;
+
+  alice_ws
+    .send_direct_message(&bob_id, "meet at noon")
+    .await
+    .unwrap();
+
+  for events in [&mut laptop_events, &mut desktop_events] {
+    let payload = wait_for(events, |event, _| event == "direct_message").await;
+    assert_eq!(payload["userId"], alice_id.as_str());
+    assert_eq!(payload["message"], "meet at noon");
+  }
+}
+
+#[tokio::test]
+async fn unexpected_device_changes_are_warned_about() {
+  let server = MockServer::start().await;
+  server.add_user("alice", PASSWORD);
+  let bob_id = server.add_user("bob", PASSWORD);
+
+  let alice = log_in(&server, "alice").await;
+  let (sink, mut events) = recorder();
+  alice.api.keyring().set_event_sink(sink);
+
+  // the first devices that we see are trusted as they are
+  let laptop = log_in(&server, "bob").await;
+  assert_eq!(list_devices(&alice, &bob_id).await.len(), 1);
+  assert!(warnings(&mut events).is_empty());
+
+  // a device that a known one vouched for is expected
+  let desktop = log_in(&server, "bob").await;
+  let fingerprint = list_devices(&desktop, &bob_id)
+    .await
+    .into_iter()
+    .find(|x| x.current)
+    .unwrap()
+    .fingerprint;
+  let result = laptop
+    .api
+    .verify_device(&bob_id, &device_id(&desktop), &fingerprint)
+    .await;
+  assert!(matches!(result, Ok(VerifyDeviceResult::Verified)));
+
+  let devices = list_devices(&alice, &bob_id).await;
+  assert_eq!(devices.len(), 2);
+  assert!(devices.iter().any(|x| x.cross_signed));
+  assert!(warnings(&mut events).is_empty());
+
+  // one that nobody vouched for isn't
+  let phone = log_in(&server, "bob").await;
+  list_devices(&alice, &bob_id).await;
+  assert_eq!(
+    warnings(&mut events),
+    [(device_id(&phone), "newDevice".into())]
+  );
+
+  // and neither are new keys for a device that we already knew
+  let mallory = Identity::generate(&bob_id).device();
+  server.set_device(
+    &bob_id,
+    &device_id(&laptop),
+    &encode_public_key(&mallory.public_key),
+    &base64::encode(mallory.signing_key.as_bytes()),
+  );
+  list_devices(&alice, &bob_id).await;
+  assert_eq!(
+    warnings(&mut events),
+    [(device_id(&laptop), "keyChanged".into())]
+  );
+}
+
+#[tokio::test]
+async fn devices_are_verified_by_fingerprint() {
+  let server = MockServer::start().await;
+  server.add_user("alice", PASSWORD);
+  let bob_id = server.add_user("bob", PASSWORD);
+
+  let alice = log_in(&server, "alice").await;
+  let bob = log_in(&server, "bob").await;
+  let bob_device = device_id(&bob);
+
+  let result = alice.api.verify_device(&bob_id, "nope", "").await;
+  assert!(matches!(result, Ok(VerifyDeviceResult::UnknownDevice)));
+
+  let result = alice
+    .api
+    .verify_device(&bob_id, &bob_device, &"0".repeat(30))
+    .await;
+  assert!(matches!(result, Ok(VerifyDeviceResult::Mismatch)));
+  assert!(!list_devices(&alice, &bob_id).await[0].verified);
+
+  // bob reads it off of their screen, and alice types it without the spaces
+  let mine = list_devices(&bob, &bob_id).await.remove(0);
+  assert!(mine.current);
+  let typed = mine.fingerprint.replace(' ', "");
+  let result = alice.api.verify_device(&bob_id, &bob_device, &typed).await;
+  assert!(matches!(result, Ok(VerifyDeviceResult::Verified)));
+
+  let theirs = list_devices(&alice, &bob_id).await.remove(0);
+  assert_eq!(theirs.fingerprint, mine.fingerprint);
+  assert!(theirs.verified);
+  assert!(!theirs.current);
+  // only their own devices can vouch for it
+  assert!(!theirs.cross_signed);
+}
+
+#[tokio::test]
+async fn logging_in_again_is_the_same_device() {
+  let server = MockServer::start().await;
+  server.add_user("alice", PASSWORD);
+  let bob_id = server.add_user("bob", PASSWORD);
+
+  let alice = log_in(&server, "alice").await;
+  let (sink, mut events) = recorder();
+  alice.api.keyring().set_event_sink(sink);
+
+  let laptop = log_in(&server, "bob").await;
+  let desktop = log_in(&server, "bob").await;
+  let fingerprint = list_devices(&desktop, &bob_id)
+    .await
+    .into_iter()
+    .find(|x| x.current)
+    .unwrap()
+    .fingerprint;
+  let result = laptop
+    .api
+    .verify_device(&bob_id, &device_id(&desktop), &fingerprint)
+    .await;
+  assert!(matches!(result, Ok(VerifyDeviceResult::Verified)));
+  assert_eq!(list_devices(&alice, &bob_id).await.len(), 2);
+  warnings(&mut events);
+
+  // the desktop keeps its keys, so it's neither new to alice nor missing the laptop's signature
+  let before = device_id(&desktop);
+  desktop.api.end_session().await;
+  desktop
+    .api
+    .log_in("bob".into(), PASSWORD.into())
+    .await
+    .unwrap();
+  assert_eq!(device_id(&desktop), before);
+
+  let devices = list_devices(&alice, &bob_id).await;
+  assert_eq!(devices.len(), 2);
+  assert!(devices
+    .iter()
+    .any(|x| x.device_id == before && x.cross_signed));
+  assert!(warnings(&mut events).is_empty());
+
+  // and it still knows the laptop, even if someone swapped its keys while we were logged out
+  let (sink, mut desktop_events) = recorder();
+  desktop.api.keyring().set_event_sink(sink);
+  let mallory = Identity::generate(&bob_id).device();
+  server.set_device(
+    &bob_id,
+    &device_id(&laptop),
+    &encode_public_key(&mallory.public_key),
+    &base64::encode(mallory.signing_key.as_bytes()),
+  );
+  list_devices(&desktop, &bob_id).await;
+  assert_eq!(
+    warnings(&mut desktop_events),
+    [(device_id(&laptop), "keyChanged".into())]
+  );
+}
@@ -0,0 +1,121 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use blop_core::{api::ApiClient, Config};
+use support::{Failure, MockServer};
+use tempfile::TempDir;
+
+async fn setup(configure: impl FnOnce(&mut Config)) -> (MockServer, ApiClient, TempDir) {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+
+  let mut config = server.config(data_dir.path());
+  configure(&mut config);
+  let api = ApiClient::new(Arc::new(config), Default::default());
+
+  (server, api, data_dir)
+}
+
+fn ids(ids: &[&str]) -> Vec<String> {
+  ids.iter().map(|x| x.to_string()).collect()
+}
+
+#[tokio::test]
+async fn resolve_users() {
+  let (server, api, _data_dir) = setup(|_| ()).await;
+  let jackson = server.add_user("jackson", "password");
+  let oliver = server.add_user("oliver", "password");
+
+  let users = api
+    .resolve_users(&ids(&[&jackson, &oliver, "nobody"]))
+    .await
+    .unwrap();
+  assert_eq!(users.len(), 3);
+  assert_eq!(users[&jackson].as_ref().unwrap().username, "jackson");
+  assert_eq!(users[&oliver].as_ref().unwrap().username, "oliver");
+  assert_eq!(users["nobody"], None);
+
+  // everything is cached now, including the ID that doesn't exist
+  api
+    .resolve_users(&ids(&[&jackson, "nobody"]))
+    .await
+    .unwrap();
+  assert_eq!(server.requests("/user/resolve"), 1);
+
+  let directory = api.directory();
+  assert_eq!(directory.get_by_username("oliver"), users[&oliver]);
+  assert_eq!(directory.get_by_username("nobody"), None);
+}
+
+#[tokio::test]
+async fn concurrent_lookups_are_batched() {
+  let (server, api, _data_dir) =
+    setup(|x| x.directory_batch_window = Duration::from_millis(50)).await;
+  let jackson = server.add_user("jackson", "password");
+  let oliver = server.add_user("oliver", "password");
+
+  let (first, second, third) = (
+    ids(&[&jackson]),
+    ids(&[&oliver]),
+    ids(&[&jackson, "nobody"]),
+  );
+  let (a, b, c) = tokio::join!(
+    api.resolve_users(&first),
+    api.resolve_users(&second),
+    api.resolve_users(&third),
+  );
+
+  assert_eq!(a.unwrap()[&jackson].as_ref().unwrap().username, "jackson");
+  assert_eq!(b.unwrap()[&oliver].as_ref().unwrap().username, "oliver");
+  assert_eq!(c.unwrap()["nobody"], None);
+  assert_eq!(server.requests("/user/resolve"), 1);
+}
+
+#[tokio::test]
+async fn entries_expire() {
+  let (server, api, _data_dir) = setup(|x| x.directory_ttl = Duration::from_millis(100)).await;
+  let jackson = server.add_user("jackson", "password");
+
+  api.resolve_users(&ids(&[&jackson])).await.unwrap();
+  tokio::time::sleep(Duration::from_millis(150)).await;
+  api.resolve_users(&ids(&[&jackson])).await.unwrap();
+
+  assert_eq!(server.requests("/user/resolve"), 2);
+}
+
+#[tokio::test]
+async fn least_recently_used_entries_are_evicted() {
+  let (server, api, _data_dir) = setup(|x| x.directory_capacity = 2).await;
+  let jackson = server.add_user("jackson", "password");
+  let oliver = server.add_user("oliver", "password");
+  let emma = server.add_user("emma", "password");
+
+  api.resolve_users(&ids(&[&jackson])).await.unwrap();
+  api.resolve_users(&ids(&[&oliver])).await.unwrap();
+  // jackson was used more recently than oliver now
+  api.resolve_users(&ids(&[&jackson])).await.unwrap();
+  api.resolve_users(&ids(&[&emma])).await.unwrap();
+  assert_eq!(server.requests("/user/resolve"), 3);
+
+  let directory = api.directory();
+  assert!(directory.get(&jackson).is_some());
+  assert!(directory.get(&oliver).is_none());
+}
+
+#[tokio::test]
+async fn failures_are_not_cached() {
+  let (server, api, _data_dir) = setup(|_| ()).await;
+  let jackson = server.add_user("jackson", "password");
+
+  server.fail_next(
+    "/user/resolve",
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+  );
+  assert!(api.resolve_users(&ids(&[&jackson])).await.is_err());
+
+  let users = api.resolve_users(&ids(&[&jackson])).await.unwrap();
+  assert_eq!(users[&jackson].as_ref().unwrap().username, "jackson");
+  assert_eq!(server.requests("/user/resolve"), 2);
+}
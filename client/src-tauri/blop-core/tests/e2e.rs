@@ -0,0 +1,203 @@
+mod support;
+
+use std::sync::Arc;
+
+use blop_core::{
+  api::ApiClient,
+  e2e::{
+    keys::{encode_public_key, Identity},
+    open, seal, SafetyNumberResult,
+  },
+};
+use serde_json::json;
+use support::{account, connect, wait_for, Account, MockServer, PASSWORD};
+
+async fn safety_number(account: &Account, user_id: &str) -> String {
+  match account.api.safety_number(user_id).await {
+    Ok(SafetyNumberResult::Success { safety_number }) => safety_number,
+    _ => panic!("expected a safety number"),
+  }
+}
+
+#[tokio::test]
+async fn sealed_messages_only_open_for_their_recipient() {
+  let alice = Identity::generate("alice");
+  let bob = Identity::generate("bob");
+  let carol = Identity::generate("carol");
+
+  let envelope = seal(&alice, "bob", &[bob.device()], "meet at noon");
+  assert_eq!(
+    open(&bob, "alice", &alice.device(), &envelope).as_deref(),
+    Ok("meet at noon")
+  );
+
+  // someone else can't read it
+  assert!(open(&carol, "alice", &alice.device(), &envelope).is_err());
+  // and the server can't say that it came from someone else
+  assert!(open(&bob, "carol", &alice.device(), &envelope).is_err());
+
+  let mut tampered = envelope.clone();
+  let sealed = tampered.sealed.get_mut(&bob.device_id).unwrap();
+  let mut ciphertext = base64::decode(&sealed.ciphertext).unwrap();
+  ciphertext[0] ^= 1;
+  sealed.ciphertext = base64::encode(ciphertext);
+  assert!(open(&bob, "alice", &alice.device(), &tampered).is_err());
+
+  // every message gets its own nonce
+  let again = seal(&alice, "bob", &[bob.device()], "meet at noon");
+  assert_ne!(
+    envelope.sealed[&bob.device_id].nonce,
+    again.sealed[&bob.device_id].nonce
+  );
+}
+
+#[tokio::test]
+async fn direct_messages_are_end_to_end_encrypted() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+  let (alice_ws, _alice_events) = connect(&server, &alice).await;
+  let (_bob_ws, mut bob_events) = connect(&server, &bob).await;
+
+  alice_ws
+    .send_direct_message(&bob.id, "meet at noon")
+    .await
+    .unwrap();
+
+  let payload = wait_for(&mut bob_events, |event, _| event == "direct_message").await;
+  assert_eq!(payload["userId"], alice.id.as_str());
+  assert_eq!(payload["message"], "meet at noon");
+
+  // the server only ever saw ciphertext
+  let messages = server.messages();
+  assert_eq!(messages.len(), 1);
+  assert!(!messages[0].contains("noon"));
+}
+
+#[tokio::test]
+async fn messages_with_unpublished_keys_are_dropped() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+  let (_bob_ws, mut bob_events) = connect(&server, &bob).await;
+
+  let bob_device = bob.api.keyring().identity().unwrap().device();
+  let real = alice.api.keyring().identity().unwrap();
+  // the same device ID, but keys that were never published
+  let mut forger = Identity::generate(&alice.id);
+  forger.device_id = real.device_id.clone();
+
+  for (identity, message) in [(&forger, "forged"), (&real, "real")] {
+    let envelope = seal(
+      identity,
+      &bob.id,
+      std::slice::from_ref(&bob_device),
+      message,
+    );
+    let frame = json!({ "type": "dm", "userId": alice.id, "envelope": envelope, "sentAt": 0 });
+    server.broadcast(&frame.to_string());
+  }
+
+  // frames arrive in order, so the forged one would have come first
+  let payload = wait_for(&mut bob_events, |event, _| event == "direct_message").await;
+  assert_eq!(payload["message"], "real");
+}
+
+#[tokio::test]
+async fn safety_numbers() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+  let bob = account(&server, "bob").await;
+
+  let number = safety_number(&alice, &bob.id).await;
+  assert_eq!(number, safety_number(&bob, &alice.id).await);
+
+  let groups: Vec<&str> = number.split(' ').collect();
+  assert_eq!(groups.len(), 12);
+  assert!(groups
+    .iter()
+    .all(|x| x.len() == 5 && x.chars().all(|x| x.is_ascii_digit())));
+
+  // if the server hands out someone else's key, the numbers don't match anymore
+  let mallory = Identity::generate(&bob.id).device();
+  server.set_device(
+    &bob.id,
+    &mallory.device_id,
+    &encode_public_key(&mallory.public_key),
+    &base64::encode(mallory.signing_key.as_bytes()),
+  );
+  assert_ne!(safety_number(&alice, &bob.id).await, number);
+
+  let carol = server.add_user("carol", PASSWORD);
+  let result = alice.api.safety_number(&carol).await;
+  assert!(matches!(result, Ok(SafetyNumberResult::NoKey)));
+}
+
+#[tokio::test]
+async fn identity_keys_are_kept_with_the_session() {
+  let server = MockServer::start().await;
+  let alice = account(&server, "alice").await;
+
+  let identity = alice.api.keyring().identity().unwrap();
+  let published = server.device_keys(&alice.id);
+  assert_eq!(
+    published.get(&identity.device_id),
+    Some(&encode_public_key(&identity.public_key()))
+  );
+
+  // restarting keeps the key
+  let config = server.config(alice.data_dir.path());
+  let restarted = ApiClient::new(Arc::new(config), Default::default());
+  assert!(restarted.restore_session().await);
+  let restored = restarted.keyring().identity().unwrap();
+  assert_eq!(restored.device(), identity.device());
+
+  // logging out keeps the device, and logging in again is the same one
+  restarted.end_session().await;
+  assert_eq!(server.device_keys(&alice.id), published);
+  assert!(restarted.keyring().identity().is_none());
+  restarted
+    .log_in("alice".into(), PASSWORD.into())
+    .await
+    .unwrap();
+  let again = restarted.keyring().identity().unwrap();
+  assert_eq!(again.device(), identity.device());
+  assert_eq!(server.device_keys(&alice.id), published);
+
+  // forgetting the device removes it, and logging in again makes a new one
+  restarted.forget_device().await;
+  assert!(server.device_keys(&alice.id).is_empty());
+  restarted
+    .log_in("alice".into(), PASSWORD.into())
+    .await
+    .unwrap();
+  let replaced = server.device_keys(&alice.id);
+  assert_eq!(replaced.len(), 1);
+  assert!(!published.contains_key(replaced.keys().next().unwrap()));
+}
+
+#[test]
+fn saved_identities_are_checked() {
+  let identity = Identity::generate("alice");
+  let saved = serde_json::to_value(&identity).unwrap();
+  let loaded: Identity = serde_json::from_value(saved.clone()).unwrap();
+  assert_eq!(loaded.device(), identity.device());
+
+  let load = |secret_key: String| {
+    let mut corrupt = saved.clone();
+    corrupt["secretKey"] = json!(secret_key);
+    match serde_json::from_value::<Identity>(corrupt) {
+      Ok(_) => panic!("expected an error"),
+      Err(e) => e.to_string(),
+    }
+  };
+  assert_eq!(load("!".repeat(44)), "identity key isn't valid base64");
+  assert_eq!(
+    load(base64::encode([0; 16])),
+    "identity key has the wrong length"
+  );
+  assert_eq!(
+    load(base64::encode([0; 33])),
+    "identity key has the wrong length"
+  );
+}
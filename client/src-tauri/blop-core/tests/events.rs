@@ -0,0 +1,37 @@
+mod support;
+
+use blop_core::events::{
+  emit, Event, LatencyEventPayload, LostConnectionReason, NotificationEventPayload,
+  SessionChangedEventPayload,
+};
+use serde_json::json;
+use support::recorder;
+
+#[tokio::test]
+async fn events_are_emitted_with_their_names() {
+  let (sink, mut events) = recorder();
+
+  emit(&*sink, Event::Latency(LatencyEventPayload { latency: 12 }));
+  emit(
+    &*sink,
+    Event::SessionChanged(SessionChangedEventPayload { user_id: None }),
+  );
+  emit(
+    &*sink,
+    Event::Notification(NotificationEventPayload::LostConnection {
+      reason: LostConnectionReason::PongTimeout,
+    }),
+  );
+
+  let expected = [
+    ("latency", json!({ "latency": 12 })),
+    ("session_changed", json!({ "userId": null })),
+    (
+      "notification",
+      json!({ "type": "lostConnection", "reason": "pongTimeout" }),
+    ),
+  ];
+  for (name, payload) in expected {
+    assert_eq!(events.recv().await.unwrap(), (name.to_string(), payload));
+  }
+}
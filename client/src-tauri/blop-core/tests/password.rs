@@ -0,0 +1,212 @@
+mod support;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use blop_core::{
+  api::ApiClient,
+  user::{
+    auth::{
+      breach::BreachedPasswords,
+      password::{PasswordPolicy, PasswordRule, PasswordValidation},
+    },
+    CreateUserResult,
+  },
+};
+use support::{Failure, MockServer};
+use tempfile::TempDir;
+
+/// A policy with every rule turned on.
+fn strict_policy() -> PasswordPolicy {
+  PasswordPolicy {
+    min_length: Some(8),
+    max_length: Some(16),
+    require_upper: true,
+    require_lower: true,
+    require_digit: true,
+    require_symbol: true,
+    ascii_only: true,
+    min_strength: None,
+    reject_breached: true,
+  }
+}
+
+/// Returns the rules that `password` fails under `policy`.
+fn failed_rules(policy: &PasswordPolicy, password: &str) -> Vec<PasswordRule> {
+  match policy.validate(password, &BreachedPasswords::bundled()) {
+    PasswordValidation::Valid(_) => vec![],
+    PasswordValidation::Invalid(criteria) => criteria
+      .rules
+      .into_iter()
+      .filter(|x| !x.passed)
+      .map(|x| x.id)
+      .collect(),
+  }
+}
+
+#[test]
+fn default_policy_allows_passphrases() {
+  let policy = PasswordPolicy::default();
+
+  assert!(policy
+    .validate(
+      "correct horse battery staple",
+      &BreachedPasswords::bundled()
+    )
+    .is_valid());
+  assert!(policy
+    .validate("pässwörter sind schön", &BreachedPasswords::bundled())
+    .is_valid());
+  assert_eq!(
+    failed_rules(&policy, "short"),
+    [PasswordRule::MinLength, PasswordRule::Strength]
+  );
+  assert_eq!(
+    failed_rules(&policy, "Password1!"),
+    [PasswordRule::Strength]
+  );
+  assert_eq!(
+    failed_rules(&policy, "password"),
+    [PasswordRule::Strength, PasswordRule::NotBreached]
+  );
+}
+
+#[test]
+fn length_counts_characters() {
+  let policy = PasswordPolicy {
+    min_length: Some(4),
+    max_length: Some(4),
+    min_strength: None,
+    ..Default::default()
+  };
+
+  assert!(policy
+    .validate("ßßßß", &BreachedPasswords::bundled())
+    .is_valid());
+  assert_eq!(failed_rules(&policy, "ßßß"), [PasswordRule::MinLength]);
+  assert_eq!(failed_rules(&policy, "ßßßßß"), [PasswordRule::MaxLength]);
+}
+
+#[test]
+fn strict_policy_rules() {
+  let policy = strict_policy();
+
+  assert!(policy
+    .validate("Password1!", &BreachedPasswords::bundled())
+    .is_valid());
+  assert_eq!(
+    failed_rules(&policy, "password"),
+    [
+      PasswordRule::Upper,
+      PasswordRule::Digit,
+      PasswordRule::Symbol,
+      PasswordRule::NotBreached
+    ]
+  );
+  assert_eq!(failed_rules(&policy, "PASSWORD1!"), [PasswordRule::Lower]);
+  assert_eq!(
+    failed_rules(&policy, "Pässword1!"),
+    [PasswordRule::AsciiOnly]
+  );
+  assert_eq!(
+    failed_rules(&policy, "Password1!Password1!"),
+    [PasswordRule::MaxLength]
+  );
+}
+
+#[test]
+fn disabled_rules_are_omitted() {
+  let policy = PasswordPolicy {
+    min_length: None,
+    max_length: None,
+    min_strength: None,
+    reject_breached: false,
+    ..Default::default()
+  };
+
+  assert!(policy
+    .validate("", &BreachedPasswords::bundled())
+    .is_valid());
+
+  let policy = PasswordPolicy {
+    require_digit: true,
+    ..Default::default()
+  };
+
+  match policy.validate("password", &BreachedPasswords::bundled()) {
+    PasswordValidation::Invalid(criteria) => {
+      let ids: Vec<_> = criteria.rules.iter().map(|x| x.id).collect();
+      assert_eq!(
+        ids,
+        [
+          PasswordRule::MinLength,
+          PasswordRule::MaxLength,
+          PasswordRule::Digit,
+          PasswordRule::Strength,
+          PasswordRule::NotBreached
+        ]
+      );
+      assert_eq!(criteria.rules[2].message, "have a digit");
+    }
+    PasswordValidation::Valid(_) => panic!("expected password to be invalid"),
+  }
+}
+
+#[test]
+fn policy_deserializes_partially() {
+  let policy: PasswordPolicy = serde_json::from_str(r#"{ "requireDigit": true }"#).unwrap();
+
+  assert_eq!(
+    policy,
+    PasswordPolicy {
+      require_digit: true,
+      ..Default::default()
+    }
+  );
+}
+
+#[tokio::test]
+async fn policy_is_fetched_once() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+  server.set_password_policy(strict_policy());
+
+  assert_eq!(api.password_policy().await, strict_policy());
+  assert!(!api.validate_password("password").await.is_valid());
+  assert_eq!(server.requests("/auth/policy"), 1);
+
+  let result = api.create_user("jackson".into(), "password".into()).await;
+  assert!(matches!(result, Ok(CreateUserResult::InvalidPassword(_))));
+  assert_eq!(server.requests("/auth/create"), 0);
+}
+
+#[tokio::test]
+async fn policy_falls_back_to_default() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+  server.set_password_policy(strict_policy());
+  server.fail_next(
+    "/auth/policy",
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+  );
+
+  assert_eq!(api.password_policy().await, PasswordPolicy::default());
+
+  // the failure wasn't cached
+  assert_eq!(api.password_policy().await, strict_policy());
+}
+
+#[tokio::test]
+async fn config_policy_takes_precedence() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+
+  let mut config = server.config(data_dir.path());
+  config.password_policy = Some(strict_policy());
+  let api = ApiClient::new(Arc::new(config), Default::default());
+
+  assert_eq!(api.password_policy().await, strict_policy());
+  assert_eq!(server.requests("/auth/policy"), 0);
+}
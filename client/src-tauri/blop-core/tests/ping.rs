@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use blop_core::websocket::{Ping, PingAction, PingPayload, Pong, PongMatch};
+
+const MAX_MISSED: u32 = 3;
+
+#[test]
+fn pong_round_trip() {
+  let payload = PingPayload {
+    seq: 7,
+    sent_at: 1655000000000,
+  };
+
+  let mut data = payload.encode();
+  assert_eq!(Pong::parse(&data).unwrap().ping, payload);
+  assert_eq!(Pong::parse(&data).unwrap().server_time, None);
+
+  data.extend(b":1655000000123");
+  let pong = Pong::parse(&data).unwrap();
+  assert_eq!(pong.ping, payload);
+  assert_eq!(pong.server_time, Some(1655000000123));
+
+  assert_eq!(Pong::parse(b":)"), None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn round_trip_time() {
+  let mut ping = Ping::default();
+
+  assert_eq!(ping.tick(MAX_MISSED), PingAction::Send);
+  let payload = ping.next_payload();
+  ping.sent(payload);
+
+  tokio::time::advance(Duration::from_millis(30)).await;
+
+  assert_eq!(
+    ping.ponged(Some(payload)),
+    PongMatch::Matched(Duration::from_millis(30))
+  );
+  assert_eq!(ping.tick(MAX_MISSED), PingAction::Send);
+}
+
+#[tokio::test(start_paused = true)]
+async fn times_out_after_missed_pongs() {
+  let mut ping = Ping::default();
+
+  ping.tick(MAX_MISSED);
+  let payload = ping.next_payload();
+  ping.sent(payload);
+
+  for _ in 1..MAX_MISSED {
+    tokio::time::advance(Duration::from_secs(5)).await;
+    assert_eq!(ping.tick(MAX_MISSED), PingAction::Wait);
+  }
+
+  tokio::time::advance(Duration::from_secs(5)).await;
+  assert_eq!(ping.tick(MAX_MISSED), PingAction::Timeout);
+
+  // once the connection is reset, the old pong is stale
+  ping.reset();
+  assert_eq!(ping.ponged(Some(payload)), PongMatch::Stale);
+  assert_eq!(ping.tick(MAX_MISSED), PingAction::Send);
+}
+
+#[tokio::test(start_paused = true)]
+async fn late_pong_resets_missed_count() {
+  let mut ping = Ping::default();
+
+  ping.tick(MAX_MISSED);
+  let payload = ping.next_payload();
+  ping.sent(payload);
+
+  for _ in 1..MAX_MISSED {
+    tokio::time::advance(Duration::from_secs(5)).await;
+    assert_eq!(ping.tick(MAX_MISSED), PingAction::Wait);
+  }
+
+  assert!(matches!(ping.ponged(Some(payload)), PongMatch::Matched(_)));
+  assert_eq!(ping.tick(MAX_MISSED), PingAction::Send);
+}
+
+#[test]
+fn unmatched_and_stale_pongs_are_counted() {
+  let mut ping = Ping::default();
+
+  let first = ping.next_payload();
+  ping.sent(first);
+  let second = ping.next_payload();
+  ping.sent(second);
+
+  // the server bundled the first ping with the second
+  assert_eq!(ping.ponged(Some(first)), PongMatch::Stale);
+  // a pong for a ping we never sent
+  let unknown = PingPayload {
+    seq: 100,
+    sent_at: 0,
+  };
+  assert_eq!(ping.ponged(Some(unknown)), PongMatch::Unmatched);
+  assert_eq!(ping.ponged(None), PongMatch::Unmatched);
+  assert!(matches!(ping.ponged(Some(second)), PongMatch::Matched(_)));
+  // answering the same ping twice
+  assert_eq!(ping.ponged(Some(second)), PongMatch::Stale);
+
+  let stats = ping.stats();
+  assert_eq!(stats.sent, 2);
+  assert_eq!(stats.matched, 1);
+  assert_eq!(stats.stale, 2);
+  assert_eq!(stats.unmatched, 2);
+}
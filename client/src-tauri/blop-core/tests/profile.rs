@@ -0,0 +1,146 @@
+mod support;
+
+use blop_core::{
+  api::MyInfoResult,
+  user::profile::{Avatar, ProfileField, ProfileUpdate, UpdateProfileResult},
+};
+use serde_json::json;
+use support::{connect, log_in, logged_in, wait_for};
+
+fn update() -> ProfileUpdate {
+  ProfileUpdate {
+    display_name: Some("Jackson".into()),
+    avatar: Some(Avatar::Url {
+      url: "https://example.com/jackson.png".into(),
+    }),
+    bio: None,
+    status: Some("on vacation".into()),
+  }
+}
+
+#[test]
+fn blob_avatars_round_trip() {
+  // the same shape that the frontend and the server use
+  let value = json!({
+    "type": "blob",
+    "contentType": "image/png",
+    "data": "iVBORw0KGgo=",
+  });
+
+  let avatar: Avatar = serde_json::from_value(value.clone()).unwrap();
+  assert_eq!(
+    avatar,
+    Avatar::Blob {
+      content_type: "image/png".into(),
+      data: "iVBORw0KGgo=".into(),
+    }
+  );
+  assert_eq!(serde_json::to_value(&avatar).unwrap(), value);
+}
+
+#[tokio::test]
+async fn get_profile_is_cached() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+
+  let profile = api.get_profile(&jackson.id).await.unwrap().unwrap();
+  assert_eq!(profile.username, "jackson");
+  assert_eq!(profile.display_name, None);
+  assert!(profile.created_at > 0);
+
+  assert_eq!(api.get_profile(&jackson.id).await.unwrap(), Some(profile));
+  assert_eq!(server.requests("/user/profile"), 1);
+
+  assert_eq!(api.get_profile("nobody").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn update_profile() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+
+  let profile = match api.update_profile(update()).await {
+    Ok(UpdateProfileResult::Success(x)) => x,
+    _ => panic!("expected the update to succeed"),
+  };
+  assert_eq!(profile.display_name.as_deref(), Some("Jackson"));
+  assert_eq!(profile.status.as_deref(), Some("on vacation"));
+
+  // the updated profile is cached
+  assert_eq!(api.get_profile(&jackson.id).await.unwrap(), Some(profile));
+  assert_eq!(server.requests("/user/profile"), 1);
+
+  match api.my_info().await {
+    Ok(MyInfoResult::Success(x)) => assert_eq!(x.display_name.as_deref(), Some("Jackson")),
+    _ => panic!("expected my_info to succeed"),
+  }
+}
+
+#[tokio::test]
+async fn invalid_fields_are_rejected() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+
+  let result = api
+    .update_profile(ProfileUpdate {
+      status: Some("a".repeat(81)),
+      ..update()
+    })
+    .await;
+  assert!(matches!(
+    result,
+    Ok(UpdateProfileResult::InvalidField {
+      field: ProfileField::Status
+    })
+  ));
+
+  let result = api
+    .update_profile(ProfileUpdate {
+      avatar: Some(Avatar::Blob {
+        content_type: "text/html".into(),
+        data: "PGgxPmhpPC9oMT4=".into(),
+      }),
+      ..update()
+    })
+    .await;
+  assert!(matches!(
+    result,
+    Ok(UpdateProfileResult::InvalidField {
+      field: ProfileField::Avatar
+    })
+  ));
+
+  assert_eq!(server.requests("/user/profile"), 0);
+}
+
+#[tokio::test]
+async fn update_profile_while_logged_out() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+  server.expire_tokens();
+
+  let result = api.update_profile(update()).await;
+  assert!(matches!(result, Ok(UpdateProfileResult::NotLoggedIn)));
+  assert!(api.auth().get_token().is_none());
+}
+
+#[tokio::test]
+async fn profile_updated_frames_invalidate_the_cache() {
+  let (server, jackson) = logged_in().await;
+  let api = &jackson.api;
+  let (_ws, mut events) = connect(&server, &jackson).await;
+
+  api.get_profile(&jackson.id).await.unwrap();
+  assert!(api.profiles().get(&jackson.id).is_some());
+
+  // another client changes the profile
+  let other = log_in(&server, "jackson").await;
+  other.api.update_profile(update()).await.unwrap();
+
+  let payload = wait_for(&mut events, |event, _| event == "profile_updated").await;
+  assert_eq!(payload["userId"], jackson.id.as_str());
+  assert!(api.profiles().get(&jackson.id).is_none());
+
+  let profile = api.get_profile(&jackson.id).await.unwrap().unwrap();
+  assert_eq!(profile.display_name.as_deref(), Some("Jackson"));
+}
@@ -0,0 +1,124 @@
+mod support;
+
+use std::{fs, sync::Arc};
+
+use axum::http::StatusCode;
+use blop_core::{
+  api::ApiClient,
+  user::{
+    auth::{
+      persist::{PersistedSession, SessionFile},
+      secret::Secret,
+    },
+    CreateUserResult,
+  },
+  Config,
+};
+use support::{Failure, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+#[test]
+fn secrets_are_redacted() {
+  let secret = Secret::from(PASSWORD);
+
+  for formatted in [
+    format!("{}", secret),
+    format!("{:?}", secret),
+    format!("{:#?}", Some(Arc::new(secret.clone()))),
+  ] {
+    assert!(!formatted.contains(PASSWORD), "{}", formatted);
+  }
+
+  assert_eq!(secret.expose(), PASSWORD);
+}
+
+#[test]
+fn secrets_can_be_deserialized() {
+  let secret: Secret = serde_json::from_str(&format!("\"{}\"", PASSWORD)).unwrap();
+  assert_eq!(secret.expose(), PASSWORD);
+}
+
+#[tokio::test]
+async fn tokens_stay_out_of_results() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  let result = api
+    .create_user("jackson".into(), PASSWORD.into())
+    .await
+    .unwrap();
+  let token = api.auth().get_token().unwrap();
+
+  // the result goes to the frontend, which never needs the token
+  assert!(matches!(result, CreateUserResult::Success(_)));
+  let serialized = serde_json::to_string(&result).unwrap();
+  assert!(!serialized.contains(token.expose()), "{}", serialized);
+
+  // but the session file does, so that the session survives restarts
+  let session = fs::read_to_string(data_dir.path().join("session.json")).unwrap();
+  assert!(session.contains(token.expose()));
+}
+
+#[cfg(unix)]
+#[test]
+fn session_file_is_private() {
+  use std::os::unix::fs::PermissionsExt;
+
+  let data_dir = TempDir::new().unwrap();
+  let path = data_dir.path().join("session.json");
+
+  // a session that was saved before sessions were private
+  fs::write(&path, "{}").unwrap();
+  fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+  let file = SessionFile::new(data_dir.path());
+  let session = PersistedSession {
+    token: Secret::from("token"),
+    user_id: "alice".into(),
+    identity: None,
+  };
+  file.save(&session).unwrap();
+
+  let mode = fs::metadata(&path).unwrap().permissions().mode();
+  assert_eq!(mode & 0o777, 0o600);
+  assert_eq!(file.load().unwrap().user_id, "alice");
+}
+
+#[tokio::test]
+async fn passwords_stay_out_of_errors() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+  server.add_user("jackson", PASSWORD);
+
+  for failure in [
+    Failure::BadRequest("JSON"),
+    Failure::MalformedJson,
+    Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+  ] {
+    server.fail_next("/auth/login", failure);
+
+    let error = api
+      .log_in("jackson".into(), PASSWORD.into())
+      .await
+      .err()
+      .unwrap();
+    assert!(!error.contains(PASSWORD), "{}", error);
+  }
+
+  // nothing listens on the discard port locally
+  let config = Config {
+    api_url: "http://127.0.0.1:9/".into(),
+    data_dir: data_dir.path().into(),
+    ..Default::default()
+  };
+  let api = ApiClient::new(Arc::new(config), Default::default());
+
+  let error = api
+    .log_in("jackson".into(), PASSWORD.into())
+    .await
+    .err()
+    .unwrap();
+  assert!(!error.contains(PASSWORD), "{}", error);
+}
@@ -0,0 +1,77 @@
+mod support;
+
+use std::sync::Arc;
+
+use blop_core::{
+  api::ApiClient,
+  user::auth::{AuthenticationState, Session},
+  websocket::WebSocketClient,
+};
+use support::{recorder, wait_for, wait_for_notification, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn subscribers_see_every_login_and_logout() {
+  let auth = AuthenticationState::default();
+  let mut changes = auth.subscribe();
+  assert!(changes.borrow().is_none());
+
+  auth.login("token".into(), "user-1".into());
+  changes.changed().await.unwrap();
+  let first = changes.borrow_and_update().clone();
+  {
+    let session = first.as_ref().unwrap();
+    assert_eq!(session.token.expose(), "token");
+    assert_eq!(session.user_id, "user-1");
+  }
+  assert_eq!(auth.get_user_id().as_deref(), Some("user-1"));
+
+  // logging in again as the same user is still a new session
+  auth.login("token".into(), "user-1".into());
+  changes.changed().await.unwrap();
+  assert!(!Session::same(&first, &changes.borrow_and_update()));
+
+  auth.logout();
+  changes.changed().await.unwrap();
+  assert!(changes.borrow_and_update().is_none());
+  assert!(!auth.is_logged_in());
+  assert!(auth.get_token().is_none());
+}
+
+#[tokio::test]
+async fn websocket_follows_the_session() {
+  let server = MockServer::start().await;
+  let id = server.add_user("jackson", PASSWORD);
+  let data_dir = TempDir::new().unwrap();
+  let config = Arc::new(server.config(data_dir.path()));
+  let auth = Arc::new(AuthenticationState::default());
+  let api = ApiClient::new(config.clone(), auth.clone());
+
+  let (sink, mut events) = recorder();
+  let ws = Arc::new(WebSocketClient::new(config, sink).with_auth(auth));
+  ws.start();
+  wait_for_notification(&mut events, "connected").await;
+
+  // the connection is replaced with one that has the token, without being asked to
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+  let payload = wait_for(&mut events, |event, _| event == "session_changed").await;
+  assert_eq!(payload["userId"], id);
+  wait_for_notification(&mut events, "connected").await;
+
+  ws.send_message("hello".into()).await.unwrap();
+  let payload = wait_for(&mut events, |event, _| event == "message").await;
+  assert_eq!(payload["userId"], id);
+  assert_eq!(payload["message"], "hello");
+  assert!(payload["sentAt"].as_u64().unwrap() > 0);
+
+  api.end_session().await;
+  let payload = wait_for(&mut events, |event, _| event == "session_changed").await;
+  assert!(payload["userId"].is_null());
+  wait_for_notification(&mut events, "connected").await;
+
+  // logging out again doesn't change anything
+  api.end_session().await;
+  ws.send_message("goodbye".into()).await.unwrap();
+  let (event, _) = events.recv().await.unwrap();
+  assert_ne!(event, "session_changed");
+}
@@ -0,0 +1,110 @@
+use blop_core::user::auth::strength::estimate_strength;
+
+/// Asserts that `password` gets `score`, and that it's warned about with `warning`.
+fn assert_strength(password: &str, score: u8, warning: Option<&str>) {
+  let strength = estimate_strength(password);
+
+  assert_eq!(strength.score, score, "score of {:?}", password);
+  assert_eq!(
+    strength.warning.as_deref(),
+    warning,
+    "warning of {:?}",
+    password
+  );
+}
+
+#[test]
+fn common_passwords() {
+  assert_strength("password", 0, Some("This is a top-10 common password."));
+  assert_strength("monkey", 0, Some("This is a top-100 common password."));
+  assert_strength("trinity", 0, Some("This is a very common password."));
+}
+
+#[test]
+fn checkbox_passwords_are_weak() {
+  let strength = estimate_strength("Password1!");
+
+  assert!(strength.score <= 1);
+  assert_eq!(
+    strength.warning.as_deref(),
+    Some("This is similar to a commonly used password.")
+  );
+  assert!(strength
+    .suggestions
+    .iter()
+    .any(|x| x == "Capitalization doesn't help very much."));
+}
+
+#[test]
+fn l33t_and_reversed_words() {
+  let strength = estimate_strength("p@ssw0rd");
+  assert!(strength.score <= 1);
+  assert!(strength
+    .suggestions
+    .iter()
+    .any(|x| x.starts_with("Predictable substitutions")));
+
+  let strength = estimate_strength("drowssap");
+  assert!(strength.score <= 1);
+  assert!(strength
+    .suggestions
+    .iter()
+    .any(|x| x == "Reversed words aren't much harder to guess."));
+}
+
+#[test]
+fn patterns() {
+  assert_strength("qwertyuiop", 0, Some("This is a top-100 common password."));
+  assert_strength(
+    "zxcvfr",
+    1,
+    Some("Short keyboard patterns are easy to guess."),
+  );
+  assert_strength(
+    "aaaaaaaaaa",
+    0,
+    Some("Repeats like \"aaa\" are easy to guess."),
+  );
+  assert_strength(
+    "lkjhlkjhlkjh",
+    1,
+    Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"."),
+  );
+  assert_strength(
+    "ghijklmn",
+    0,
+    Some("Sequences like abc or 6543 are easy to guess."),
+  );
+  assert_strength("13/07/1998", 1, Some("Dates are often easy to guess."));
+}
+
+#[test]
+fn passphrases_are_strong() {
+  for password in [
+    "correct horse battery staple",
+    "tractor attic velvet",
+    "pässwörter sind schön",
+  ] {
+    let strength = estimate_strength(password);
+
+    assert!(strength.score >= 3, "score of {:?}", password);
+    assert_eq!(strength.warning, None);
+  }
+}
+
+#[test]
+fn entropy_follows_guesses() {
+  let weak = estimate_strength("password");
+  let strong = estimate_strength("tractor attic velvet");
+
+  assert!(weak.guesses < strong.guesses);
+  assert!((weak.entropy - weak.guesses.log2()).abs() < 1e-9);
+}
+
+#[test]
+fn empty_password() {
+  let strength = estimate_strength("");
+
+  assert_eq!(strength.score, 0);
+  assert_eq!(strength.suggestions.len(), 2);
+}
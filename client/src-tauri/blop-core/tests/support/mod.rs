@@ -0,0 +1,1559 @@
+//! An in-process stand-in for the Go backend, so that the client can be tested without MongoDB.
+//! It implements the same routes, broadcast and pong behaviour, and failures can be scripted.
+
+#![allow(dead_code)]
+
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  net::SocketAddr,
+  path::Path,
+  sync::{
+    atomic::{AtomicI64, AtomicU32, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+  body::Bytes,
+  extract::{
+    ws::{Message, WebSocket, WebSocketUpgrade},
+    Extension, Query,
+  },
+  http::{
+    header::{AUTHORIZATION, RETRY_AFTER},
+    HeaderMap, StatusCode,
+  },
+  response::{IntoResponse, Response},
+  routing::{get, post},
+  Json, Router, Server,
+};
+use blop_core::{
+  api::ApiClient,
+  events::EventSink,
+  user::{
+    auth::{breach::BreachedPasswords, password::PasswordPolicy, totp, AuthenticationState},
+    profile::{Profile, ProfileUpdate},
+  },
+  websocket::WebSocketClient,
+  Config,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tokio::sync::{
+  broadcast,
+  mpsc::{self, UnboundedReceiver},
+};
+
+/// The password that test users are created with.
+pub const PASSWORD: &str = "tractor attic velvet";
+
+/// How long `wait_for` waits for an event before failing the test.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A response that a route gives instead of its usual one.
+#[derive(Clone, Copy, Debug)]
+pub enum Failure {
+  /// `400 Bad Request` with the given `type`, like the Go backend's validation errors.
+  BadRequest(&'static str),
+  /// `401 Unauthorized` with no body.
+  Unauthorized,
+  /// `200 OK` with a body that isn't JSON.
+  MalformedJson,
+  /// The given status with no body.
+  Status(StatusCode),
+  /// `429 Too Many Requests`, with a `Retry-After` of the given number of seconds if there is one.
+  RateLimited(Option<u64>),
+}
+
+impl IntoResponse for Failure {
+  fn into_response(self) -> Response {
+    match self {
+      Failure::BadRequest(typ) => bad_request(typ),
+      Failure::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+      Failure::MalformedJson => (StatusCode::OK, "{ this isn't json").into_response(),
+      Failure::Status(x) => x.into_response(),
+      Failure::RateLimited(Some(x)) => (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, x.to_string())],
+      )
+        .into_response(),
+      Failure::RateLimited(None) => StatusCode::TOO_MANY_REQUESTS.into_response(),
+    }
+  }
+}
+
+/// How the server answers pings.
+#[derive(Clone, Copy, Debug)]
+pub enum PongBehaviour {
+  /// Answer right away.
+  Normal,
+  /// Answer after the given delay.
+  Delay(Duration),
+  /// Never answer, and stop reading from the connection, like a half-open TCP connection.
+  Ignore,
+}
+
+#[derive(Clone, Debug)]
+enum SocketEvent {
+  Broadcast(String),
+  /// A frame for the sockets of the user with the given ID only.
+  Send {
+    to: String,
+    text: String,
+  },
+  Drop,
+}
+
+struct MockUser {
+  id: String,
+  password: String,
+  profile: ProfileUpdate,
+  created_at: u64,
+  friends: HashSet<String>,
+  /// The IDs of users that sent this user a friend request.
+  incoming: HashSet<String>,
+  /// The IDs of users that this user sent a friend request.
+  outgoing: HashSet<String>,
+  blocked: HashSet<String>,
+  /// The keys of the user's devices.
+  devices: Vec<MockDevice>,
+  /// The TOTP secret and unused recovery codes, if they set up two-factor authentication.
+  totp: Option<(Vec<u8>, Vec<String>)>,
+}
+
+/// The public keys of one of a user's devices, in base64.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MockDevice {
+  device_id: String,
+  public_key: String,
+  signing_key: String,
+  /// Signatures over this device, keyed by the ID of the device that made them.
+  signatures: HashMap<String, String>,
+}
+
+impl MockUser {
+  fn new(id: String, password: String, created_at: u64) -> MockUser {
+    MockUser {
+      id,
+      password,
+      profile: Default::default(),
+      created_at,
+      friends: Default::default(),
+      incoming: Default::default(),
+      outgoing: Default::default(),
+      blocked: Default::default(),
+      devices: Vec::new(),
+      totp: None,
+    }
+  }
+
+  fn profile(&self, username: &str) -> Profile {
+    Profile {
+      id: self.id.clone(),
+      username: username.into(),
+      display_name: self.profile.display_name.clone(),
+      avatar: self.profile.avatar.clone(),
+      bio: self.profile.bio.clone(),
+      status: self.profile.status.clone(),
+      created_at: self.created_at,
+    }
+  }
+}
+
+struct Inner {
+  /// Users, keyed by username.
+  users: Mutex<HashMap<String, MockUser>>,
+  /// User IDs, keyed by token.
+  tokens: Mutex<HashMap<String, String>>,
+  /// The usernames and wrong codes so far of logins that are waiting for a code, keyed by
+  /// challenge ID.
+  challenges: Mutex<HashMap<String, (String, u32)>>,
+  /// Responses to give instead of the usual ones, keyed by route.
+  failures: Mutex<HashMap<&'static str, VecDeque<Failure>>>,
+  /// The number of requests made to each route.
+  requests: Mutex<HashMap<&'static str, u32>>,
+  /// Text messages received over WebSocket connections.
+  messages: Mutex<Vec<String>>,
+  pongs: Mutex<PongBehaviour>,
+  /// The policy served at `/auth/policy`, and that new passwords are validated against.
+  password_policy: Mutex<PasswordPolicy>,
+  /// How far the server's clock is ahead of the real time, in milliseconds.
+  clock_offset: AtomicI64,
+  next_id: AtomicU32,
+  sockets: broadcast::Sender<SocketEvent>,
+}
+
+impl Inner {
+  /// Records a request to `route`, and returns the failure scripted for it, if any.
+  fn request(&self, route: &'static str) -> Option<Failure> {
+    *self.requests.lock().unwrap().entry(route).or_default() += 1;
+
+    self
+      .failures
+      .lock()
+      .unwrap()
+      .get_mut(route)
+      .and_then(|x| x.pop_front())
+  }
+
+  fn next_id(&self) -> u32 {
+    self.next_id.fetch_add(1, Ordering::SeqCst)
+  }
+
+  /// Creates a token for the user with the given ID.
+  fn sign_token(&self, user_id: &str) -> String {
+    let token = format!("token-{}", self.next_id());
+    self
+      .tokens
+      .lock()
+      .unwrap()
+      .insert(token.clone(), user_id.into());
+    token
+  }
+
+  /// Returns the user ID that the request's bearer token belongs to.
+  fn authorize(&self, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let token = headers
+      .get(AUTHORIZATION)
+      .and_then(|x| x.to_str().ok())
+      .and_then(|x| x.strip_prefix("Bearer "))
+      .ok_or(StatusCode::BAD_REQUEST)?;
+
+    self
+      .tokens
+      .lock()
+      .unwrap()
+      .get(token)
+      .cloned()
+      .ok_or(StatusCode::UNAUTHORIZED)
+  }
+
+  /// Returns the server's time in milliseconds since the Unix epoch.
+  fn now(&self) -> u64 {
+    let now = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_millis() as i64;
+
+    (now + self.clock_offset.load(Ordering::SeqCst)) as u64
+  }
+}
+
+/// A running mock server. It stops when the test's runtime shuts down.
+pub struct MockServer {
+  addr: SocketAddr,
+  inner: Arc<Inner>,
+}
+
+impl MockServer {
+  /// Starts a server on a free local port.
+  pub async fn start() -> MockServer {
+    let (sockets, _) = broadcast::channel(64);
+
+    let inner = Arc::new(Inner {
+      users: Default::default(),
+      tokens: Default::default(),
+      challenges: Default::default(),
+      failures: Default::default(),
+      requests: Default::default(),
+      messages: Default::default(),
+      pongs: Mutex::new(PongBehaviour::Normal),
+      password_policy: Default::default(),
+      clock_offset: AtomicI64::new(0),
+      next_id: AtomicU32::new(0),
+      sockets,
+    });
+
+    let app = Router::new()
+      .route("/auth/create", post(create_user))
+      .route("/auth/login", get(login))
+      .route("/auth/login/totp", post(submit_totp))
+      .route("/auth/totp/enable", post(enable_totp))
+      .route("/auth/verify", get(verify))
+      .route("/auth/policy", get(password_policy))
+      .route("/auth/password", post(change_password))
+      .route("/auth/username", post(change_username))
+      .route("/auth/delete", post(delete_account))
+      .route("/user/getid", get(get_user_id))
+      .route("/user/me", get(me))
+      .route("/user/profile", get(get_profile).post(update_profile))
+      .route("/user/resolve", post(resolve_users))
+      .route("/user/contacts", get(list_contacts))
+      .route("/user/friends/request", post(send_friend_request))
+      .route("/user/friends/respond", post(respond_friend_request))
+      .route("/user/friends/remove", post(remove_friend))
+      .route("/user/block", post(block_user))
+      .route("/user/unblock", post(unblock_user))
+      .route("/user/keys", get(get_devices).post(register_device))
+      .route("/user/keys/sign", post(cross_sign_device))
+      .route("/user/keys/remove", post(remove_device))
+      .route("/ws", get(websocket))
+      .layer(Extension(inner.clone()));
+
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    MockServer { addr, inner }
+  }
+
+  /// Returns a config that points at this server, with short intervals so tests run quickly.
+  pub fn config(&self, data_dir: &Path) -> Config {
+    Config {
+      ws_url: format!("ws://{}/ws", self.addr),
+      api_url: format!("http://{}/", self.addr),
+      data_dir: data_dir.into(),
+      ping_interval: Duration::from_millis(100),
+      reconnect_delay: Duration::from_millis(100),
+      availability_debounce: Duration::from_millis(50),
+      ..Default::default()
+    }
+  }
+
+  /// Adds a user and returns their ID.
+  pub fn add_user(&self, username: &str, password: &str) -> String {
+    let id = format!("user-{}", self.inner.next_id());
+
+    self.inner.users.lock().unwrap().insert(
+      username.into(),
+      MockUser::new(id.clone(), password.into(), self.inner.now()),
+    );
+
+    id
+  }
+
+  /// Makes the next request to `route` fail with `failure`. Failures queue up in order.
+  pub fn fail_next(&self, route: &'static str, failure: Failure) {
+    self
+      .inner
+      .failures
+      .lock()
+      .unwrap()
+      .entry(route)
+      .or_default()
+      .push_back(failure);
+  }
+
+  /// Returns the number of requests that have been made to `route`.
+  pub fn requests(&self, route: &'static str) -> u32 {
+    self
+      .inner
+      .requests
+      .lock()
+      .unwrap()
+      .get(route)
+      .copied()
+      .unwrap_or(0)
+  }
+
+  /// Turns on two-factor authentication for the user with the given username.
+  pub fn enable_totp(&self, username: &str, secret: &[u8], recovery_codes: &[&str]) {
+    let mut users = self.inner.users.lock().unwrap();
+    let user = users.get_mut(username).expect("no such user");
+    let recovery_codes = recovery_codes.iter().map(|x| x.to_string()).collect();
+    user.totp = Some((secret.into(), recovery_codes));
+  }
+
+  /// Returns whether the user with the given ID has two-factor authentication turned on.
+  pub fn totp_enabled(&self, user_id: &str) -> bool {
+    let users = self.inner.users.lock().unwrap();
+    users.values().any(|x| x.id == user_id && x.totp.is_some())
+  }
+
+  /// Invalidates every token, as if they had all expired.
+  pub fn expire_tokens(&self) {
+    self.inner.tokens.lock().unwrap().clear();
+  }
+
+  /// Returns the text messages received over WebSocket connections so far.
+  pub fn messages(&self) -> Vec<String> {
+    self.inner.messages.lock().unwrap().clone()
+  }
+
+  /// Returns the public keys of the devices of the user with the given ID, keyed by device ID.
+  pub fn device_keys(&self, user_id: &str) -> HashMap<String, String> {
+    let users = self.inner.users.lock().unwrap();
+    let user = match users.values().find(|user| user.id == user_id) {
+      Some(x) => x,
+      None => return HashMap::new(),
+    };
+
+    user
+      .devices
+      .iter()
+      .map(|x| (x.device_id.clone(), x.public_key.clone()))
+      .collect()
+  }
+
+  /// Adds a device to the user with the given ID, or replaces the keys of one that they have, like a
+  /// malicious server.
+  pub fn set_device(&self, user_id: &str, device_id: &str, public_key: &str, signing_key: &str) {
+    let mut users = self.inner.users.lock().unwrap();
+    if let Some(user) = users.values_mut().find(|user| user.id == user_id) {
+      user.devices.retain(|x| x.device_id != device_id);
+      user.devices.push(MockDevice {
+        device_id: device_id.into(),
+        public_key: public_key.into(),
+        signing_key: signing_key.into(),
+        signatures: HashMap::new(),
+      });
+    }
+  }
+
+  /// Changes how pings are answered from now on.
+  pub fn set_pongs(&self, behaviour: PongBehaviour) {
+    *self.inner.pongs.lock().unwrap() = behaviour;
+  }
+
+  /// Sets how far the server's clock is ahead of the real time, in milliseconds.
+  pub fn set_clock_offset(&self, offset: i64) {
+    self.inner.clock_offset.store(offset, Ordering::SeqCst);
+  }
+
+  /// Changes the password policy from now on.
+  pub fn set_password_policy(&self, policy: PasswordPolicy) {
+    *self.inner.password_policy.lock().unwrap() = policy;
+  }
+
+  /// Sends `text` to every WebSocket connection.
+  pub fn broadcast(&self, text: &str) {
+    let _ = self.inner.sockets.send(SocketEvent::Broadcast(text.into()));
+  }
+
+  /// Closes every WebSocket connection without a close handshake.
+  pub fn drop_connections(&self) {
+    let _ = self.inner.sockets.send(SocketEvent::Drop);
+  }
+}
+
+fn bad_request(typ: &str) -> Response {
+  (StatusCode::BAD_REQUEST, Json(json!({ "type": typ }))).into_response()
+}
+
+#[derive(Deserialize)]
+struct AuthenticationParams {
+  username: String,
+  password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangePasswordParams {
+  old_password: String,
+  new_password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubmitTotpParams {
+  challenge_id: String,
+  code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnableTotpParams {
+  secret: String,
+  code: String,
+  recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ChangeUsernameParams {
+  username: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteAccountParams {
+  password: String,
+}
+
+#[derive(Deserialize)]
+struct GetProfileParams {
+  id: String,
+}
+
+#[derive(Deserialize)]
+struct ResolveUsersParams {
+  ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContactParams {
+  user_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RespondFriendRequestParams {
+  user_id: String,
+  accept: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterDeviceParams {
+  device_id: String,
+  public_key: String,
+  signing_key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CrossSignDeviceParams {
+  device_id: String,
+  signer_id: String,
+  signature: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoveDeviceParams {
+  device_id: String,
+}
+
+/// A frame that a client sends to deliver a direct message.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DirectMessageFrame {
+  #[serde(rename = "type")]
+  typ: String,
+  to: String,
+  envelope: Value,
+  sent_at: u64,
+}
+
+/// The envelope that a client wraps chat messages in.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatMessageFrame {
+  message: String,
+  sent_at: u64,
+}
+
+#[derive(Deserialize)]
+struct GetUserIdParams {
+  username: String,
+}
+
+async fn create_user(Extension(inner): Extension<Arc<Inner>>, body: Bytes) -> Response {
+  if let Some(x) = inner.request("/auth/create") {
+    return x.into_response();
+  }
+
+  let body: AuthenticationParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  if !inner
+    .password_policy
+    .lock()
+    .unwrap()
+    .validate(&body.password, &BreachedPasswords::bundled())
+    .is_valid()
+  {
+    return bad_request("PASSWORD");
+  }
+
+  let mut users = inner.users.lock().unwrap();
+  if users.contains_key(&body.username) {
+    return bad_request("USERALREADYEXISTS");
+  }
+
+  let id = format!("user-{}", inner.next_id());
+  users.insert(
+    body.username,
+    MockUser::new(id.clone(), body.password, inner.now()),
+  );
+  drop(users);
+
+  let token = inner.sign_token(&id);
+  Json(json!({ "id": id, "token": token })).into_response()
+}
+
+async fn password_policy(Extension(inner): Extension<Arc<Inner>>) -> Response {
+  if let Some(x) = inner.request("/auth/policy") {
+    return x.into_response();
+  }
+
+  Json(inner.password_policy.lock().unwrap().clone()).into_response()
+}
+
+async fn change_password(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/auth/password") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: ChangePasswordParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.values_mut().find(|x| x.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  if user.password != body.old_password {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  user.password = body.new_password;
+  StatusCode::OK.into_response()
+}
+
+async fn change_username(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/auth/username") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: ChangeUsernameParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  if users.contains_key(&body.username) {
+    return bad_request("USERALREADYEXISTS");
+  }
+
+  let old = match users.iter().find(|(_, user)| user.id == id) {
+    Some((username, _)) => username.clone(),
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  let user = users.remove(&old).unwrap();
+  users.insert(body.username, user);
+  StatusCode::OK.into_response()
+}
+
+async fn delete_account(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/auth/delete") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: DeleteAccountParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let (username, user) = match users.iter().find(|(_, user)| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  if user.password != body.password {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let username = username.clone();
+  users.remove(&username);
+  StatusCode::OK.into_response()
+}
+
+async fn login(Extension(inner): Extension<Arc<Inner>>, body: Bytes) -> Response {
+  if let Some(x) = inner.request("/auth/login") {
+    return x.into_response();
+  }
+
+  let body: AuthenticationParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let id = match inner.users.lock().unwrap().get(&body.username) {
+    None => return bad_request("USER"),
+    Some(x) if x.password != body.password => return StatusCode::UNAUTHORIZED.into_response(),
+    // like the Go backend, the token is only handed out once a code is submitted too
+    Some(x) if x.totp.is_some() => None,
+    Some(x) => Some(x.id.clone()),
+  };
+
+  match id {
+    Some(id) => {
+      let token = inner.sign_token(&id);
+      Json(json!({ "id": id, "token": token })).into_response()
+    }
+    None => {
+      let challenge_id = format!("challenge-{}", inner.next_id());
+      inner
+        .challenges
+        .lock()
+        .unwrap()
+        .insert(challenge_id.clone(), (body.username, 0));
+      let body = Json(json!({ "challengeId": challenge_id }));
+      (StatusCode::ACCEPTED, body).into_response()
+    }
+  }
+}
+
+async fn submit_totp(Extension(inner): Extension<Arc<Inner>>, body: Bytes) -> Response {
+  if let Some(x) = inner.request("/auth/login/totp") {
+    return x.into_response();
+  }
+
+  let body: SubmitTotpParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut challenges = inner.challenges.lock().unwrap();
+  let (username, attempts) = match challenges.get_mut(&body.challenge_id) {
+    Some(x) => x,
+    None => return StatusCode::NOT_FOUND.into_response(),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.get_mut(username.as_str()) {
+    Some(x) => x,
+    None => return StatusCode::NOT_FOUND.into_response(),
+  };
+  let (secret, recovery_codes) = match &mut user.totp {
+    Some(x) => x,
+    None => return StatusCode::NOT_FOUND.into_response(),
+  };
+
+  let valid = if totp::verify(secret, &body.code, inner.now() / 1000) {
+    true
+  } else if let Some(i) = recovery_codes.iter().position(|x| *x == body.code) {
+    // recovery codes only work once
+    recovery_codes.remove(i);
+    true
+  } else {
+    false
+  };
+
+  if !valid {
+    // like the Go backend, guessing is cut off after a few tries
+    *attempts += 1;
+    if *attempts >= 5 {
+      challenges.remove(&body.challenge_id);
+    }
+    return StatusCode::UNAUTHORIZED.into_response();
+  }
+
+  let id = user.id.clone();
+  challenges.remove(&body.challenge_id);
+  drop((users, challenges));
+
+  let token = inner.sign_token(&id);
+  Json(json!({ "id": id, "token": token })).into_response()
+}
+
+async fn enable_totp(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/auth/totp/enable") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: EnableTotpParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let secret = match totp::base32_decode(&body.secret) {
+    Some(x) if x.len() >= 16 => x,
+    _ => return bad_request("KEY"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.values_mut().find(|user| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  if user.totp.is_some() {
+    return bad_request("ENABLED");
+  }
+
+  if !totp::verify(&secret, &body.code, inner.now() / 1000) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  user.totp = Some((secret, body.recovery_codes));
+  StatusCode::OK.into_response()
+}
+
+async fn verify(Extension(inner): Extension<Arc<Inner>>, headers: HeaderMap) -> Response {
+  if let Some(x) = inner.request("/auth/verify") {
+    return x.into_response();
+  }
+
+  match inner.authorize(&headers) {
+    Ok(_) => StatusCode::OK.into_response(),
+    Err(x) => x.into_response(),
+  }
+}
+
+async fn get_user_id(
+  Extension(inner): Extension<Arc<Inner>>,
+  Query(query): Query<HashMap<String, String>>,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/user/getid") {
+    return x.into_response();
+  }
+
+  // like the Go backend, the username can be in the query or, for old clients, the body
+  let username = match query.get("username") {
+    Some(x) => x.clone(),
+    None => match serde_json::from_slice::<GetUserIdParams>(&body) {
+      Ok(x) => x.username,
+      Err(_) => return bad_request("JSON"),
+    },
+  };
+
+  match inner.users.lock().unwrap().get(&username) {
+    Some(x) => Json(json!({ "id": x.id })).into_response(),
+    None => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+async fn resolve_users(Extension(inner): Extension<Arc<Inner>>, body: Bytes) -> Response {
+  if let Some(x) = inner.request("/user/resolve") {
+    return x.into_response();
+  }
+
+  let body: ResolveUsersParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  // like the Go backend, users that don't exist are left out
+  let users: Vec<Value> = inner
+    .users
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|(_, user)| body.ids.contains(&user.id))
+    .map(|(username, user)| json!({ "id": user.id, "username": username }))
+    .collect();
+
+  Json(users).into_response()
+}
+
+async fn get_devices(
+  Extension(inner): Extension<Arc<Inner>>,
+  Query(query): Query<GetProfileParams>,
+) -> Response {
+  if let Some(x) = inner.request("/user/keys") {
+    return x.into_response();
+  }
+
+  let users = inner.users.lock().unwrap();
+  match users.values().find(|user| user.id == query.id) {
+    Some(user) => Json(json!({ "userId": query.id, "devices": user.devices })).into_response(),
+    None => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+/// Returns `true` if `key` is a base64 encoded 32 byte key.
+fn is_key(key: &str) -> bool {
+  base64::decode(key).map(|x| x.len()) == Ok(32)
+}
+
+async fn register_device(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/user/keys") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: RegisterDeviceParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  // like the Go backend, only X25519 and Ed25519 keys are accepted
+  if !is_key(&body.public_key) || !is_key(&body.signing_key) {
+    return bad_request("KEY");
+  }
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.values_mut().find(|user| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  // new keys need new signatures
+  user.devices.retain(|x| x.device_id != body.device_id);
+  user.devices.push(MockDevice {
+    device_id: body.device_id,
+    public_key: body.public_key,
+    signing_key: body.signing_key,
+    signatures: HashMap::new(),
+  });
+
+  StatusCode::OK.into_response()
+}
+
+async fn cross_sign_device(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/user/keys/sign") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: CrossSignDeviceParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.values_mut().find(|user| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  let find = |device_id: &str| user.devices.iter().position(|x| x.device_id == device_id);
+  let (device, signer) = match (find(&body.device_id), find(&body.signer_id)) {
+    (Some(device), Some(signer)) if device != signer => (device, signer),
+    _ => return StatusCode::NOT_FOUND.into_response(),
+  };
+
+  // like the Go backend, signatures have to check out
+  let device_info = &user.devices[device];
+  let mut statement = b"blop device v1".to_vec();
+  statement.extend(format!("{}\n{}\n", id, device_info.device_id).bytes());
+  statement.extend(base64::decode(&device_info.public_key).unwrap());
+  statement.extend(base64::decode(&device_info.signing_key).unwrap());
+
+  let signing_key = base64::decode(&user.devices[signer].signing_key).unwrap();
+  let valid = match (
+    ed25519_dalek::PublicKey::from_bytes(&signing_key),
+    base64::decode(&body.signature)
+      .ok()
+      .and_then(|x| ed25519_dalek::Signature::try_from(&x[..]).ok()),
+  ) {
+    (Ok(key), Some(signature)) => key.verify_strict(&statement, &signature).is_ok(),
+    _ => false,
+  };
+  if !valid {
+    return bad_request("SIGNATURE");
+  }
+
+  user.devices[device]
+    .signatures
+    .insert(body.signer_id, body.signature);
+
+  StatusCode::OK.into_response()
+}
+
+async fn remove_device(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/user/keys/remove") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: RemoveDeviceParams = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+  let user = match users.values_mut().find(|user| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  let count = user.devices.len();
+  user.devices.retain(|x| x.device_id != body.device_id);
+  if user.devices.len() == count {
+    return StatusCode::NOT_FOUND.into_response();
+  }
+
+  // its signatures don't vouch for anything anymore
+  for device in &mut user.devices {
+    device.signatures.remove(&body.device_id);
+  }
+
+  StatusCode::OK.into_response()
+}
+
+/// Returns the username of the user with the given ID.
+fn username_of(users: &HashMap<String, MockUser>, id: &str) -> Option<String> {
+  users
+    .iter()
+    .find(|(_, user)| user.id == id)
+    .map(|(username, _)| username.clone())
+}
+
+/// Authorizes a request to one of the contact routes, and finds the usernames of the user that
+/// made it and the user that it's about. Responses are boxed because they're large.
+fn contact_request(
+  inner: &Inner,
+  route: &'static str,
+  headers: &HeaderMap,
+  body: &Bytes,
+) -> Result<(String, String, String, String), Box<Response>> {
+  if let Some(x) = inner.request(route) {
+    return Err(Box::new(x.into_response()));
+  }
+
+  let unauthorized = || Box::new(StatusCode::UNAUTHORIZED.into_response());
+
+  let id = inner.authorize(headers).map_err(|_| unauthorized())?;
+
+  let body: ContactParams =
+    serde_json::from_slice(body).map_err(|_| Box::new(bad_request("JSON")))?;
+
+  let users = inner.users.lock().unwrap();
+  let me = username_of(&users, &id).ok_or_else(unauthorized)?;
+  let them = username_of(&users, &body.user_id)
+    .ok_or_else(|| Box::new(StatusCode::NOT_FOUND.into_response()))?;
+
+  Ok((id, me, body.user_id, them))
+}
+
+async fn list_contacts(Extension(inner): Extension<Arc<Inner>>, headers: HeaderMap) -> Response {
+  if let Some(x) = inner.request("/user/contacts") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  let users = inner.users.lock().unwrap();
+  let user = match users.values().find(|user| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  let lists = [
+    (&user.friends, "friend"),
+    (&user.incoming, "incoming"),
+    (&user.outgoing, "outgoing"),
+    (&user.blocked, "blocked"),
+  ];
+
+  let mut contacts = Vec::new();
+  for (ids, status) in lists {
+    for id in ids {
+      if let Some(username) = username_of(&users, id) {
+        contacts.push(json!({ "id": id, "username": username, "status": status }));
+      }
+    }
+  }
+
+  Json(contacts).into_response()
+}
+
+async fn send_friend_request(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  let (id, me, their_id, them) =
+    match contact_request(&inner, "/user/friends/request", &headers, &body) {
+      Ok(x) => x,
+      Err(x) => return *x,
+    };
+
+  let mut users = inner.users.lock().unwrap();
+
+  let blocked = users[&me].blocked.contains(&their_id) || users[&them].blocked.contains(&id);
+  if blocked || id == their_id {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  if users[&me].friends.contains(&their_id) {
+    return bad_request("ALREADYFRIENDS");
+  }
+
+  // they already asked, so this accepts their request
+  if users[&me].incoming.contains(&their_id) {
+    let user = users.get_mut(&me).unwrap();
+    user.incoming.remove(&their_id);
+    user.friends.insert(their_id.clone());
+
+    let user = users.get_mut(&them).unwrap();
+    user.outgoing.remove(&id);
+    user.friends.insert(id);
+
+    return Json(json!({ "result": "accepted" })).into_response();
+  }
+
+  users
+    .get_mut(&me)
+    .unwrap()
+    .outgoing
+    .insert(their_id.clone());
+  users.get_mut(&them).unwrap().incoming.insert(id.clone());
+  drop(users);
+
+  // like the Go backend, tell them about it
+  let frame = json!({ "type": "friend_request", "userId": id, "username": me });
+  let _ = inner.sockets.send(SocketEvent::Send {
+    to: their_id,
+    text: frame.to_string(),
+  });
+
+  Json(json!({ "result": "sent" })).into_response()
+}
+
+async fn respond_friend_request(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  let (id, me, their_id, them) =
+    match contact_request(&inner, "/user/friends/respond", &headers, &body) {
+      Ok(x) => x,
+      Err(x) => return *x,
+    };
+
+  let accept = match serde_json::from_slice::<RespondFriendRequestParams>(&body) {
+    Ok(x) => x.accept,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  let mut users = inner.users.lock().unwrap();
+
+  if !users.get_mut(&me).unwrap().incoming.remove(&their_id) {
+    return StatusCode::NOT_FOUND.into_response();
+  }
+  users.get_mut(&them).unwrap().outgoing.remove(&id);
+
+  if accept {
+    users.get_mut(&me).unwrap().friends.insert(their_id);
+    users.get_mut(&them).unwrap().friends.insert(id);
+  }
+
+  StatusCode::OK.into_response()
+}
+
+async fn remove_friend(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  let (id, me, their_id, them) =
+    match contact_request(&inner, "/user/friends/remove", &headers, &body) {
+      Ok(x) => x,
+      Err(x) => return *x,
+    };
+
+  let mut users = inner.users.lock().unwrap();
+
+  // this also cancels a request that we sent
+  let user = users.get_mut(&me).unwrap();
+  if !user.friends.remove(&their_id) && !user.outgoing.remove(&their_id) {
+    return StatusCode::NOT_FOUND.into_response();
+  }
+
+  let user = users.get_mut(&them).unwrap();
+  user.friends.remove(&id);
+  user.incoming.remove(&id);
+
+  StatusCode::OK.into_response()
+}
+
+async fn block_user(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  let (id, me, their_id, them) = match contact_request(&inner, "/user/block", &headers, &body) {
+    Ok(x) => x,
+    Err(x) => return *x,
+  };
+
+  let mut users = inner.users.lock().unwrap();
+
+  let user = users.get_mut(&me).unwrap();
+  user.friends.remove(&their_id);
+  user.incoming.remove(&their_id);
+  user.outgoing.remove(&their_id);
+  user.blocked.insert(their_id);
+
+  let user = users.get_mut(&them).unwrap();
+  user.friends.remove(&id);
+  user.incoming.remove(&id);
+  user.outgoing.remove(&id);
+
+  StatusCode::OK.into_response()
+}
+
+async fn unblock_user(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  let (_, me, their_id, _) = match contact_request(&inner, "/user/unblock", &headers, &body) {
+    Ok(x) => x,
+    Err(x) => return *x,
+  };
+
+  match inner
+    .users
+    .lock()
+    .unwrap()
+    .get_mut(&me)
+    .unwrap()
+    .blocked
+    .remove(&their_id)
+  {
+    true => StatusCode::OK.into_response(),
+    false => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+async fn me(Extension(inner): Extension<Arc<Inner>>, headers: HeaderMap) -> Response {
+  if let Some(x) = inner.request("/user/me") {
+    return x.into_response();
+  }
+
+  // like the Go backend, a token that can't be verified is a server error
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+  };
+
+  let users = inner.users.lock().unwrap();
+  match users.iter().find(|(_, user)| user.id == id) {
+    Some((username, user)) => Json(user.profile(username)).into_response(),
+    None => StatusCode::BAD_REQUEST.into_response(),
+  }
+}
+
+async fn get_profile(
+  Extension(inner): Extension<Arc<Inner>>,
+  Query(query): Query<GetProfileParams>,
+) -> Response {
+  if let Some(x) = inner.request("/user/profile") {
+    return x.into_response();
+  }
+
+  let users = inner.users.lock().unwrap();
+  match users.iter().find(|(_, user)| user.id == query.id) {
+    Some((username, user)) => Json(user.profile(username)).into_response(),
+    None => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+async fn update_profile(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if let Some(x) = inner.request("/user/profile") {
+    return x.into_response();
+  }
+
+  let id = match inner.authorize(&headers) {
+    Ok(x) => x,
+    Err(x) => return x.into_response(),
+  };
+
+  let body: ProfileUpdate = match serde_json::from_slice(&body) {
+    Ok(x) => x,
+    Err(_) => return bad_request("JSON"),
+  };
+
+  if body.invalid_field().is_some() {
+    return bad_request("PROFILE");
+  }
+
+  let mut users = inner.users.lock().unwrap();
+  let (username, user) = match users.iter_mut().find(|(_, user)| user.id == id) {
+    Some(x) => x,
+    None => return StatusCode::UNAUTHORIZED.into_response(),
+  };
+
+  user.profile = body;
+  let profile = user.profile(username);
+  drop(users);
+
+  // like the Go backend, tell everyone that the profile changed
+  let frame = json!({ "type": "profile_updated", "userId": id });
+  let _ = inner
+    .sockets
+    .send(SocketEvent::Broadcast(frame.to_string()));
+
+  Json(profile).into_response()
+}
+
+async fn websocket(
+  Extension(inner): Extension<Arc<Inner>>,
+  headers: HeaderMap,
+  ws: WebSocketUpgrade,
+) -> Response {
+  inner.request("/ws");
+
+  // like the Go backend, connections without a valid token are anonymous
+  let user_id = inner.authorize(&headers).ok();
+
+  ws.on_upgrade(move |socket| async move {
+    let presence = |online: bool| {
+      if let Some(id) = &user_id {
+        let frame = json!({ "type": "presence", "userId": id, "online": online });
+        let _ = inner
+          .sockets
+          .send(SocketEvent::Broadcast(frame.to_string()));
+      }
+    };
+
+    presence(true);
+    handle_socket(socket, inner.clone(), user_id.clone()).await;
+    presence(false);
+  })
+}
+
+async fn handle_socket(mut socket: WebSocket, inner: Arc<Inner>, user_id: Option<String>) {
+  let mut events = inner.sockets.subscribe();
+
+  loop {
+    tokio::select! {
+      message = socket.recv() => {
+        let message = match message {
+          Some(Ok(x)) => x,
+          _ => return,
+        };
+
+        match message {
+          Message::Ping(data) => {
+            let behaviour = *inner.pongs.lock().unwrap();
+            match behaviour {
+              PongBehaviour::Normal => (),
+              PongBehaviour::Delay(x) => tokio::time::sleep(x).await,
+              PongBehaviour::Ignore => {
+                // not reading means that the automatic pong is never sent either
+                while !matches!(events.recv().await, Ok(SocketEvent::Drop) | Err(_)) {}
+                return;
+              }
+            }
+
+            // like the Go backend, the server time is appended to the ping's payload.
+            // unlike the Go backend, tungstenite has already queued a plain echo of the ping, which
+            // gets sent first, so clients see this pong as stale
+            let mut data = data;
+            data.extend(format!(":{}", inner.now()).bytes());
+
+            if socket.send(Message::Pong(data)).await.is_err() {
+              return;
+            }
+          }
+          Message::Text(text) => {
+            inner.messages.lock().unwrap().push(text.clone());
+
+            // like the Go backend, direct messages only go to their recipient
+            if let (Some(id), Ok(frame)) = (&user_id, serde_json::from_str::<DirectMessageFrame>(&text)) {
+              if frame.typ == "dm" {
+                let out = json!({
+                  "type": "dm",
+                  "userId": id,
+                  "envelope": frame.envelope,
+                  "sentAt": frame.sent_at,
+                });
+                let _ = inner.sockets.send(SocketEvent::Send { to: frame.to, text: out.to_string() });
+                continue;
+              }
+            }
+
+            // chat messages say when they were sent, and ones that don't are stamped with our clock
+            let (message, sent_at) = match serde_json::from_str::<ChatMessageFrame>(&text) {
+              Ok(frame) => (frame.message, frame.sent_at),
+              Err(_) => (text.clone(), inner.now()),
+            };
+
+            let out = match &user_id {
+              Some(id) => json!({
+                "type": "message",
+                "userId": id,
+                "message": message,
+                "sentAt": sent_at,
+              })
+              .to_string(),
+              None => format!("MESSAGE[{}]: {}", message.len(), message),
+            };
+            let _ = inner.sockets.send(SocketEvent::Broadcast(out));
+          }
+          Message::Close(_) => return,
+          _ => (),
+        }
+      }
+      event = events.recv() => match event {
+        Ok(SocketEvent::Broadcast(x)) => {
+          if socket.send(Message::Text(x)).await.is_err() {
+            return;
+          }
+        }
+        Ok(SocketEvent::Send { to, text }) if user_id.as_ref() == Some(&to) => {
+          if socket.send(Message::Text(text)).await.is_err() {
+            return;
+          }
+        }
+        // someone else's
+        Ok(SocketEvent::Send { .. }) => (),
+        Ok(SocketEvent::Drop) => return,
+        Err(_) => (),
+      },
+    }
+  }
+}
+
+/// An event that was emitted by the client, i.e. its name and payload.
+pub type Event = (String, Value);
+
+/// Returns an event sink that records events, along with the receiving end of the recording.
+pub fn recorder() -> (Arc<dyn EventSink>, UnboundedReceiver<Event>) {
+  let (tx, rx) = mpsc::unbounded_channel();
+
+  let sink = move |event: &str, payload: Value| {
+    let _ = tx.send((event.into(), payload));
+  };
+
+  (Arc::new(sink), rx)
+}
+
+/// Waits for an event that matches `predicate` and returns its payload, panicking if it doesn't
+/// arrive in time.
+pub async fn wait_for<P>(events: &mut UnboundedReceiver<Event>, predicate: P) -> Value
+where
+  P: Fn(&str, &Value) -> bool,
+{
+  let wait = async {
+    while let Some((event, payload)) = events.recv().await {
+      if predicate(&event, &payload) {
+        return payload;
+      }
+    }
+
+    panic!("the client stopped emitting events");
+  };
+
+  tokio::time::timeout(EVENT_TIMEOUT, wait)
+    .await
+    .expect("timed out waiting for an event")
+}
+
+/// Waits for a `notification` event of the given type.
+pub async fn wait_for_notification(events: &mut UnboundedReceiver<Event>, typ: &str) -> Value {
+  wait_for(events, |event, payload| {
+    event == "notification" && payload["type"] == typ
+  })
+  .await
+}
+
+/// A client that's logged in to a `MockServer`, along with the user's ID.
+pub struct Account {
+  pub api: ApiClient,
+  pub auth: Arc<AuthenticationState>,
+  pub id: String,
+  pub data_dir: TempDir,
+}
+
+/// Creates `username` on `server` and returns a client that's logged in as them.
+pub async fn account(server: &MockServer, username: &str) -> Account {
+  server.add_user(username, PASSWORD);
+  log_in(server, username).await
+}
+
+/// Logs in as an existing user with a data directory of its own, i.e. on another device.
+pub async fn log_in(server: &MockServer, username: &str) -> Account {
+  let data_dir = TempDir::new().unwrap();
+  let auth = Arc::new(AuthenticationState::default());
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), auth.clone());
+  api.log_in(username.into(), PASSWORD.into()).await.unwrap();
+
+  Account {
+    api,
+    id: auth.get_user_id().unwrap(),
+    auth,
+    data_dir,
+  }
+}
+
+/// Starts a mock server and returns a client that's logged in to it as "jackson".
+pub async fn logged_in() -> (MockServer, Account) {
+  let server = MockServer::start().await;
+  let jackson = account(&server, "jackson").await;
+
+  (server, jackson)
+}
+
+/// Connects to the WebSocket server as `account`, sharing its caches, and waits until it's
+/// connected.
+pub async fn connect(
+  server: &MockServer,
+  account: &Account,
+) -> (Arc<WebSocketClient>, UnboundedReceiver<Event>) {
+  let (sink, mut events) = recorder();
+  let ws = Arc::new(
+    WebSocketClient::new(Arc::new(server.config(account.data_dir.path())), sink)
+      .with_auth(account.auth.clone())
+      .with_profiles(account.api.profiles())
+      .with_contacts(account.api.contacts())
+      .with_keyring(account.api.keyring()),
+  );
+  ws.start();
+
+  wait_for_notification(&mut events, "connected").await;
+  (ws, events)
+}
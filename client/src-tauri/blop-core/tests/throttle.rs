@@ -0,0 +1,99 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use blop_core::{
+  api::{ApiClient, LoginResult},
+  user::auth::throttle::LoginThrottle,
+};
+use support::{Failure, MockServer, PASSWORD};
+use tempfile::TempDir;
+
+fn api(server: &MockServer, data_dir: &TempDir) -> ApiClient {
+  let mut config = server.config(data_dir.path());
+  config.login_free_attempts = 2;
+  config.login_cooldown = Duration::from_millis(200);
+  config.login_max_cooldown = Duration::from_secs(1);
+
+  ApiClient::new(Arc::new(config), Default::default())
+}
+
+fn retry_after_ms(result: Result<LoginResult, String>) -> u64 {
+  match result {
+    Ok(LoginResult::RateLimited { retry_after_ms }) => retry_after_ms,
+    _ => panic!("expected the login to be rate limited"),
+  }
+}
+
+#[test]
+fn cooldown_doubles_up_to_the_max() {
+  let throttle = LoginThrottle::new(2, Duration::from_secs(1), Duration::from_secs(5));
+
+  assert_eq!(throttle.fail("jackson"), Duration::ZERO);
+  assert_eq!(throttle.fail("jackson"), Duration::ZERO);
+  assert!(throttle.retry_after("jackson").is_none());
+
+  assert_eq!(throttle.fail("Jackson"), Duration::from_secs(1));
+  assert_eq!(throttle.fail("jackson"), Duration::from_secs(2));
+  assert_eq!(throttle.fail("jackson"), Duration::from_secs(4));
+  assert_eq!(throttle.fail("jackson"), Duration::from_secs(5));
+  assert!(throttle.retry_after("JACKSON").is_some());
+
+  // other usernames aren't affected
+  assert!(throttle.retry_after("amelia").is_none());
+
+  throttle.clear("jackson");
+  assert!(throttle.retry_after("jackson").is_none());
+  assert_eq!(throttle.fail("jackson"), Duration::ZERO);
+}
+
+#[tokio::test]
+async fn failed_logins_are_throttled() {
+  let server = MockServer::start().await;
+  server.add_user("jackson", PASSWORD);
+  let data_dir = TempDir::new().unwrap();
+  let api = api(&server, &data_dir);
+
+  for _ in 0..3 {
+    let result = api.log_in("jackson".into(), "wrong password".into()).await;
+    assert!(matches!(result, Ok(LoginResult::Unauthorized)));
+  }
+
+  // the server isn't even asked while the cooldown lasts, not even with the right password
+  let retry_after = retry_after_ms(api.log_in("jackson".into(), PASSWORD.into()).await);
+  assert!(retry_after > 0 && retry_after <= 200, "{}", retry_after);
+  assert_eq!(server.requests("/auth/login"), 3);
+
+  tokio::time::sleep(Duration::from_millis(250)).await;
+  let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+  api.end_session().await;
+
+  // logging in started the count over
+  let result = api.log_in("jackson".into(), "wrong password".into()).await;
+  assert!(matches!(result, Ok(LoginResult::Unauthorized)));
+  let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+  assert!(matches!(result, Ok(LoginResult::Authorized)));
+}
+
+#[tokio::test]
+async fn server_rate_limits_are_respected() {
+  let server = MockServer::start().await;
+  server.add_user("jackson", PASSWORD);
+  let data_dir = TempDir::new().unwrap();
+  let api = api(&server, &data_dir);
+
+  server.fail_next("/auth/login", Failure::RateLimited(Some(30)));
+  let result = api.log_in("jackson".into(), PASSWORD.into()).await;
+  assert_eq!(retry_after_ms(result), 30_000);
+
+  // and remembered, so that we don't ask again too early
+  let retry_after = retry_after_ms(api.log_in("jackson".into(), PASSWORD.into()).await);
+  assert!(retry_after > 29_000, "{}", retry_after);
+  assert_eq!(server.requests("/auth/login"), 1);
+
+  // without a Retry-After, our own cooldown is used
+  server.fail_next("/auth/login", Failure::RateLimited(None));
+  let result = api.log_in("amelia".into(), PASSWORD.into()).await;
+  assert_eq!(retry_after_ms(result), 200);
+}
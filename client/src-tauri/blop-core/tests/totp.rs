@@ -0,0 +1,226 @@
+mod support;
+
+use std::sync::Arc;
+
+use blop_core::{
+  accounts::AccountManager,
+  api::{ApiClient, LoginResult},
+  clock::unix_millis,
+  user::auth::totp::{
+    base32_decode, base32_encode, hotp, otpauth_uri, totp, verify, ConfirmTotpResult,
+    SubmitTotpResult, TotpAlgorithm, TotpEnrollmentResult, DIGITS,
+  },
+};
+use support::{MockServer, PASSWORD};
+use tempfile::TempDir;
+
+/// The secret that the RFC 4226 and RFC 6238 test vectors use with SHA-1.
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+fn now() -> u64 {
+  unix_millis() / 1000
+}
+
+fn code(secret: &[u8]) -> String {
+  totp(secret, now(), TotpAlgorithm::Sha1, DIGITS)
+}
+
+/// Returns a code for `secret` that isn't valid right now.
+fn wrong_code(secret: &[u8]) -> String {
+  totp(secret, now() + 3600, TotpAlgorithm::Sha1, DIGITS)
+}
+
+fn challenge_id(result: Result<LoginResult, String>) -> String {
+  match result {
+    Ok(LoginResult::TwoFactorRequired { challenge_id }) => challenge_id,
+    _ => panic!("expected a code to be required"),
+  }
+}
+
+#[test]
+fn rfc4226_vectors() {
+  let expected = [
+    "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+    "520489",
+  ];
+
+  for (counter, code) in expected.iter().enumerate() {
+    assert_eq!(
+      hotp(RFC_SECRET, counter as u64, TotpAlgorithm::Sha1, 6),
+      *code
+    );
+  }
+}
+
+#[test]
+fn rfc6238_vectors() {
+  let sha256_secret = b"12345678901234567890123456789012";
+  let sha512_secret = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+  let expected = [
+    (59, "94287082", "46119246", "90693936"),
+    (1111111109, "07081804", "68084774", "25091201"),
+    (1111111111, "14050471", "67062674", "99943326"),
+    (1234567890, "89005924", "91819424", "93441116"),
+    (2000000000, "69279037", "90698825", "38618901"),
+    (20000000000, "65353130", "77737706", "47863826"),
+  ];
+
+  for (time, sha1, sha256, sha512) in expected {
+    assert_eq!(totp(RFC_SECRET, time, TotpAlgorithm::Sha1, 8), sha1);
+    assert_eq!(totp(sha256_secret, time, TotpAlgorithm::Sha256, 8), sha256);
+    assert_eq!(totp(sha512_secret, time, TotpAlgorithm::Sha512, 8), sha512);
+  }
+}
+
+#[test]
+fn verify_allows_a_step_of_drift() {
+  let time = 1111111111;
+  let at = |time| totp(RFC_SECRET, time, TotpAlgorithm::Sha1, DIGITS);
+
+  assert!(verify(RFC_SECRET, &at(time), time));
+  assert!(verify(RFC_SECRET, &at(time - 30), time));
+  assert!(verify(RFC_SECRET, &format!(" {} ", at(time + 30)), time));
+  assert!(!verify(RFC_SECRET, &at(time + 90), time));
+  assert!(!verify(RFC_SECRET, &at(time)[1..], time));
+}
+
+#[test]
+fn base32_round_trips() {
+  // from RFC 4648, without the padding
+  assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+  assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+  assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+  assert_eq!(base32_decode("MZXW 6YTB").unwrap(), b"fooba");
+  assert!(base32_decode("not base32!").is_none());
+
+  let uri = otpauth_uri("jackson", RFC_SECRET);
+  assert!(uri.starts_with("otpauth://totp/Blop:jackson?"), "{}", uri);
+  assert!(uri.contains(&format!("secret={}", base32_encode(RFC_SECRET))));
+}
+
+#[tokio::test]
+async fn enrollment_checks_the_first_code() {
+  let server = MockServer::start().await;
+  let id = server.add_user("jackson", PASSWORD);
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  assert!(matches!(
+    api.begin_totp_enrollment().await,
+    Ok(TotpEnrollmentResult::NotLoggedIn)
+  ));
+
+  api.log_in("jackson".into(), PASSWORD.into()).await.unwrap();
+  assert!(matches!(
+    api.confirm_totp_enrollment("123456".into()).await,
+    Ok(ConfirmTotpResult::NotStarted)
+  ));
+
+  let (secret, uri, recovery_codes) = match api.begin_totp_enrollment().await {
+    Ok(TotpEnrollmentResult::Success {
+      secret,
+      uri,
+      recovery_codes,
+    }) => (base32_decode(&secret).unwrap(), uri, recovery_codes),
+    _ => panic!("expected enrollment to start"),
+  };
+  assert!(uri.contains("Blop:jackson"), "{}", uri);
+  assert_eq!(recovery_codes.len(), 10);
+
+  // a wrong code never reaches the server
+  let result = api
+    .confirm_totp_enrollment(wrong_code(&secret).into())
+    .await;
+  assert!(matches!(result, Ok(ConfirmTotpResult::WrongCode)));
+  assert_eq!(server.requests("/auth/totp/enable"), 0);
+
+  let result = api.confirm_totp_enrollment(code(&secret).into()).await;
+  assert!(matches!(result, Ok(ConfirmTotpResult::Enabled)));
+  assert!(server.totp_enabled(&id));
+
+  // the enrollment was used up
+  let result = api.confirm_totp_enrollment(code(&secret).into()).await;
+  assert!(matches!(result, Ok(ConfirmTotpResult::NotStarted)));
+}
+
+#[tokio::test]
+async fn logging_in_needs_a_code() {
+  let server = MockServer::start().await;
+  server.add_user("jackson", PASSWORD);
+  server.enable_totp("jackson", RFC_SECRET, &["abcde-fghij"]);
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  let challenge = challenge_id(api.log_in("jackson".into(), PASSWORD.into()).await);
+  assert!(api.auth().get_token().is_none());
+
+  let result = api
+    .submit_totp(&challenge, wrong_code(RFC_SECRET).into())
+    .await;
+  assert!(matches!(result, Ok(SubmitTotpResult::WrongCode)));
+
+  let result = api.submit_totp(&challenge, code(RFC_SECRET).into()).await;
+  assert!(matches!(result, Ok(SubmitTotpResult::Authorized)));
+  assert!(api.auth().get_token().is_some());
+
+  // challenges only work once
+  let result = api.submit_totp(&challenge, code(RFC_SECRET).into()).await;
+  assert!(matches!(result, Ok(SubmitTotpResult::ChallengeExpired)));
+}
+
+#[tokio::test]
+async fn recovery_codes_work_once() {
+  let server = MockServer::start().await;
+  server.add_user("jackson", PASSWORD);
+  server.enable_totp("jackson", RFC_SECRET, &["abcde-fghij"]);
+  let data_dir = TempDir::new().unwrap();
+  let api = ApiClient::new(Arc::new(server.config(data_dir.path())), Default::default());
+
+  let challenge = challenge_id(api.log_in("jackson".into(), PASSWORD.into()).await);
+  let result = api.submit_totp(&challenge, "abcde-fghij".into()).await;
+  assert!(matches!(result, Ok(SubmitTotpResult::Authorized)));
+  api.end_session().await;
+
+  let challenge = challenge_id(api.log_in("jackson".into(), PASSWORD.into()).await);
+  let result = api.submit_totp(&challenge, "abcde-fghij".into()).await;
+  assert!(matches!(result, Ok(SubmitTotpResult::WrongCode)));
+
+  // guessing is cut off after a few tries
+  for _ in 0..4 {
+    let result = api
+      .submit_totp(&challenge, wrong_code(RFC_SECRET).into())
+      .await;
+    assert!(matches!(result, Ok(SubmitTotpResult::WrongCode)));
+  }
+  let result = api.submit_totp(&challenge, code(RFC_SECRET).into()).await;
+  assert!(matches!(result, Ok(SubmitTotpResult::ChallengeExpired)));
+}
+
+#[tokio::test]
+async fn accounts_are_added_once_the_code_is_submitted() {
+  let server = MockServer::start().await;
+  let id = server.add_user("jackson", PASSWORD);
+  server.enable_totp("jackson", RFC_SECRET, &[]);
+  let data_dir = TempDir::new().unwrap();
+  let accounts = AccountManager::new(Arc::new(server.config(data_dir.path())));
+  accounts.restore().await;
+
+  let result = accounts
+    .log_in("jackson".into(), PASSWORD.into(), false)
+    .await;
+  let challenge = challenge_id(result);
+  assert!(accounts.list_accounts().await.is_empty());
+
+  let result = accounts
+    .submit_totp(challenge, code(RFC_SECRET).into())
+    .await;
+  assert!(matches!(result, Ok(SubmitTotpResult::Authorized)));
+
+  let listed = accounts.list_accounts().await;
+  assert_eq!(listed.len(), 1);
+  assert_eq!(listed[0].user_id, id);
+  assert!(listed[0].active);
+  // it remembers that they didn't want to stay logged in
+  assert!(!listed[0].auto_login);
+}
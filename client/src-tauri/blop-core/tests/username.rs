@@ -0,0 +1,136 @@
+use blop_core::user::auth::username::{
+  canonical_username, validate_username, UsernameCriteria, UsernamePolicy, UsernameValidation,
+};
+
+fn unicode() -> UsernamePolicy {
+  UsernamePolicy {
+    unicode: true,
+    ..Default::default()
+  }
+}
+
+fn criteria(username: &str, policy: &UsernamePolicy) -> UsernameCriteria {
+  match validate_username(username, policy) {
+    UsernameValidation::Valid(x) | UsernameValidation::Invalid(x) => x,
+  }
+}
+
+#[test]
+fn ascii_mode() {
+  let policy = UsernamePolicy::default();
+
+  assert!(validate_username("jackson_2", &policy).is_valid());
+  assert!(!criteria("jo", &policy).length);
+  assert!(!criteria("jäckson", &policy).charset);
+  // the length is still counted in bytes
+  assert!(!criteria("ääääääääääääää", &policy).length);
+}
+
+#[test]
+fn unicode_mode_allows_other_scripts() {
+  let policy = unicode();
+
+  assert!(validate_username("jäckson", &policy).is_valid());
+  assert!(validate_username("José_García", &policy).is_valid());
+  assert!(validate_username("Дмитрий", &policy).is_valid());
+  assert!(validate_username("山田太郎さん", &policy).is_valid());
+  assert!(!criteria("jack son", &policy).charset);
+  assert!(!criteria("jack😀", &policy).charset);
+}
+
+#[test]
+fn unicode_length_counts_graphemes() {
+  let policy = unicode();
+
+  // "e" followed by a combining acute accent is one grapheme
+  let accented = "e\u{301}".repeat(4);
+  assert!(criteria(&accented, &policy).length);
+  assert!(!criteria(&"e\u{301}".repeat(3), &policy).length);
+  assert!(criteria(&"ä".repeat(24), &policy).length);
+  assert!(!criteria(&"ä".repeat(25), &policy).length);
+}
+
+#[test]
+fn unicode_usernames_are_normalized() {
+  let policy = unicode();
+
+  // fullwidth letters are compatibility characters for ASCII ones
+  let fullwidth = criteria("ｊａｃｋｓｏｎ", &policy);
+  assert!(fullwidth.charset && fullwidth.length && fullwidth.not_confusable);
+  assert_eq!(fullwidth.canonical, canonical_username("jackson"));
+}
+
+#[test]
+fn mixed_scripts_are_rejected() {
+  let policy = unicode();
+
+  // a Cyrillic "а" in an otherwise Latin username
+  assert!(!criteria("p\u{430}ypal", &policy).single_script);
+  assert!(!criteria("jacksonДмитрий", &policy).single_script);
+  // digits and punctuation don't belong to a script
+  assert!(criteria("Дмитрий_42", &policy).single_script);
+}
+
+#[test]
+fn confusable_usernames_are_rejected() {
+  let policy = unicode();
+
+  // entirely Cyrillic, but looks like "pace"
+  let lookalike = criteria("\u{440}\u{430}\u{441}\u{435}", &policy);
+  assert!(lookalike.single_script);
+  assert!(!lookalike.not_confusable);
+  assert_eq!(lookalike.canonical, canonical_username("pace"));
+
+  assert!(criteria("Дмитрий", &policy).not_confusable);
+}
+
+#[test]
+fn canonical_forms_match_lookalikes() {
+  assert_eq!(canonical_username("Jackson"), canonical_username("jackson"));
+  assert_eq!(
+    canonical_username("p\u{430}ypal"),
+    canonical_username("paypal")
+  );
+  assert_ne!(canonical_username("jackson"), canonical_username("jakson"));
+}
+
+#[test]
+fn reserved_usernames_are_rejected() {
+  let policy = UsernamePolicy::default();
+
+  assert!(!criteria("admin", &policy).not_reserved);
+  assert!(!criteria("Adm1n", &policy).not_reserved);
+  assert!(!criteria("sys_tem", &policy).not_reserved);
+  assert!(!criteria("ѕуѕtеm", &unicode()).not_reserved);
+  assert!(criteria("admin_jackson", &policy).not_reserved);
+
+  let policy = UsernamePolicy {
+    reserved: vec!["jackson".into()],
+    ..Default::default()
+  };
+  assert!(!criteria("JACKSON", &policy).not_reserved);
+  assert!(validate_username("admin", &policy).is_valid());
+}
+
+#[test]
+fn profanity_is_rejected() {
+  let policy = UsernamePolicy::default();
+
+  assert!(!criteria("fuckface", &policy).not_profane);
+  assert!(!criteria("xX_5h1t_Xx", &policy).not_profane);
+  assert!(!criteria("big-ass", &policy).not_profane);
+  assert!(!criteria("b1tch", &policy).not_profane);
+}
+
+#[test]
+fn profanity_inside_other_words_is_allowed() {
+  let policy = UsernamePolicy::default();
+
+  for username in ["classic", "scunthorpe", "therapist", "peacock", "assassin"] {
+    assert!(
+      validate_username(username, &policy).is_valid(),
+      "{} was rejected",
+      username
+    );
+  }
+}
@@ -0,0 +1,207 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use blop_core::{websocket::WebSocketClient, Config};
+use futures::StreamExt;
+use serde_json::json;
+use support::{recorder, wait_for, wait_for_notification, Event, MockServer, PongBehaviour};
+use tempfile::TempDir;
+use tokio::{
+  io::AsyncReadExt,
+  net::TcpListener,
+  sync::{mpsc::UnboundedReceiver, oneshot},
+};
+
+/// Starts a WebSocket client for `server` and waits for it to connect.
+async fn connect(
+  server: &MockServer,
+  data_dir: &TempDir,
+) -> (Arc<WebSocketClient>, UnboundedReceiver<Event>) {
+  let (sink, mut events) = recorder();
+  let ws = Arc::new(WebSocketClient::new(
+    Arc::new(server.config(data_dir.path())),
+    sink,
+  ));
+  ws.start();
+
+  let connected = wait_for_notification(&mut events, "connected").await;
+  assert_eq!(connected["firstConnection"], true);
+
+  (ws, events)
+}
+
+#[tokio::test]
+async fn send_message_before_connecting() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let (sink, _events) = recorder();
+  let ws = WebSocketClient::new(Arc::new(server.config(data_dir.path())), sink);
+
+  assert!(ws.send_message("hello".into()).await.is_err());
+}
+
+#[tokio::test]
+async fn messages_are_broadcast() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let (alice, _alice_events) = connect(&server, &data_dir).await;
+  let (_bob, mut bob_events) = connect(&server, &data_dir).await;
+
+  alice.send_message("hello".into()).await.unwrap();
+
+  let message = wait_for(&mut bob_events, |event, _| event == "message").await;
+  assert_eq!(message["message"], "MESSAGE[5]: hello");
+
+  // the envelope is timestamped
+  let envelope: serde_json::Value = serde_json::from_str(&server.messages()[0]).unwrap();
+  assert_eq!(envelope["message"], "hello");
+  assert!(envelope["sentAt"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn latency_is_reported() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let (ws, mut events) = connect(&server, &data_dir).await;
+
+  wait_for(&mut events, |event, _| event == "latency").await;
+
+  let stats = ws.ping_stats().await;
+  assert!(stats.sent >= 1);
+  assert!(stats.matched >= 1);
+  assert_eq!(stats.unmatched, 0);
+}
+
+#[tokio::test]
+async fn delayed_pongs_are_matched() {
+  let server = MockServer::start().await;
+  server.set_pongs(PongBehaviour::Delay(Duration::from_millis(250)));
+  let data_dir = TempDir::new().unwrap();
+  let (_ws, mut events) = connect(&server, &data_dir).await;
+
+  // the pong takes a couple of ping intervals to arrive, but it's still within the limit
+  let latency = wait_for(&mut events, |event, _| event == "latency").await;
+  assert!(latency["latency"].as_u64().unwrap() >= 250);
+}
+
+#[tokio::test]
+async fn reconnects_after_connection_is_dropped() {
+  let server = MockServer::start().await;
+  let data_dir = TempDir::new().unwrap();
+  let (_ws, mut events) = connect(&server, &data_dir).await;
+
+  server.drop_connections();
+
+  let lost = wait_for_notification(&mut events, "lostConnection").await;
+  assert_eq!(lost["reason"], "closed");
+
+  let connected = wait_for_notification(&mut events, "connected").await;
+  assert_eq!(connected["firstConnection"], false);
+}
+
+#[tokio::test]
+async fn reconnects_after_pong_timeout() {
+  let server = MockServer::start().await;
+  server.set_pongs(PongBehaviour::Ignore);
+  let data_dir = TempDir::new().unwrap();
+  let (_ws, mut events) = connect(&server, &data_dir).await;
+
+  let lost = wait_for_notification(&mut events, "lostConnection").await;
+  assert_eq!(lost["reason"], "pongTimeout");
+
+  server.set_pongs(PongBehaviour::Normal);
+
+  let connected = wait_for_notification(&mut events, "connected").await;
+  assert_eq!(connected["firstConnection"], false);
+  wait_for(&mut events, |event, _| event == "latency").await;
+}
+
+/// Returns the next event that isn't a latency update.
+async fn next_event(events: &mut UnboundedReceiver<Event>) -> Event {
+  loop {
+    let event = events.recv().await.unwrap();
+    if event.0 != "latency" {
+      return event;
+    }
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconnects_once_after_missed_pongs() {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let config = Config {
+    ws_url: format!("ws://{}", listener.local_addr().unwrap()),
+    ..Default::default()
+  };
+  let interval = config.ping_interval;
+
+  let (sink, mut events) = recorder();
+  let ws = WebSocketClient::new(Arc::new(config), sink);
+  let (closed_tx, closed) = oneshot::channel();
+
+  tokio::spawn(async move {
+    // the first connection reads the frames as plain bytes, so that pings never get a pong
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut conn = tokio_tungstenite::accept_async(stream).await.unwrap();
+    let mut buf = [0; 256];
+    while conn.get_mut().read(&mut buf).await.unwrap_or(0) > 0 {}
+    let _ = closed_tx.send(());
+
+    // the second one answers pings like a healthy server
+    let (stream, _) = listener.accept().await.unwrap();
+    conn = tokio_tungstenite::accept_async(stream).await.unwrap();
+    while let Some(Ok(_)) = conn.next().await {}
+  });
+
+  let check = async {
+    let connected = |first| json!({ "type": "connected", "firstConnection": first });
+
+    assert_eq!(
+      next_event(&mut events).await,
+      ("notification".into(), connected(true))
+    );
+    assert_eq!(
+      next_event(&mut events).await,
+      (
+        "notification".into(),
+        json!({ "type": "lostConnection", "reason": "pongTimeout" })
+      )
+    );
+    closed.await.unwrap();
+    assert_eq!(
+      next_event(&mut events).await,
+      ("notification".into(), connected(false))
+    );
+
+    // the new connection stays up, and pongs are measured again
+    tokio::time::sleep(interval * 10).await;
+    let mut latencies = 0;
+    while let Ok((event, payload)) = events.try_recv() {
+      assert_eq!(event, "latency", "unexpected event {}", payload);
+      latencies += 1;
+    }
+    assert!(latencies > 0);
+  };
+
+  tokio::select! {
+    _ = ws.listen() => unreachable!(),
+    _ = ws.pinger() => unreachable!(),
+    _ = check => (),
+  }
+}
+
+#[tokio::test]
+async fn clock_skew_is_reported() {
+  let server = MockServer::start().await;
+  server.set_clock_offset(60_000);
+  let data_dir = TempDir::new().unwrap();
+  let (ws, mut events) = connect(&server, &data_dir).await;
+
+  let skew = wait_for_notification(&mut events, "clockSkew").await;
+  let offset = skew["offset"].as_i64().unwrap();
+  assert!((offset - 60_000).abs() < 1000, "{}", offset);
+
+  let now = blop_core::clock::unix_millis();
+  assert!(ws.server_now().await > now + 59_000);
+}
@@ -0,0 +1,274 @@
+//! A headless client for scripting Blop from a terminal or CI, without launching the desktop app.
+//! It shares its config and saved accounts with the desktop app, and acts as the active account.
+
+use std::{
+  fs::File,
+  io::{self, BufRead, BufReader},
+  path::PathBuf,
+  process,
+  sync::Arc,
+  time::Duration,
+};
+
+use blop_core::{
+  accounts::AccountManager,
+  api::{LoginResult, MyInfoResult},
+  user::{
+    auth::{
+      breach::{parse_hash_line, BreachedPasswords, HashList},
+      secret::Secret,
+      totp::SubmitTotpResult,
+    },
+    CreateUserResult, DeleteAccountResult,
+  },
+  websocket::WebSocketClient,
+  Config,
+};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+#[derive(Parser)]
+#[clap(name = "blop-cli", about = "A headless client for Blop.")]
+struct Cli {
+  /// Gives up after this many seconds.
+  #[clap(long, global = true)]
+  timeout: Option<u64>,
+  #[clap(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Logs in and saves the session.
+  Login {
+    username: String,
+    /// If omitted, the password is read from the first line of stdin.
+    #[clap(long)]
+    password: Option<String>,
+    /// The two-factor authentication code or recovery code, if the account needs one. If omitted,
+    /// it's read from the next line of stdin.
+    #[clap(long)]
+    code: Option<String>,
+  },
+  /// Prints the logged in user.
+  Whoami,
+  /// Sends a message to the WebSocket server.
+  Send { message: String },
+  /// Prints every event from the WebSocket server as a line of JSON.
+  Tail,
+  /// Prints the latency to the WebSocket server.
+  Ping {
+    /// The number of pongs to wait for.
+    #[clap(short, long, default_value_t = 1)]
+    count: u32,
+  },
+  /// Creates a new user and logs in as them.
+  CreateUser {
+    username: String,
+    /// If omitted, the password is read from the first line of stdin.
+    #[clap(long)]
+    password: Option<String>,
+  },
+  /// Deletes the logged in user, along with their account on this device.
+  DeleteAccount {
+    /// If omitted, the password is read from the first line of stdin.
+    #[clap(long)]
+    password: Option<String>,
+  },
+  /// Imports a list of breached password hashes, replacing any that was imported before. The
+  /// list has a SHA-1 hash on each line, like the ones from Have I Been Pwned.
+  ImportBreaches { file: PathBuf },
+}
+
+/// An event emitted by the WebSocket client, i.e. its name and payload.
+type Event = (String, Value);
+
+#[tokio::main]
+async fn main() {
+  let cli = Cli::parse();
+
+  let result = match cli.timeout {
+    Some(x) => tokio::time::timeout(Duration::from_secs(x), run(cli.command))
+      .await
+      .unwrap_or_else(|_| Err("timed out".into())),
+    None => run(cli.command).await,
+  };
+
+  if let Err(e) = result {
+    eprintln!("error: {}", e);
+    process::exit(1);
+  }
+}
+
+async fn run(command: Command) -> Result<(), String> {
+  let config = Arc::new(Config::from_env());
+
+  // whichever account the desktop app was using last
+  let accounts = AccountManager::new(config.clone());
+  accounts.restore().await;
+  let account = accounts.active();
+
+  match command {
+    Command::Login {
+      username,
+      password,
+      code,
+    } => {
+      let result = accounts
+        .log_in(username, read_password(password)?, true)
+        .await?;
+      print_json(&result)?;
+
+      let challenge_id = match result {
+        LoginResult::Authorized => return Ok(()),
+        LoginResult::TwoFactorRequired { challenge_id } => challenge_id,
+        LoginResult::RateLimited { retry_after_ms } => {
+          return Err(format!(
+            "too many failed logins, try again in {}s",
+            (retry_after_ms + 999) / 1000
+          ))
+        }
+        _ => return Err("couldn't log in".into()),
+      };
+
+      let result = accounts
+        .submit_totp(challenge_id, read_password(code)?)
+        .await?;
+      print_json(&result)?;
+
+      match result {
+        SubmitTotpResult::Authorized => Ok(()),
+        _ => Err("couldn't log in".into()),
+      }
+    }
+    Command::Whoami => match account.api.my_info().await? {
+      MyInfoResult::Success(user) => print_json(&user),
+      MyInfoResult::NotLoggedIn => Err("not logged in".into()),
+    },
+    Command::Send { message } => {
+      let (ws, mut events) = connect(&accounts);
+      wait_for_connection(&mut events).await?;
+      ws.send_message(message).await
+    }
+    Command::Tail => {
+      let (_ws, mut events) = connect(&accounts);
+
+      while let Some((event, payload)) = events.recv().await {
+        println!("{}", json!({ "event": event, "payload": payload }));
+      }
+
+      Ok(())
+    }
+    Command::Ping { count } => {
+      let (_ws, mut events) = connect(&accounts);
+
+      for _ in 0..count {
+        print_json(&wait_for(&mut events, |event, _| event == "latency").await?)?;
+      }
+
+      Ok(())
+    }
+    Command::CreateUser { username, password } => {
+      let result = accounts
+        .create_user(username, read_password(password)?)
+        .await?;
+      print_json(&result)?;
+
+      match result {
+        CreateUserResult::Success(_) => Ok(()),
+        _ => Err("couldn't create user".into()),
+      }
+    }
+    Command::DeleteAccount { password } => {
+      let result = accounts.delete_account(read_password(password)?).await?;
+      print_json(&result)?;
+
+      match result {
+        DeleteAccountResult::Success => Ok(()),
+        _ => Err("couldn't delete account".into()),
+      }
+    }
+    Command::ImportBreaches { file } => {
+      let file = File::open(file).map_err(|e| e.to_string())?;
+
+      let mut hashes = Vec::new();
+      for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+          continue;
+        }
+
+        match parse_hash_line(&line) {
+          Some(x) => hashes.push(x),
+          None => return Err(format!("invalid hash on line {}", i + 1)),
+        }
+      }
+
+      let list = HashList::from_hashes(hashes);
+      BreachedPasswords::import(&config.data_dir, &list).map_err(|e| e.to_string())?;
+      print_json(&json!({ "imported": list.len() }))
+    }
+  }
+}
+
+/// Returns `password`, or reads it from the next line of stdin if it wasn't given.
+fn read_password(password: Option<String>) -> Result<Secret, String> {
+  if let Some(x) = password {
+    return Ok(x.into());
+  }
+
+  let mut line = String::new();
+  io::stdin()
+    .read_line(&mut line)
+    .map_err(|e| e.to_string())?;
+  let line = Secret::new(line);
+  Ok(line.expose().trim_end_matches(&['\r', '\n'][..]).into())
+}
+
+/// Prints `value` as a line of JSON.
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+  println!(
+    "{}",
+    serde_json::to_string(value).map_err(|e| e.to_string())?
+  );
+  Ok(())
+}
+
+/// Connects the active account, returning its WebSocket client along with the events that it
+/// emits. Without an active account, it connects without logging in.
+fn connect(accounts: &AccountManager) -> (Arc<WebSocketClient>, UnboundedReceiver<Event>) {
+  let (tx, rx) = mpsc::unbounded_channel();
+
+  let sink = move |event: &str, payload: Value| {
+    // nobody is listening anymore if this fails
+    let _ = tx.send((event.into(), payload));
+  };
+
+  let account = accounts.start_active(Arc::new(sink));
+  (account.ws.clone(), rx)
+}
+
+/// Waits for an event that matches `predicate` and returns its payload.
+async fn wait_for<P>(events: &mut UnboundedReceiver<Event>, predicate: P) -> Result<Value, String>
+where
+  P: Fn(&str, &Value) -> bool,
+{
+  while let Some((event, payload)) = events.recv().await {
+    if predicate(&event, &payload) {
+      return Ok(payload);
+    }
+  }
+
+  Err("the WebSocket client stopped".into())
+}
+
+/// Waits until the WebSocket client has connected.
+async fn wait_for_connection(events: &mut UnboundedReceiver<Event>) -> Result<(), String> {
+  wait_for(events, |event, payload| {
+    event == "notification" && payload["type"] == "connected"
+  })
+  .await
+  .map(|_| ())
+}
@@ -1,242 +1,333 @@
-use futures::SinkExt;
-use reqwest::{Client, StatusCode};
-use serde::Serialize;
-use serde_json::json;
-use tauri::State;
-use ts_rs::TS;
+use std::collections::HashMap;
 
-use crate::{
-  common::BadRequestResponseBody,
+use blop_core::{
+  accounts::{AccountActionResult, AccountInfo, AccountKey, AccountManager},
+  api::{LoginResult, MyInfoResult, VerifyTokenResult},
+  e2e::{ListDevicesResult, SafetyNumberResult, VerifyDeviceResult},
+  events::AuthStateEventPayload,
+  history::HistoryEntry,
   user::{
     auth::{
-      password::{validate_password as _validate_password, PasswordValidation},
-      set_optional_mutex_value,
-      username::{
-        user_exists as _user_exists, validate_username as _validate_username, UsernameValidation,
-      },
-      AuthenticationState,
+      password::PasswordValidation,
+      secret::Secret,
+      totp::{ConfirmTotpResult, SubmitTotpResult, TotpEnrollmentResult},
+      username::{availability::Availability, UsernameValidation},
     },
-    create_user as _create_user, AuthenticationSuccessResponse, CreateUserResult, User,
+    contacts::{ContactActionResult, FriendRequestResult, ListContactsResult},
+    profile::{Profile, ProfileUpdate, UpdateProfileResult},
+    ChangePasswordResult, ChangeUsernameResult, CreateUserResult, DeleteAccountResult, User,
   },
-  websocket::WebSocketState,
-  Config,
+  websocket::PingStats,
 };
+use tauri::State;
+use tokio::sync::watch;
+
+/// What happened to the saved session at startup. It's `None` until the session has been checked.
+pub type AuthState = watch::Receiver<Option<AuthStateEventPayload>>;
 
 #[tauri::command]
-pub fn validate_password(password: String) -> PasswordValidation {
-  _validate_password(&password)
+pub async fn validate_password(
+  accounts: State<'_, AccountManager>,
+  password: Secret,
+) -> Result<PasswordValidation, String> {
+  Ok(
+    accounts
+      .active()
+      .api
+      .validate_password(password.expose())
+      .await,
+  )
 }
 
 #[tauri::command]
-pub fn validate_username(username: String) -> UsernameValidation {
-  _validate_username(&username)
+pub fn validate_username(
+  accounts: State<'_, AccountManager>,
+  username: String,
+) -> UsernameValidation {
+  accounts.active().api.validate_username(&username)
 }
 
 #[tauri::command]
 pub async fn create_user(
-  auth_state: State<'_, AuthenticationState>,
-  config_state: State<'_, Config>,
+  accounts: State<'_, AccountManager>,
   username: String,
-  password: String,
+  password: Secret,
 ) -> Result<CreateUserResult, String> {
-  // can't log in while logged in
-  if auth_state.token.lock().await.is_logged_in() {
-    return Ok(CreateUserResult::AlreadyLoggedIn);
-  }
+  accounts.create_user(username, password).await
+}
 
-  let create_user_result = _create_user(
-    &config_state.get_api_url("/auth/create"),
-    username,
-    password,
-  )
-  .await;
-
-  match &create_user_result {
-    Ok(x) => match x {
-      CreateUserResult::Success(x) => {
-        set_optional_mutex_value(&auth_state.token, Some(x.token.clone())).await;
-      }
-      _ => (),
-    },
-    _ => (),
-  };
+#[tauri::command]
+pub async fn change_password(
+  accounts: State<'_, AccountManager>,
+  old_password: Secret,
+  new_password: Secret,
+) -> Result<ChangePasswordResult, String> {
+  accounts
+    .active()
+    .api
+    .change_password(old_password, new_password)
+    .await
+}
 
-  create_user_result
+#[tauri::command]
+pub async fn change_username(
+  accounts: State<'_, AccountManager>,
+  username: String,
+) -> Result<ChangeUsernameResult, String> {
+  accounts.active().api.change_username(username).await
+}
+
+#[tauri::command]
+pub async fn delete_account(
+  accounts: State<'_, AccountManager>,
+  password: Secret,
+) -> Result<DeleteAccountResult, String> {
+  accounts.delete_account(password).await
 }
 
 #[tauri::command]
 pub async fn user_exists(
-  config_state: State<'_, Config>,
+  accounts: State<'_, AccountManager>,
   username: String,
 ) -> Result<bool, String> {
-  // TODO: don't just Ok this
-  Ok(_user_exists(&config_state.get_api_url("/user/getid"), &username).await)
+  accounts.active().api.user_exists(&username).await
 }
 
-#[derive(Clone, TS, Serialize)]
-#[ts(export, export_to = "../src/types/auth/verify-token-result.d.ts")]
-#[serde(rename_all = "camelCase", tag = "result")]
-pub enum VerifyTokenResult {
-  NotLoggedIn,
-  Authorized,
-  Expired,
+#[tauri::command]
+pub async fn check_username(
+  accounts: State<'_, AccountManager>,
+  username: String,
+) -> Result<Availability, String> {
+  Ok(accounts.active().api.check_username(&username).await)
 }
 
 #[tauri::command]
-pub async fn verify_token(
-  auth_state: State<'_, AuthenticationState>,
-  config_state: State<'_, Config>,
-) -> Result<VerifyTokenResult, String> {
-  let maybe_token = auth_state.get_token().await;
-
-  match maybe_token {
-    None => Ok(VerifyTokenResult::NotLoggedIn),
-    Some(token) => {
-      match Client::new()
-        .get(config_state.get_api_url("/auth/verify"))
-        .bearer_auth(token)
-        .send()
-        .await
-      {
-        Err(_) => Err("couldn't verify token".into()),
-        Ok(x) => match x.status() {
-          StatusCode::OK => Ok(VerifyTokenResult::Authorized),
-          StatusCode::BAD_REQUEST => Err("malformed token".into()),
-          StatusCode::UNAUTHORIZED => {
-            AuthenticationState::logout(&auth_state).await;
-            Ok(VerifyTokenResult::Expired)
-          }
-          _ => Err("invalid server response".into()),
-        },
-      }
-    }
-  }
+pub async fn resolve_users(
+  accounts: State<'_, AccountManager>,
+  ids: Vec<String>,
+) -> Result<HashMap<String, Option<User>>, String> {
+  accounts.active().api.resolve_users(&ids).await
+}
+
+#[tauri::command]
+pub async fn list_contacts(
+  accounts: State<'_, AccountManager>,
+) -> Result<ListContactsResult, String> {
+  accounts.active().api.list_contacts().await
+}
+
+#[tauri::command]
+pub async fn send_friend_request(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<FriendRequestResult, String> {
+  accounts.active().api.send_friend_request(&user_id).await
+}
+
+#[tauri::command]
+pub async fn respond_friend_request(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+  accept: bool,
+) -> Result<ContactActionResult, String> {
+  accounts
+    .active()
+    .api
+    .respond_friend_request(&user_id, accept)
+    .await
+}
+
+#[tauri::command]
+pub async fn remove_friend(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<ContactActionResult, String> {
+  accounts.active().api.remove_friend(&user_id).await
 }
 
-#[derive(Clone, TS, Serialize)]
-#[ts(export, export_to = "../src/types/auth/login-result.d.ts")]
-#[serde(rename_all = "camelCase", tag = "result")]
-pub enum LoginResult {
-  Authorized,
-  Unauthorized,
-  UserDoesNotExist,
+#[tauri::command]
+pub async fn block_user(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<ContactActionResult, String> {
+  accounts.active().api.block_user(&user_id).await
+}
+
+#[tauri::command]
+pub async fn unblock_user(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<ContactActionResult, String> {
+  accounts.active().api.unblock_user(&user_id).await
+}
+
+#[tauri::command]
+pub async fn get_profile(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<Option<Profile>, String> {
+  accounts.active().api.get_profile(&user_id).await
+}
+
+#[tauri::command]
+pub async fn update_profile(
+  accounts: State<'_, AccountManager>,
+  update: ProfileUpdate,
+) -> Result<UpdateProfileResult, String> {
+  accounts.active().api.update_profile(update).await
+}
+
+#[tauri::command]
+pub async fn verify_token(
+  accounts: State<'_, AccountManager>,
+) -> Result<VerifyTokenResult, String> {
+  accounts.active().api.verify_token().await
 }
 
 #[tauri::command]
 pub async fn log_in(
-  auth_state: State<'_, AuthenticationState>,
-  config_state: State<'_, Config>,
+  accounts: State<'_, AccountManager>,
   username: String,
-  password: String,
+  password: Secret,
+  remember_me: bool,
 ) -> Result<LoginResult, String> {
-  let request_body = json!({
-    "username": username,
-    "password": password,
-  });
-
-  match Client::new()
-    .get(config_state.get_api_url("/auth/login"))
-    .json(&request_body)
-    .send()
-    .await
-  {
-    // we couldn't connect
-    Err(e) => Err(format!("couldn't log in! {}", e.to_string())),
-    Ok(x) => match x.status() {
-      StatusCode::OK => {
-        // parse response body
-        let body: AuthenticationSuccessResponse = match x.json().await {
-          Err(_) => return Err("invalid server response (c5d3)".into()),
-          Ok(x) => x,
-        };
-
-        // update token and ID
-        AuthenticationState::login(&auth_state, body.token, body.id).await;
-
-        Ok(LoginResult::Authorized)
-      }
-      // invalid credentials
-      StatusCode::UNAUTHORIZED => Ok(LoginResult::Unauthorized),
-      StatusCode::BAD_REQUEST => {
-        // parse the bad request response
-        // we expect a field that contains the specific type
-        let body: BadRequestResponseBody = match x.json().await {
-          Err(_) => return Err("invalid server response (1908)".into()),
-          Ok(x) => x,
-        };
-
-        // match expected types
-        match body.typ.as_str() {
-          "USER" => Ok(LoginResult::UserDoesNotExist),
-          other => Err(format!("invalid server response (7c63): {}", other)),
-        }
-      }
-      other => Err(format!(
-        "authorization error {}",
-        other.canonical_reason().unwrap()
-      )),
-    },
-  }
+  accounts.log_in(username, password, remember_me).await
 }
 
-#[derive(Serialize, TS)]
-#[ts(export, export_to = "../src/types/user/info.d.ts")]
-#[serde(tag = "type", rename_all = "camelCase")]
-pub enum MyInfoResult {
-  Success(User),
-  NotLoggedIn,
+/// Finishes a login that returned `twoFactorRequired`.
+#[tauri::command]
+pub async fn submit_totp(
+  accounts: State<'_, AccountManager>,
+  challenge_id: String,
+  code: Secret,
+) -> Result<SubmitTotpResult, String> {
+  accounts.submit_totp(challenge_id, code).await
 }
 
 #[tauri::command]
-pub async fn my_info(
-  auth_state: State<'_, AuthenticationState>,
-  config_state: State<'_, Config>,
-) -> Result<MyInfoResult, String> {
-  // TODO: figure out locks here. maybe rework/replace optionalstate
-  let token = match auth_state.get_token().await {
-    None => {
-      AuthenticationState::logout(&auth_state).await;
-      return Ok(MyInfoResult::NotLoggedIn);
-    }
-    Some(x) => x,
-  };
-
-  // TODO: don't unwrap. this is bad
-  let resp = Client::new()
-    .get(config_state.get_api_url("/user/me"))
-    .bearer_auth(token)
-    .send()
-    .await
-    .unwrap();
+pub async fn begin_totp_enrollment(
+  accounts: State<'_, AccountManager>,
+) -> Result<TotpEnrollmentResult, String> {
+  accounts.active().api.begin_totp_enrollment().await
+}
+
+#[tauri::command]
+pub async fn confirm_totp_enrollment(
+  accounts: State<'_, AccountManager>,
+  code: Secret,
+) -> Result<ConfirmTotpResult, String> {
+  accounts.active().api.confirm_totp_enrollment(code).await
+}
 
-  match resp.status() {
-    StatusCode::OK => {
-      let body: User = match resp.json().await {
-        Err(_) => return Err("invalid server response (c5d3)".into()),
-        Ok(x) => x,
-      };
+/// Gets what happened to the saved session at startup, in case the `auth_state` event was emitted
+/// before anything was listening. If the session is still being checked, this waits for it.
+#[tauri::command]
+pub async fn auth_state(state: State<'_, AuthState>) -> Result<AuthStateEventPayload, String> {
+  let mut state = state.inner().clone();
 
-      Ok(MyInfoResult::Success(body))
+  loop {
+    if let Some(x) = &*state.borrow() {
+      return Ok(x.clone());
     }
-    other => Err(format!(
-      "authorization error {}",
-      other.canonical_reason().unwrap()
-    )),
+
+    state.changed().await.map_err(|e| e.to_string())?;
   }
 }
 
 #[tauri::command]
-pub async fn send_message(state: State<'_, WebSocketState>, message: String) -> Result<(), String> {
-  let mut guard = state.write.lock().await;
+pub async fn list_accounts(
+  accounts: State<'_, AccountManager>,
+) -> Result<Vec<AccountInfo>, String> {
+  Ok(accounts.list_accounts().await)
+}
+
+#[tauri::command]
+pub fn switch_account(
+  accounts: State<'_, AccountManager>,
+  account: AccountKey,
+) -> AccountActionResult {
+  accounts.switch_account(&account)
+}
 
-  // unwrap option inside MutexGuard
-  let conn = match &mut *guard {
-    Some(x) => x,
-    None => return Err("not connected to WebSocket server".into()),
-  };
+#[tauri::command]
+pub fn set_auto_login(
+  accounts: State<'_, AccountManager>,
+  account: AccountKey,
+  enabled: bool,
+) -> AccountActionResult {
+  accounts.set_auto_login(&account, enabled)
+}
 
-  conn.feed(message.into()).await.map_err(|e| e.to_string())?;
-  conn.flush().await.map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn remove_account(
+  accounts: State<'_, AccountManager>,
+  account: AccountKey,
+) -> Result<AccountActionResult, String> {
+  Ok(accounts.remove_account(&account).await)
+}
 
-  Ok(())
+#[tauri::command]
+pub async fn my_info(accounts: State<'_, AccountManager>) -> Result<MyInfoResult, String> {
+  accounts.active().api.my_info().await
+}
+
+#[tauri::command]
+pub async fn send_message(
+  accounts: State<'_, AccountManager>,
+  message: String,
+) -> Result<(), String> {
+  accounts.active().ws.send_message(message).await
+}
+
+#[tauri::command]
+pub async fn send_direct_message(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+  message: String,
+) -> Result<(), String> {
+  accounts
+    .active()
+    .ws
+    .send_direct_message(&user_id, &message)
+    .await
+}
+
+#[tauri::command]
+pub async fn safety_number(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<SafetyNumberResult, String> {
+  accounts.active().api.safety_number(&user_id).await
+}
+
+#[tauri::command]
+pub async fn list_devices(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+) -> Result<ListDevicesResult, String> {
+  accounts.active().api.list_devices(&user_id).await
+}
+
+#[tauri::command]
+pub async fn verify_device(
+  accounts: State<'_, AccountManager>,
+  user_id: String,
+  device_id: String,
+  fingerprint: String,
+) -> Result<VerifyDeviceResult, String> {
+  accounts
+    .active()
+    .api
+    .verify_device(&user_id, &device_id, &fingerprint)
+    .await
+}
+
+#[tauri::command]
+pub async fn ping_stats(accounts: State<'_, AccountManager>) -> Result<PingStats, String> {
+  Ok(accounts.active().ws.ping_stats().await)
+}
+
+#[tauri::command]
+pub fn message_history(accounts: State<'_, AccountManager>, limit: usize) -> Vec<HistoryEntry> {
+  accounts.active().history.recent(limit)
 }
@@ -1,31 +1,42 @@
-use ts_rs::TS;
+use blop_core::events::EventSink;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
 
-/// The payload that carries the current latency to the WebSocket server.
-#[derive(Clone, serde::Serialize, TS)]
-#[ts(export, export_to = "../src/events/Latency.d.ts")]
-pub struct LatencyEventPayload {
-  pub latency: u32,
+/// The label of the window that the app opens with.
+pub const MAIN_WINDOW: &str = "main";
+
+/// Which windows of the frontend get the events.
+pub enum EventTarget {
+  All,
+  /// Only the window with the given label. Events are dropped while it's closed.
+  Window(String),
+}
+
+/// Forwards events from the core to the frontend.
+pub struct TauriEventSink {
+  app: AppHandle,
+  target: EventTarget,
 }
 
-/// The payload that carries messages from the WebSocket server.
-#[derive(Clone, serde::Serialize, TS)]
-#[ts(export, export_to = "../src/events/Message.d.ts")]
-pub struct MessageEventPayload {
-  pub message: String,
+impl TauriEventSink {
+  pub fn new(app: AppHandle, target: EventTarget) -> TauriEventSink {
+    TauriEventSink { app, target }
+  }
 }
 
-#[derive(Clone, serde::Serialize, TS)]
-#[ts(export, export_to = "../src/events/Notification.d.ts")]
-#[serde(tag = "type", rename_all = "camelCase")]
-pub enum NotificationEventPayload {
-  LostConnection,
-  Connected {
-    /// Whether or not this is the first time we've connected to the server.
-    /// If this is false, it means this is a reconnect event.
-    #[serde(rename = "firstConnection")]
-    first_connection: bool,
-  },
-  Broadcast {
-    message: String,
-  },
+impl EventSink for TauriEventSink {
+  fn emit(&self, event: &str, payload: Value) {
+    let result = match &self.target {
+      EventTarget::All => self.app.emit_all(event, payload),
+      EventTarget::Window(label) => match self.app.get_window(label) {
+        Some(window) => window.emit(event, payload),
+        None => return,
+      },
+    };
+
+    // a window that's closing can't get events anymore, which isn't worth crashing over
+    if let Err(e) = result {
+      eprintln!("couldn't emit {} event: {}", event, e);
+    }
+  }
 }
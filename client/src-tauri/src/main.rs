@@ -3,64 +3,94 @@
   windows_subsystem = "windows"
 )]
 
+use std::sync::Arc;
+
+use blop_core::{
+  accounts::AccountManager,
+  events::{emit, Event},
+  Config,
+};
 use command::{
-  create_user, log_in, my_info, send_message, user_exists, validate_password, validate_username,
-  verify_token,
+  auth_state, begin_totp_enrollment, block_user, change_password, change_username, check_username,
+  confirm_totp_enrollment, create_user, delete_account, get_profile, list_accounts, list_contacts,
+  list_devices, log_in, message_history, my_info, ping_stats, remove_account, remove_friend,
+  resolve_users, respond_friend_request, safety_number, send_direct_message, send_friend_request,
+  send_message, set_auto_login, submit_totp, switch_account, unblock_user, update_profile,
+  user_exists, validate_password, validate_username, verify_device, verify_token, AuthState,
 };
-use futures::lock::Mutex;
-use user::auth::AuthenticationState;
-use websocket::{listen, pinger, WebSocketState};
+use events::{EventTarget, TauriEventSink, MAIN_WINDOW};
+use tauri::Manager;
+use tokio::sync::watch;
 
 pub mod command;
-pub mod common;
 pub mod events;
-pub mod user;
-pub mod websocket;
-
-pub struct Config {
-  ws_url: String,
-  api_url: String,
-  ping_interval: u64,
-}
-
-impl Config {
-  /// Gets the URL for the given API endpoint.
-  pub fn get_api_url(&self, endpoint: &str) -> String {
-    format!(
-      "{}/{}",
-      self.api_url.trim_end_matches('/'),
-      endpoint.trim_start_matches('/')
-    )
-  }
-}
 
 #[tokio::main]
 async fn main() {
+  let config = Arc::new(Config::from_env());
+
   tauri::Builder::default()
-    .manage::<Config>(Config {
-      ws_url: "ws://localhost:80/ws".into(),
-      api_url: "http://localhost:80/".into(),
-      ping_interval: 5,
-    })
-    .manage::<WebSocketState>(WebSocketState {
-      write: Mutex::from(None),
-      ping: Default::default(),
-    })
-    .manage::<AuthenticationState>(AuthenticationState::default())
+    .manage::<AccountManager>(AccountManager::new(config))
     .setup(|app| {
-      tokio::spawn(listen(app.handle()));
-      tokio::spawn(pinger(app.handle()));
+      // there's only the one window, and anything that opens later shouldn't get its events
+      let target = EventTarget::Window(MAIN_WINDOW.into());
+      let events = Arc::new(TauriEventSink::new(app.handle(), target));
+
+      app.state::<AccountManager>().start(events.clone());
+
+      // the frontend waits for this to decide between the login screen and the app, but restoring
+      // the accounts and checking the session can take requests, so the window opens in the
+      // meantime
+      let (state_tx, state_rx) = watch::channel(None);
+      app.manage::<AuthState>(state_rx);
+
+      let handle = app.handle();
+      tokio::spawn(async move {
+        let accounts = handle.state::<AccountManager>();
+        accounts.restore().await;
+        let state = accounts.auto_login().await;
+
+        emit(&*events, Event::AuthState(state.clone()));
+        let _ = state_tx.send(Some(state));
+      });
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       send_message,
+      send_direct_message,
       validate_password,
       validate_username,
       create_user,
+      change_password,
+      change_username,
+      delete_account,
       user_exists,
+      check_username,
+      resolve_users,
+      list_contacts,
+      send_friend_request,
+      respond_friend_request,
+      remove_friend,
+      block_user,
+      unblock_user,
+      safety_number,
+      list_devices,
+      verify_device,
+      get_profile,
+      update_profile,
       verify_token,
       log_in,
-      my_info
+      submit_totp,
+      begin_totp_enrollment,
+      confirm_totp_enrollment,
+      auth_state,
+      list_accounts,
+      switch_account,
+      set_auto_login,
+      remove_account,
+      message_history,
+      my_info,
+      ping_stats
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
@@ -15,7 +15,7 @@ const App: Component = () => {
   listen("notification", (e) => {
     const type = e.payload.type
     if (type == "lostConnection") {
-      console.log("lost connection!", Math.random())
+      console.log("lost connection!", e.payload.reason)
     } else if (type == "connected") {
       console.log("connected! first connection:", e.payload.firstConnection)
     }
@@ -66,7 +66,7 @@ const ProfileSection: Component = () => {
       console.log(myInfoResult)
       if (myInfoResult.type === "success") {
         setAuthed(true)
-        setUsername(myInfoResult.username)
+        setUsername(myInfoResult.displayName ?? myInfoResult.username)
       }
     } else {
       setAuthed(false)
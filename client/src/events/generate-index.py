@@ -3,6 +3,12 @@ import sys
 
 HEADER_COMMENT = "// Automatically generated. Do not manually edit.\n\n"
 TAURI_IMPORTS = 'import { EventCallback, UnlistenFn } from "@tauri-apps/api/event"\n'
+# generated by the backend from every event it emits, keyed by event name
+MAP_IMPORT = 'import { EventMap } from "./map"\n'
+LISTEN_DECLARATIONS = [
+  '  export function listen<K extends keyof EventMap>(event: K, handler: EventCallback<EventMap[K]>): Promise<UnlistenFn>\n',
+  '  export function once<K extends keyof EventMap>(event: K, handler: EventCallback<EventMap[K]>): Promise<UnlistenFn>\n',
+]
 TAURI_MODULE_OPEN = 'declare module "@tauri-apps/api/event" {\n'
 TAURI_MODULE_CLOSE = '}\n'
 
@@ -19,14 +25,6 @@ def generate_export_statement(type: str) -> str:
   return f'export * from "./{type}"\n'
 
 
-def generate_import_statement(type: str) -> str:
-  return f'import {{ {type}EventPayload }} from "./{type}"\n'
-
-
-def generate_function_declaration(type: str) -> str:
-  return f'  export function listen<T>(event: "{type.lower()}", handler: EventCallback<{type}EventPayload>): Promise<UnlistenFn>\n'
-
-
 def main():
   print("Generating index.d.ts for events")
   directory = sys.argv[1]
@@ -45,12 +43,11 @@ def main():
     file.write("\n")
 
     file.write(TAURI_IMPORTS)
-    for statement in map(generate_import_statement, types):
-      file.write(statement)
+    file.write(MAP_IMPORT)
     file.write("\n")
 
     file.write(TAURI_MODULE_OPEN)
-    for func_decl in map(generate_function_declaration, types):
+    for func_decl in LISTEN_DECLARATIONS:
       file.write(func_decl)
     file.write(TAURI_MODULE_CLOSE)
 
@@ -1,5 +1,12 @@
-import { Component, createComponent, createResource, Show } from "solid-js"
-import { myInfo, verifyToken } from "./commands"
+import {
+  Component,
+  createComponent,
+  createResource,
+  onCleanup,
+  Show,
+} from "solid-js"
+import { listen } from "@tauri-apps/api/event"
+import { authState, myInfo, verifyToken } from "./commands"
 import { Without } from "./util"
 import { JSX } from "solid-js"
 import { useNavigate } from "solid-app-router"
@@ -32,6 +39,9 @@ type AuthenticationEnforcerProps<P extends AuthenticatedPageProps> = {
   redirect?: string
 }
 
+// the startup auth state is only up to date for the first check
+let checkedStartup = false
+
 /**
  * A wrapper that requires the user to be authenticated to access the given page.
  */
@@ -49,6 +59,18 @@ export function AuthenticationEnforcer<P extends AuthenticatedPageProps>({
   const [user] = createResource(
     authPath,
     async (path) => {
+      if (!checkedStartup) {
+        checkedStartup = true
+        const state = await authState()
+        if (state.state === "restored") {
+          return state.user
+        } else if (state.state !== "offline") {
+          navigate(path)
+          return null
+        }
+        // the server couldn't be reached at startup, so try again
+      }
+
       const result = await verifyToken()
       if (result.result !== "authorized") {
         // maybe emit a toast here?
@@ -67,6 +89,12 @@ export function AuthenticationEnforcer<P extends AuthenticatedPageProps>({
     { initialValue: null },
   )
 
+  // e.g. the token expired while the page was open
+  const unlisten = listen("session_changed", (e) => {
+    if (e.payload.userId === null) navigate(authPath())
+  })
+  onCleanup(() => unlisten.then((f) => f()))
+
   return (
     <Show when={user()} fallback={fallback}>
       {createComponent(page, { ...props, user: user() })}
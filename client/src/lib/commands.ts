@@ -1,10 +1,33 @@
 import { invoke } from "@tauri-apps/api"
+import { AuthStateEventPayload } from "../events/AuthState"
+import { AccountActionResult } from "../types/accounts/account-action"
+import { AccountInfo } from "../types/accounts/account-info"
+import { AccountKey } from "../types/accounts/account-key"
+import { ConfirmTotpResult } from "../types/auth/confirm-totp"
 import { LoginResult } from "../types/auth/login-result"
+import { SubmitTotpResult } from "../types/auth/submit-totp"
+import { TotpEnrollmentResult } from "../types/auth/totp-enrollment"
 import { VerifyTokenResult } from "../types/auth/verify-token-result"
+import { ListDevicesResult } from "../types/e2e/list-devices"
+import { SafetyNumberResult } from "../types/e2e/safety-number"
+import { VerifyDeviceResult } from "../types/e2e/verify-device"
+import { HistoryEntry } from "../types/history/history-entry"
+import { Availability } from "../types/user/availability"
+import { ChangePasswordResult } from "../types/user/change-password"
+import { ChangeUsernameResult } from "../types/user/change-username"
+import { ContactActionResult } from "../types/user/contact-action"
 import { CreateUserResult } from "../types/user/create-user"
+import { DeleteAccountResult } from "../types/user/delete-account"
+import { FriendRequestResult } from "../types/user/friend-request"
 import { PasswordValidation } from "../types/user/error/password-validation"
 import { UsernameValidation } from "../types/user/error/username-validation"
 import { MyInfoResult } from "../types/user/info"
+import { ListContactsResult } from "../types/user/list-contacts"
+import { Profile } from "../types/user/profile"
+import { ProfileUpdate } from "../types/user/profile-update"
+import { UpdateProfileResult } from "../types/user/update-profile"
+import { User } from "../types/user/user"
+import { PingStats } from "../types/websocket/ping-stats"
 
 /**
  * Sends a message to the WebSocket server.
@@ -15,6 +38,55 @@ export async function sendMessage(message: string): Promise<void> {
   return await invoke("send_message", { message })
 }
 
+/**
+ * Encrypts a message so that only the user with the given ID can read it, and sends it to them.
+ * @param userId the recipient's ID
+ * @param message the message to send
+ * @returns an error if they haven't published an identity key, or if the websocket server isn't
+ * connected
+ */
+export async function sendDirectMessage(
+  userId: string,
+  message: string,
+): Promise<void> {
+  return await invoke("send_direct_message", { userId, message })
+}
+
+/**
+ * Gets the safety number of the conversation with the user with the given ID. If it matches the
+ * number that they see, nobody else can read the messages between you.
+ * @param userId the user's ID
+ * @returns the result of getting the safety number
+ */
+export async function safetyNumber(userId: string): Promise<SafetyNumberResult> {
+  return await invoke("safety_number", { userId })
+}
+
+/**
+ * Lists the devices of the user with the given ID, along with their fingerprints.
+ * @param userId the user's ID, which can be our own
+ * @returns the result of listing the devices
+ */
+export async function listDevices(userId: string): Promise<ListDevicesResult> {
+  return await invoke("list_devices", { userId })
+}
+
+/**
+ * Compares the fingerprint of one of a user's devices with the one that the device itself shows.
+ * If it's one of our own devices and they match, this device vouches for it too.
+ * @param userId the user's ID
+ * @param deviceId the device's ID
+ * @param fingerprint the fingerprint that the device shows
+ * @returns the result of the comparison
+ */
+export async function verifyDevice(
+  userId: string,
+  deviceId: string,
+  fingerprint: string,
+): Promise<VerifyDeviceResult> {
+  return await invoke("verify_device", { userId, deviceId, fingerprint })
+}
+
 /**
  * Returns the validation for `password`.
  * @param password the password
@@ -50,17 +122,148 @@ export async function createUser(
   return await invoke("create_user", { username, password })
 }
 
+/**
+ * Attempts to change the logged in user's password, and logs out if it worked.
+ * @param oldPassword the current password
+ * @param newPassword the new password
+ * @returns the result of changing the password
+ */
+export async function changePassword(
+  oldPassword: string,
+  newPassword: string,
+): Promise<ChangePasswordResult> {
+  return await invoke("change_password", { oldPassword, newPassword })
+}
+
+/**
+ * Attempts to change the logged in user's username.
+ * @param username the new username
+ * @returns the result of changing the username
+ */
+export async function changeUsername(
+  username: string,
+): Promise<ChangeUsernameResult> {
+  return await invoke("change_username", { username })
+}
+
+/**
+ * Attempts to delete the logged in user's account, and logs out if it worked.
+ * @param password the user's password
+ * @returns the result of deleting the account
+ */
+export async function deleteAccount(
+  password: string,
+): Promise<DeleteAccountResult> {
+  return await invoke("delete_account", { password })
+}
+
 /**
  * Attempts to log in with the given username and password.
  * @param username the username
  * @param password the password
+ * @param rememberMe whether to stay logged in after a restart
  * @returns the result of attempting to log in
  */
 export async function login(
   username: string,
   password: string,
+  rememberMe = true,
 ): Promise<LoginResult> {
-  return await invoke("log_in", { username, password })
+  return await invoke("log_in", { username, password, rememberMe })
+}
+
+/**
+ * Finishes a login that needs a two-factor authentication code.
+ * @param challengeId the challenge ID from the login result
+ * @param code a code from an authenticator app, or one of the recovery codes
+ * @returns the result of submitting the code
+ */
+export async function submitTotp(
+  challengeId: string,
+  code: string,
+): Promise<SubmitTotpResult> {
+  return await invoke("submit_totp", { challengeId, code })
+}
+
+/**
+ * Generates a two-factor authentication secret and recovery codes for the logged in user. It
+ * isn't turned on until a code is confirmed with confirmTotpEnrollment.
+ * @returns the secret, its otpauth URI and the recovery codes
+ */
+export async function beginTotpEnrollment(): Promise<TotpEnrollmentResult> {
+  return await invoke("begin_totp_enrollment")
+}
+
+/**
+ * Turns on two-factor authentication, if the code matches the secret from beginTotpEnrollment.
+ * @param code a code from the authenticator app
+ * @returns the result of confirming it
+ */
+export async function confirmTotpEnrollment(
+  code: string,
+): Promise<ConfirmTotpResult> {
+  return await invoke("confirm_totp_enrollment", { code })
+}
+
+/**
+ * Gets whether the saved session could be used at startup. It's the same as the payload of the
+ * auth_state event, for when that was emitted before we started listening.
+ * @returns the startup auth state
+ */
+export async function authState(): Promise<AuthStateEventPayload> {
+  return await invoke("auth_state")
+}
+
+/**
+ * Lists the accounts that are logged in. Logging in adds an account and switches to it.
+ * @returns every account of the current server profile
+ */
+export async function listAccounts(): Promise<AccountInfo[]> {
+  return await invoke("list_accounts")
+}
+
+/**
+ * Makes commands and events go to another account.
+ * @param account the account to switch to
+ * @returns the result of switching
+ */
+export async function switchAccount(
+  account: AccountKey,
+): Promise<AccountActionResult> {
+  return await invoke("switch_account", { account })
+}
+
+/**
+ * Sets whether an account stays logged in after a restart.
+ * @param account the account
+ * @param enabled whether it should stay logged in
+ * @returns the result of changing it
+ */
+export async function setAutoLogin(
+  account: AccountKey,
+  enabled: boolean,
+): Promise<AccountActionResult> {
+  return await invoke("set_auto_login", { account, enabled })
+}
+
+/**
+ * Logs an account out and deletes its data from this device, including its message history.
+ * @param account the account to remove
+ * @returns the result of removing it
+ */
+export async function removeAccount(
+  account: AccountKey,
+): Promise<AccountActionResult> {
+  return await invoke("remove_account", { account })
+}
+
+/**
+ * Gets the messages that the active account received most recently.
+ * @param limit the most messages to return
+ * @returns the messages, oldest first
+ */
+export async function messageHistory(limit: number): Promise<HistoryEntry[]> {
+  return await invoke("message_history", { limit })
 }
 
 /**
@@ -81,9 +284,123 @@ export async function userExists(username: string): Promise<boolean> {
 }
 
 /**
- * Gets information about the current user if it is cached, otherwise queries the database.
- * @returns information about the user
+ * Returns whether `username` is available. This can be called on every keystroke, since checks
+ * are debounced and cached.
+ * @param username the username
+ * @returns whether the username is available, or `superseded` if a newer check was started
+ */
+export async function checkUsername(username: string): Promise<Availability> {
+  return await invoke("check_username", { username })
+}
+
+/**
+ * Resolves user IDs to users. Lookups are cached and batched, so this can be called freely.
+ * @param ids the user IDs
+ * @returns the users, keyed by ID, with null for IDs that don't belong to anyone
+ */
+export async function resolveUsers(
+  ids: string[],
+): Promise<Record<string, User | null>> {
+  return await invoke("resolve_users", { ids })
+}
+
+/**
+ * Lists the logged in user's friends, friend requests and blocked users. If the server can't be
+ * reached, the contacts that were seen last are returned, with `offline` set.
+ * @returns the result of listing contacts
+ */
+export async function listContacts(): Promise<ListContactsResult> {
+  return await invoke("list_contacts")
+}
+
+/**
+ * Sends a friend request to the user with the given ID, or accepts theirs if they sent one.
+ * @param userId the user's ID
+ * @returns the result of sending the request
+ */
+export async function sendFriendRequest(
+  userId: string,
+): Promise<FriendRequestResult> {
+  return await invoke("send_friend_request", { userId })
+}
+
+/**
+ * Accepts or declines the friend request from the user with the given ID.
+ * @param userId the user's ID
+ * @param accept whether to accept the request
+ * @returns the result of responding
+ */
+export async function respondFriendRequest(
+  userId: string,
+  accept: boolean,
+): Promise<ContactActionResult> {
+  return await invoke("respond_friend_request", { userId, accept })
+}
+
+/**
+ * Removes the user with the given ID from the logged in user's friends, or cancels a request to
+ * them.
+ * @param userId the user's ID
+ * @returns the result of removing the friend
+ */
+export async function removeFriend(
+  userId: string,
+): Promise<ContactActionResult> {
+  return await invoke("remove_friend", { userId })
+}
+
+/**
+ * Blocks the user with the given ID. Their messages, typing and presence are hidden from then on.
+ * @param userId the user's ID
+ * @returns the result of blocking the user
+ */
+export async function blockUser(userId: string): Promise<ContactActionResult> {
+  return await invoke("block_user", { userId })
+}
+
+/**
+ * Unblocks the user with the given ID.
+ * @param userId the user's ID
+ * @returns the result of unblocking the user
+ */
+export async function unblockUser(
+  userId: string,
+): Promise<ContactActionResult> {
+  return await invoke("unblock_user", { userId })
+}
+
+/**
+ * Gets the profile of the user with the given ID. Profiles are cached until they change.
+ * @param userId the user's ID
+ * @returns the profile, or null if there's no such user
+ */
+export async function getProfile(userId: string): Promise<Profile | null> {
+  return await invoke("get_profile", { userId })
+}
+
+/**
+ * Replaces the logged in user's profile. Fields that are left out are cleared.
+ * @param update the new profile
+ * @returns the result of updating the profile
+ */
+export async function updateProfile(
+  update: ProfileUpdate,
+): Promise<UpdateProfileResult> {
+  return await invoke("update_profile", { update })
+}
+
+/**
+ * Gets the logged in user's profile.
+ * @returns the profile of the logged in user
  */
 export async function myInfo(): Promise<MyInfoResult> {
   return await invoke("my_info")
 }
+
+/**
+ * Gets counters for the pongs received from the WebSocket server.
+ * @returns the ping stats
+ */
+export async function pingStats(): Promise<PingStats> {
+  return await invoke("ping_stats")
+}
@@ -4,17 +4,20 @@ import {
   createEffect,
   createSignal,
   onCleanup,
+  Show,
 } from "solid-js"
 import Button from "../components/Button"
 import Input from "../components/Input"
 
 import {
+  checkUsername,
   createUser,
   login,
-  userExists,
+  submitTotp,
   validatePassword,
   validateUsername,
 } from "../lib/commands"
+import { Availability } from "../types/user/availability"
 import { PasswordValidation } from "../types/user/error/password-validation"
 import { UsernameValidation } from "../types/user/error/username-validation"
 
@@ -58,13 +61,48 @@ const LoginSection: Component<Props> = ({ redirect }) => {
 
   const [username, setUsername] = createSignal("")
   const [password, setPassword] = createSignal("")
+  const [rememberMe, setRememberMe] = createSignal(true)
+  // set once the password was right, but a two-factor code is needed too
+  const [challengeId, setChallengeId] = createSignal<string | null>(null)
+  const [code, setCode] = createSignal("")
+  // set when too many logins failed, to when the next one is allowed
+  const [retryAt, setRetryAt] = createSignal<number | null>(null)
+  const [now, setNow] = createSignal(Date.now())
+
+  const timer = setInterval(() => setNow(Date.now()), 1000)
+  onCleanup(() => clearInterval(timer))
+
+  const secondsLeft = () => {
+    const at = retryAt()
+    return at === null ? 0 : Math.max(0, Math.ceil((at - now()) / 1000))
+  }
 
   const navigate = useNavigate()
 
   const submitHandler = async () => {
-    const result = await login(username(), password())
+    const result = await login(username(), password(), rememberMe())
     if (result.result === "authorized") {
       navigate(redirect ?? "/", { resolve: false })
+    } else if (result.result === "twoFactorRequired") {
+      setChallengeId(result.challengeId)
+    } else if (result.result === "rateLimited") {
+      batch(() => {
+        setNow(Date.now())
+        setRetryAt(Date.now() + result.retryAfterMs)
+      })
+    }
+  }
+
+  const submitCodeHandler = async () => {
+    const id = challengeId()
+    if (id === null) return
+
+    const result = await submitTotp(id, code())
+    if (result.result === "authorized") {
+      navigate(redirect ?? "/", { resolve: false })
+    } else if (result.result === "challengeExpired") {
+      // the password has to be given again
+      setChallengeId(null)
     }
   }
 
@@ -102,12 +140,39 @@ const LoginSection: Component<Props> = ({ redirect }) => {
           password
           class="mb-20px"
         />
+        <label class="mb-20px flex flex-row items-center gap-8px">
+          <input
+            type="checkbox"
+            checked={rememberMe()}
+            onChange={(e) => setRememberMe(e.currentTarget.checked)}
+          />
+          Keep me logged in
+        </label>
+        <Show when={challengeId() !== null}>
+          <Input
+            color="delta"
+            label="code"
+            placeholder="from your authenticator app, or a recovery code"
+            spellcheck={false}
+            hideValidation
+            onInput={(e) => setCode(e.currentTarget.value)}
+            onEnter={submitCodeHandler}
+            class="mb-20px"
+          />
+        </Show>
+        <Show when={secondsLeft() > 0}>
+          <p class="mb-20px">
+            Too many failed attempts. Try again in {secondsLeft()} seconds.
+          </p>
+        </Show>
         <Button
           color="gamma"
           text="Log in"
           icon={{ elt: CheckIcon }}
-          enabled={usernameValid() && passwordValid()}
-          onClick={submitHandler}
+          enabled={usernameValid() && passwordValid() && secondsLeft() === 0}
+          onClick={() =>
+            challengeId() === null ? submitHandler() : submitCodeHandler()
+          }
         />
       </div>
     </div>
@@ -122,19 +187,26 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
       result: "invalid",
       length: false,
       charset: true,
+      singleScript: true,
+      notConfusable: true,
+      notReserved: true,
+      notProfane: true,
+      canonical: "",
     })
-  const [usernameIsUnique, setUsernameIsUnique] = createSignal(true)
+  const [usernameAvailability, setUsernameAvailability] =
+    createSignal<Availability>({ result: "available" })
 
   const [passwordValidation, setPasswordValidation] =
     createSignal<PasswordValidation>({
       result: "invalid",
-      length: false,
-      alpha: false,
-      upper: false,
-      lower: false,
-      charset: true,
-      digit: false,
-      special: false,
+      rules: [],
+      strength: {
+        guesses: 1,
+        entropy: 0,
+        score: 0,
+        warning: null,
+        suggestions: [],
+      },
     })
 
   const [confirmMatches, setConfirmMatches] = createSignal(false)
@@ -163,7 +235,7 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
       usernameValidation().result === "valid" &&
       passwordValidation().result === "valid" &&
       confirmMatches() &&
-      !(await userExists(username()))
+      usernameAvailability().result === "available"
     ) {
       setEnabled(true)
     } else {
@@ -192,17 +264,21 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
           validators={[
             async (value) => {
               const valid = await validateUsername(value)
-              const unique = !(await userExists(value))
+              const availability = await checkUsername(value)
+
+              // a newer check will set everything
+              if (availability.result === "superseded") return "unknown"
 
-              console.log({ valid, unique })
+              console.log({ valid, availability })
 
               batch(() => {
                 setUsername(value)
                 setUsernameValidation(valid)
-                setUsernameIsUnique(unique)
+                setUsernameAvailability(availability)
               })
 
-              return !unique ? "invalid" : valid.result
+              if (availability.result === "unknown") return "unknown"
+              return availability.result === "taken" ? "invalid" : valid.result
             },
           ]}
           validateDelay={USER_TYPING_COOLDOWN}
@@ -210,7 +286,8 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
             invalid: {
               content: (() => {
                 const validation = usernameValidation()
-                const _usernameIsUnique = usernameIsUnique()
+                const availability = usernameAvailability()
+                const _usernameIsUnique = availability.result !== "taken"
                 if (validation.result === "valid" && _usernameIsUnique)
                   return null
 
@@ -237,6 +314,14 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
                       >
                         be between 4 and 16 characters long
                       </li>
+                      {validation.singleScript || (
+                        <li>not mix alphabets</li>
+                      )}
+                      {validation.notConfusable || (
+                        <li>not look like a Latin username</li>
+                      )}
+                      {validation.notReserved || <li>not be reserved</li>}
+                      {validation.notProfane || <li>not be offensive</li>}
                       <li class={_usernameIsUnique ? "opacity-30" : ""}>
                         be unique
                       </li>
@@ -277,30 +362,19 @@ const RegisterSection: Component<Props> = ({ redirect }) => {
                   <div>
                     Your password must:
                     <ul class="list-circle list-inside">
-                      <li
-                        class={
-                          validation.charset && password().length > 0
-                            ? "opacity-30"
-                            : ""
-                        }
-                      >
-                        be alphanumeric with symbols
-                      </li>
-                      <li class={validation.length ? "opacity-30" : ""}>
-                        be at least 8 characters
-                      </li>
-                      <li class={validation.upper ? "opacity-30" : ""}>
-                        have an uppercase letter
-                      </li>
-                      <li class={validation.lower ? "opacity-30" : ""}>
-                        have a lowercase letter
-                      </li>
-                      <li class={validation.digit ? "opacity-30" : ""}>
-                        have a digit
-                      </li>
-                      <li class={validation.special ? "opacity-30" : ""}>
-                        have a symbol
-                      </li>
+                      {validation.rules.map((rule) => (
+                        <li class={rule.passed ? "opacity-30" : ""}>
+                          {rule.message}
+                        </li>
+                      ))}
+                    </ul>
+                    {validation.strength.warning && (
+                      <p class="mt-10px">{validation.strength.warning}</p>
+                    )}
+                    <ul class="list-circle list-inside">
+                      {validation.strength.suggestions.map((suggestion) => (
+                        <li>{suggestion}</li>
+                      ))}
                     </ul>
                   </div>
                 )
@@ -10,7 +10,7 @@ import (
 )
 
 // Connects API routes.
-func SetupApiRoutes(router *gin.Engine, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+func SetupApiRoutes(router *gin.Engine, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars, connMgr *lib.ConnectionManager) {
 	router.POST("/auth/create", func(c *gin.Context) {
 		auth.CreateUserHandler(c, logger, mongo, vars)
 	})
@@ -19,10 +19,62 @@ func SetupApiRoutes(router *gin.Engine, logger *log.Logger, mongo *lib.MongoDBCo
 		user.GetUserIdHandler(c, logger, mongo)
 	})
 
+	router.POST("/user/resolve", func(c *gin.Context) {
+		user.ResolveUsersHandler(c, logger, mongo)
+	})
+
 	router.GET("/user/me", func(c *gin.Context) {
 		user.MeHandler(c, logger, mongo, vars)
 	})
 
+	router.GET("/user/profile", func(c *gin.Context) {
+		user.GetProfileHandler(c, logger, mongo)
+	})
+
+	router.POST("/user/profile", func(c *gin.Context) {
+		user.UpdateProfileHandler(c, logger, mongo, vars, connMgr)
+	})
+
+	router.GET("/user/contacts", func(c *gin.Context) {
+		user.ListContactsHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/friends/request", func(c *gin.Context) {
+		user.SendFriendRequestHandler(c, logger, mongo, vars, connMgr)
+	})
+
+	router.POST("/user/friends/respond", func(c *gin.Context) {
+		user.RespondFriendRequestHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/friends/remove", func(c *gin.Context) {
+		user.RemoveFriendHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/block", func(c *gin.Context) {
+		user.BlockUserHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/unblock", func(c *gin.Context) {
+		user.UnblockUserHandler(c, logger, mongo, vars)
+	})
+
+	router.GET("/user/keys", func(c *gin.Context) {
+		user.GetDevicesHandler(c, logger, mongo)
+	})
+
+	router.POST("/user/keys", func(c *gin.Context) {
+		user.RegisterDeviceHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/keys/sign", func(c *gin.Context) {
+		user.CrossSignDeviceHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/user/keys/remove", func(c *gin.Context) {
+		user.RemoveDeviceHandler(c, logger, mongo, vars)
+	})
+
 	router.GET("/auth/verify", func(c *gin.Context) {
 		user.VerifyHandler(c, logger, mongo, vars)
 	})
@@ -30,4 +82,26 @@ func SetupApiRoutes(router *gin.Engine, logger *log.Logger, mongo *lib.MongoDBCo
 	router.GET("/auth/login", func(c *gin.Context) {
 		auth.LoginHandler(c, logger, mongo, vars)
 	})
+
+	router.POST("/auth/login/totp", func(c *gin.Context) {
+		auth.SubmitTotpHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/auth/totp/enable", func(c *gin.Context) {
+		auth.EnableTotpHandler(c, logger, mongo, vars)
+	})
+
+	router.GET("/auth/policy", auth.PolicyHandler)
+
+	router.POST("/auth/password", func(c *gin.Context) {
+		auth.ChangePasswordHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/auth/username", func(c *gin.Context) {
+		auth.ChangeUsernameHandler(c, logger, mongo, vars)
+	})
+
+	router.POST("/auth/delete", func(c *gin.Context) {
+		auth.DeleteAccountHandler(c, logger, mongo, vars)
+	})
 }
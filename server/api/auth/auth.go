@@ -11,6 +11,7 @@ import (
 	"unicode"
 
 	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v4"
 )
 
 // Hashes a password using the SHA-256 algorithm.
@@ -28,7 +29,6 @@ type AuthenticationParams struct {
 }
 
 const USERNAME_CHARS string = "-_"
-const PASSWORD_CHARS string = "~!@#$%^&*()_+~`! @#$%^&*()_-+={[}]|\\:;\"'<,>.?/"
 
 // Returns true if the given username is valid.
 func isValidUsername(username string) bool {
@@ -37,24 +37,12 @@ func isValidUsername(username string) bool {
 		return false
 	}
 
-	for _, r := range username {
-		if !unicode.IsLetter(r) && !unicode.IsDigit(r) && !strings.ContainsRune(USERNAME_CHARS, r) {
-			return false
-		}
-	}
-
-	return true
-}
-
-// Returns true if the given password is valid.
-func isValidPassword(password string) bool {
-	passwordLen := len(password)
-	if passwordLen > 512 || passwordLen < 8 {
+	if isReservedUsername(username) {
 		return false
 	}
 
-	for _, r := range password {
-		if !unicode.IsLetter(r) && !unicode.IsDigit(r) && !strings.ContainsRune(PASSWORD_CHARS, r) {
+	for _, r := range username {
+		if !unicode.IsLetter(r) && !unicode.IsDigit(r) && !strings.ContainsRune(USERNAME_CHARS, r) {
 			return false
 		}
 	}
@@ -88,7 +76,7 @@ func CreateUserHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBCon
 	}
 
 	// validate password
-	if !isValidPassword(body.Password) {
+	if !DefaultPasswordPolicy.Validate(body.Password) {
 		c.JSON(http.StatusBadRequest, gin.H{
 			"type": "PASSWORD",
 		})
@@ -142,7 +130,20 @@ func LoginHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnecti
 	hashedPassword := hash(body.Password, vars.SALT)
 
 	if user.HashedPassword == hashedPassword {
-		// correct password
+		// correct password, but the token is only given out once a code is submitted too
+		if user.TotpSecret != "" {
+			challengeId, err := challenges.Create(user.Id)
+			if err != nil {
+				c.Status(http.StatusInternalServerError)
+				return
+			}
+
+			c.JSON(http.StatusAccepted, gin.H{
+				"challengeId": challengeId,
+			})
+			return
+		}
+
 		tokenString, err := lib.CreateSignedJWT(user.Id, []byte(vars.JWT_KEY), time.Now().Add(30*time.Second))
 		if err != nil {
 			c.JSON(http.StatusInternalServerError, gin.H{
@@ -160,3 +161,144 @@ func LoginHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnecti
 		c.Status(http.StatusUnauthorized)
 	}
 }
+
+type ChangePasswordParams struct {
+	OldPassword string `json:"oldPassword"`
+	NewPassword string `json:"newPassword"`
+}
+
+func ChangePasswordHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body ChangePasswordParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	user, err := lib.GetUserByIdUnsafe(userId, mongo)
+	if err != nil {
+		// the user was deleted after the token was signed
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	// the old password has to be given again, in case someone else has the token
+	if user.HashedPassword != hash(body.OldPassword, vars.SALT) {
+		c.Status(http.StatusForbidden)
+		return
+	}
+
+	if !DefaultPasswordPolicy.Validate(body.NewPassword) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "PASSWORD",
+		})
+		return
+	}
+
+	if err := lib.SetPassword(userId, hash(body.NewPassword, vars.SALT), mongo); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T CHANGE PASSWORD",
+		})
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+type ChangeUsernameParams struct {
+	Username string `json:"username"`
+}
+
+func ChangeUsernameHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body ChangeUsernameParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	if !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	if !isValidUsername(body.Username) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "USERNAME",
+		})
+		return
+	}
+
+	if lib.UsernameExists(body.Username, mongo) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "USERALREADYEXISTS",
+		})
+		return
+	}
+
+	if err := lib.SetUsername(userId, body.Username, mongo); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T CHANGE USERNAME",
+		})
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+type DeleteAccountParams struct {
+	Password string `json:"password"`
+}
+
+func DeleteAccountHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body DeleteAccountParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	user, err := lib.GetUserByIdUnsafe(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	if user.HashedPassword != hash(body.Password, vars.SALT) {
+		c.Status(http.StatusForbidden)
+		return
+	}
+
+	if err := lib.DeleteUser(userId, mongo); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T DELETE USER",
+		})
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
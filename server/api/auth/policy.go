@@ -0,0 +1,84 @@
+package auth
+
+import (
+	"net/http"
+	"strings"
+	"unicode"
+	"unicode/utf8"
+
+	"github.com/gin-gonic/gin"
+)
+
+// The rules that a password has to follow. Clients fetch this from /auth/policy, so the field
+// names have to match the client's PasswordPolicy.
+type PasswordPolicy struct {
+	// The minimum number of characters, or nil for no minimum.
+	MinLength      *int `json:"minLength"`
+	// The maximum number of characters, or nil for no maximum.
+	MaxLength      *int `json:"maxLength"`
+	RequireUpper   bool `json:"requireUpper"`
+	RequireLower   bool `json:"requireLower"`
+	RequireDigit   bool `json:"requireDigit"`
+	RequireSymbol  bool `json:"requireSymbol"`
+	// Only allows printable ASCII characters and spaces.
+	AsciiOnly      bool `json:"asciiOnly"`
+	// The minimum estimated strength, from 0 to 4, or nil for no minimum. Only clients enforce this,
+	// since estimating it needs their word lists.
+	MinStrength    *int `json:"minStrength"`
+	// Rejects passwords that are known to have been compromised. Only clients enforce this too,
+	// since they ship the lists.
+	RejectBreached bool `json:"rejectBreached"`
+}
+
+func intPtr(x int) *int {
+	return &x
+}
+
+// Returns true if any rune in s satisfies f.
+func containsFunc(s string, f func(rune) bool) bool {
+	return strings.IndexFunc(s, f) != -1
+}
+
+// The policy that passwords are validated against.
+var DefaultPasswordPolicy = PasswordPolicy{
+	MinLength:      intPtr(8),
+	MaxLength:      intPtr(512),
+	MinStrength:    intPtr(2),
+	RejectBreached: true,
+}
+
+// Returns true if the given password follows the policy.
+func (p PasswordPolicy) Validate(password string) bool {
+	length := utf8.RuneCountInString(password)
+	if p.MinLength != nil && length < *p.MinLength {
+		return false
+	}
+	if p.MaxLength != nil && length > *p.MaxLength {
+		return false
+	}
+
+	if p.AsciiOnly && containsFunc(password, func(r rune) bool { return r < ' ' || r > '~' }) {
+		return false
+	}
+
+	if p.RequireUpper && !containsFunc(password, unicode.IsUpper) {
+		return false
+	}
+	if p.RequireLower && !containsFunc(password, unicode.IsLower) {
+		return false
+	}
+	if p.RequireDigit && !containsFunc(password, unicode.IsNumber) {
+		return false
+	}
+	if p.RequireSymbol && !containsFunc(password, func(r rune) bool {
+		return !unicode.IsLetter(r) && !unicode.IsNumber(r) && !unicode.IsSpace(r)
+	}) {
+		return false
+	}
+
+	return true
+}
+
+func PolicyHandler(c *gin.Context) {
+	c.JSON(http.StatusOK, DefaultPasswordPolicy)
+}
@@ -0,0 +1,53 @@
+package auth
+
+import "strings"
+
+// Usernames that nobody can take. The client's default username policy has the same list, so
+// keep them in sync.
+var ReservedUsernames = []string{
+	"admin",
+	"administrator",
+	"anonymous",
+	"blop",
+	"deleted",
+	"everyone",
+	"moderator",
+	"null",
+	"official",
+	"root",
+	"security",
+	"server",
+	"staff",
+	"support",
+	"system",
+	"undefined",
+	"unknown",
+	"username",
+}
+
+// Undoes common leetspeak, and removes separators.
+var unleet = strings.NewReplacer(
+	"0", "o",
+	"1", "i",
+	"3", "e",
+	"4", "a",
+	"5", "s",
+	"7", "t",
+	"8", "b",
+	"9", "g",
+	"-", "",
+	"_", "",
+)
+
+// Returns true if the given username is reserved, ignoring case, separators and leetspeak.
+func isReservedUsername(username string) bool {
+	folded := unleet.Replace(strings.ToLower(username))
+
+	for _, x := range ReservedUsernames {
+		if folded == x {
+			return true
+		}
+	}
+
+	return false
+}
@@ -0,0 +1,156 @@
+package auth
+
+import (
+	"blop-backend/lib"
+	"log"
+	"net/http"
+	"strings"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v4"
+)
+
+// The shortest secret that's accepted, in bytes. RFC 4226 requires at least 128 bits.
+const minTotpSecretLength = 16
+
+// Logins that are waiting for a code.
+var challenges = lib.NewTotpChallenges()
+
+// Hashes a recovery code like a password, ignoring case and surrounding spaces.
+func hashRecoveryCode(code string, salt string) string {
+	return hash(strings.ToLower(strings.TrimSpace(code)), salt)
+}
+
+type SubmitTotpParams struct {
+	ChallengeId string `json:"challengeId"`
+	Code        string `json:"code"`
+}
+
+// Finishes a login that LoginHandler answered with a challenge, with a code or a recovery code.
+func SubmitTotpHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	var body SubmitTotpParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	userId, ok := challenges.Get(body.ChallengeId)
+	if !ok {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	user, err := lib.GetUserByIdUnsafe(userId, mongo)
+	if err != nil || user.TotpSecret == "" {
+		// the user was deleted, or turned two-factor authentication off, since the challenge was made
+		challenges.Remove(body.ChallengeId)
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	secret, err := lib.DecodeTotpSecret(user.TotpSecret)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	valid := lib.ValidateTotp(secret, body.Code, time.Now())
+	if !valid {
+		// recovery codes only work once
+		valid, err = lib.UseRecoveryCode(userId, hashRecoveryCode(body.Code, vars.SALT), mongo)
+		if err != nil {
+			c.Status(http.StatusInternalServerError)
+			return
+		}
+	}
+
+	if !valid {
+		challenges.Fail(body.ChallengeId)
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	challenges.Remove(body.ChallengeId)
+
+	tokenString, err := lib.CreateSignedJWT(user.Id, []byte(vars.JWT_KEY), time.Now().Add(30*time.Second))
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T SIGN JWT",
+		})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{
+		"id":    user.Id,
+		"token": tokenString,
+	})
+}
+
+type EnableTotpParams struct {
+	Secret        string   `json:"secret"`
+	Code          string   `json:"code"`
+	RecoveryCodes []string `json:"recoveryCodes"`
+}
+
+// Turns on two-factor authentication. The client generates the secret and recovery codes, and a code has to be
+// given too, to show that the secret made it into an authenticator app intact.
+func EnableTotpHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body EnableTotpParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	user, err := lib.GetUserByIdUnsafe(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	if user.TotpSecret != "" {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "ENABLED",
+		})
+		return
+	}
+
+	secret, err := lib.DecodeTotpSecret(body.Secret)
+	if err != nil || len(secret) < minTotpSecretLength {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "KEY",
+		})
+		return
+	}
+
+	if !lib.ValidateTotp(secret, body.Code, time.Now()) {
+		c.Status(http.StatusForbidden)
+		return
+	}
+
+	hashedCodes := make([]string, len(body.RecoveryCodes))
+	for i, code := range body.RecoveryCodes {
+		hashedCodes[i] = hashRecoveryCode(code, vars.SALT)
+	}
+
+	if err := lib.SetTotp(userId, body.Secret, hashedCodes, mongo); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T ENABLE TOTP",
+		})
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
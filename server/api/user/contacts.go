@@ -0,0 +1,252 @@
+package user
+
+import (
+	"blop-backend/lib"
+	"encoding/json"
+	"log"
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v4"
+	"github.com/gorilla/websocket"
+)
+
+// A user that the logged in user is connected to.
+type Contact struct {
+	Id       string `json:"id"`
+	Username string `json:"username"`
+	// Either "friend", "incoming", "outgoing" or "blocked"
+	Status string `json:"status"`
+}
+
+type ContactParams struct {
+	UserId string `json:"userId"`
+	// Whether to accept the request, when responding to one
+	Accept bool `json:"accept"`
+}
+
+// Verifies the requesting user and parses the body of a request about another user.
+// Returns the ID of the requesting user and the body, or writes a response and returns false.
+func parseContactRequest(c *gin.Context, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) (string, ContactParams, bool) {
+	var body ContactParams
+
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil || !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return "", body, false
+	}
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return "", body, false
+	}
+
+	if !lib.UserIdExists(body.UserId, mongo) {
+		c.Status(http.StatusNotFound)
+		return "", body, false
+	}
+
+	return userId, body, true
+}
+
+func ListContactsHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	contacts, err := lib.GetContacts(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	lists := map[string][]string{
+		"friend":   contacts.Friends,
+		"incoming": contacts.Incoming,
+		"outgoing": contacts.Outgoing,
+		"blocked":  contacts.Blocked,
+	}
+
+	ids := []string{}
+	for _, list := range lists {
+		ids = append(ids, list...)
+	}
+
+	users, err := lib.GetUsersByIds(ids, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	usernames := make(map[string]string)
+	for _, user := range users {
+		usernames[user.Id] = user.Username
+	}
+
+	// users that have since been deleted are left out
+	result := []Contact{}
+	for status, list := range lists {
+		for _, id := range list {
+			if username, ok := usernames[id]; ok {
+				result = append(result, Contact{id, username, status})
+			}
+		}
+	}
+
+	c.JSON(http.StatusOK, result)
+}
+
+func SendFriendRequestHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars, connMgr *lib.ConnectionManager) {
+	userId, body, ok := parseContactRequest(c, mongo, vars)
+	if !ok {
+		return
+	}
+
+	mine, err := lib.GetContacts(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+	theirs, err := lib.GetContacts(body.UserId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	if userId == body.UserId || lib.Contains(mine.Blocked, body.UserId) || lib.Contains(theirs.Blocked, userId) {
+		c.Status(http.StatusForbidden)
+		return
+	}
+
+	if lib.Contains(mine.Friends, body.UserId) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "ALREADYFRIENDS",
+		})
+		return
+	}
+
+	// they already asked, so this accepts their request
+	if lib.Contains(mine.Incoming, body.UserId) {
+		if err := lib.RespondToFriendRequest(userId, body.UserId, true, mongo); err != nil {
+			c.Status(http.StatusInternalServerError)
+			return
+		}
+
+		c.JSON(http.StatusOK, gin.H{
+			"result": "accepted",
+		})
+		return
+	}
+
+	if err := lib.AddFriendRequest(userId, body.UserId, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	if user, err := lib.GetUserById(userId, mongo); err == nil {
+		frame, _ := json.Marshal(gin.H{
+			"type":     "friend_request",
+			"userId":   userId,
+			"username": user.Username,
+		})
+		connMgr.SendToUser(body.UserId, websocket.TextMessage, frame)
+	}
+
+	c.JSON(http.StatusOK, gin.H{
+		"result": "sent",
+	})
+}
+
+func RespondFriendRequestHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, body, ok := parseContactRequest(c, mongo, vars)
+	if !ok {
+		return
+	}
+
+	mine, err := lib.GetContacts(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	if !lib.Contains(mine.Incoming, body.UserId) {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	if err := lib.RespondToFriendRequest(userId, body.UserId, body.Accept, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+func RemoveFriendHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, body, ok := parseContactRequest(c, mongo, vars)
+	if !ok {
+		return
+	}
+
+	mine, err := lib.GetContacts(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	// this also cancels a request that we sent
+	if !lib.Contains(mine.Friends, body.UserId) && !lib.Contains(mine.Outgoing, body.UserId) {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	if err := lib.RemoveFriend(userId, body.UserId, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+func BlockUserHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, body, ok := parseContactRequest(c, mongo, vars)
+	if !ok {
+		return
+	}
+
+	if err := lib.BlockUser(userId, body.UserId, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+func UnblockUserHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, body, ok := parseContactRequest(c, mongo, vars)
+	if !ok {
+		return
+	}
+
+	mine, err := lib.GetContacts(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	if !lib.Contains(mine.Blocked, body.UserId) {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	if err := lib.UnblockUser(userId, body.UserId, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
@@ -0,0 +1,219 @@
+package user
+
+import (
+	"blop-backend/lib"
+	"bytes"
+	"crypto/ed25519"
+	"encoding/base64"
+	"log"
+	"net/http"
+	"regexp"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v4"
+)
+
+// The length of X25519 and Ed25519 public keys, in bytes.
+const publicKeyLength = 32
+
+// Mixed into every signature over a device, like the client does.
+const deviceSignatureContext = "blop device v1"
+
+// Device IDs are used in document paths, so they're limited to URL-safe base64.
+var deviceIdRegex = regexp.MustCompile(`^[A-Za-z0-9_-]{1,64}$`)
+
+// Returns the key that key encodes, or nil if it isn't a base64 encoded 32 byte key.
+func decodeKey(key string) []byte {
+	decoded, err := base64.StdEncoding.DecodeString(key)
+	if err != nil || len(decoded) != publicKeyLength {
+		return nil
+	}
+	return decoded
+}
+
+// Returns what a device signs when it vouches for another of the user's devices.
+func deviceStatement(userId string, device lib.Device) []byte {
+	var statement bytes.Buffer
+	statement.WriteString(deviceSignatureContext)
+	statement.WriteString(userId + "\n" + device.DeviceId + "\n")
+	statement.Write(decodeKey(device.PublicKey))
+	statement.Write(decodeKey(device.SigningKey))
+	return statement.Bytes()
+}
+
+// Returns the device with the given ID, or nil if there isn't one.
+func findDevice(devices []lib.Device, deviceId string) *lib.Device {
+	for i := range devices {
+		if devices[i].DeviceId == deviceId {
+			return &devices[i]
+		}
+	}
+	return nil
+}
+
+func GetDevicesHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection) {
+	userId, ok := c.GetQuery("id")
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "ID",
+		})
+		return
+	}
+
+	devices, err := lib.GetDevices(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	if devices == nil {
+		devices = []lib.Device{}
+	}
+
+	c.JSON(http.StatusOK, gin.H{
+		"userId":  userId,
+		"devices": devices,
+	})
+}
+
+type RegisterDeviceParams struct {
+	DeviceId   string `json:"deviceId"`
+	PublicKey  string `json:"publicKey"`
+	SigningKey string `json:"signingKey"`
+}
+
+func RegisterDeviceHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil || !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body RegisterDeviceParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	if !deviceIdRegex.MatchString(body.DeviceId) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "ID",
+		})
+		return
+	}
+
+	// only X25519 and Ed25519 keys are accepted
+	if decodeKey(body.PublicKey) == nil || decodeKey(body.SigningKey) == nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "KEY",
+		})
+		return
+	}
+
+	device := lib.Device{
+		DeviceId:   body.DeviceId,
+		PublicKey:  body.PublicKey,
+		SigningKey: body.SigningKey,
+	}
+	if err := lib.SetDevice(userId, device, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+type CrossSignDeviceParams struct {
+	DeviceId  string `json:"deviceId"`
+	SignerId  string `json:"signerId"`
+	Signature string `json:"signature"`
+}
+
+func CrossSignDeviceHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil || !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body CrossSignDeviceParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	devices, err := lib.GetDevices(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	device := findDevice(devices, body.DeviceId)
+	signer := findDevice(devices, body.SignerId)
+	if device == nil || signer == nil || device == signer {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	// clients check signatures too, but there's no point in handing out ones that don't check out
+	signature, err := base64.StdEncoding.DecodeString(body.Signature)
+	signingKey := ed25519.PublicKey(decodeKey(signer.SigningKey))
+	if err != nil || !ed25519.Verify(signingKey, deviceStatement(userId, *device), signature) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "SIGNATURE",
+		})
+		return
+	}
+
+	if err := lib.AddDeviceSignature(userId, body.DeviceId, body.SignerId, body.Signature, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
+
+type RemoveDeviceParams struct {
+	DeviceId string `json:"deviceId"`
+}
+
+func RemoveDeviceHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil || !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body RemoveDeviceParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	devices, err := lib.GetDevices(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	if findDevice(devices, body.DeviceId) == nil {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	if err := lib.RemoveDevice(userId, body.DeviceId, mongo); err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.Status(http.StatusOK)
+}
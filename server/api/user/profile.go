@@ -0,0 +1,117 @@
+package user
+
+import (
+	"blop-backend/lib"
+	"encoding/json"
+	"log"
+	"net/http"
+	"strings"
+	"unicode/utf8"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v4"
+	"github.com/gorilla/websocket"
+)
+
+const (
+	maxDisplayNameLength = 32
+	maxBioLength         = 300
+	maxStatusLength      = 80
+	// base64 encoded, so about 192 KiB of image
+	maxAvatarBlobLength = 256 * 1024
+)
+
+func GetProfileHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection) {
+	userId, ok := c.GetQuery("id")
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "ID",
+		})
+		return
+	}
+
+	user, err := lib.GetUserById(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusNotFound)
+		return
+	}
+
+	c.JSON(http.StatusOK, user)
+}
+
+// Returns true if every field of the profile is within its limits.
+func isValidProfile(profile lib.Profile) bool {
+	if utf8.RuneCountInString(profile.DisplayName) > maxDisplayNameLength ||
+		utf8.RuneCountInString(profile.Bio) > maxBioLength ||
+		utf8.RuneCountInString(profile.Status) > maxStatusLength {
+		return false
+	}
+
+	if avatar := profile.Avatar; avatar != nil {
+		switch avatar.Type {
+		case "url":
+			if !strings.HasPrefix(avatar.Url, "https://") && !strings.HasPrefix(avatar.Url, "http://") {
+				return false
+			}
+		case "blob":
+			if !strings.HasPrefix(avatar.ContentType, "image/") || len(avatar.Data) > maxAvatarBlobLength {
+				return false
+			}
+		default:
+			return false
+		}
+	}
+
+	return true
+}
+
+func UpdateProfileHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection, vars lib.EnvironmentVars, connMgr *lib.ConnectionManager) {
+	userId, err := lib.VerifyJWTFromContext(c, []byte(vars.JWT_KEY), jwt.SigningMethodHS256)
+	if err != nil {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	var body lib.Profile
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	if !lib.UserIdExists(userId, mongo) {
+		c.Status(http.StatusUnauthorized)
+		return
+	}
+
+	if !isValidProfile(body) {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "PROFILE",
+		})
+		return
+	}
+
+	if err := lib.SetProfile(userId, body, mongo); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{
+			"message": "COULDN'T UPDATE PROFILE",
+		})
+		return
+	}
+
+	user, err := lib.GetUserById(userId, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	// let clients know that their cached copy is out of date
+	frame, _ := json.Marshal(gin.H{
+		"type":   "profile_updated",
+		"userId": userId,
+	})
+	connMgr.Broadcast(websocket.TextMessage, frame)
+
+	c.JSON(http.StatusOK, user)
+}
@@ -16,7 +16,10 @@ type GetUserIdParams struct {
 func GetUserIdHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection) {
 	var body GetUserIdParams
 
-	if err := c.BindJSON(&body); err != nil {
+	// GET requests shouldn't have a body, but old clients send the username in one
+	if username, ok := c.GetQuery("username"); ok {
+		body.Username = username
+	} else if err := c.BindJSON(&body); err != nil {
 		c.JSON(http.StatusBadRequest, gin.H{
 			"type": "JSON",
 		})
@@ -34,6 +37,40 @@ func GetUserIdHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConn
 	}
 }
 
+// The most IDs that can be resolved in one request.
+const maxResolveIds = 100
+
+type ResolveUsersParams struct {
+	Ids []string `json:"ids"`
+}
+
+func ResolveUsersHandler(c *gin.Context, logger *log.Logger, mongo *lib.MongoDBConnection) {
+	var body ResolveUsersParams
+
+	if err := c.BindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "JSON",
+		})
+		return
+	}
+
+	if len(body.Ids) > maxResolveIds {
+		c.JSON(http.StatusBadRequest, gin.H{
+			"type": "TOOMANY",
+		})
+		return
+	}
+
+	// users that don't exist are left out
+	users, err := lib.GetUsersByIds(body.Ids, mongo)
+	if err != nil {
+		c.Status(http.StatusInternalServerError)
+		return
+	}
+
+	c.JSON(http.StatusOK, users)
+}
+
 type GetUserHandlerParams struct {
 	Username string `json:"username"`
 }
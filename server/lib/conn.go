@@ -3,6 +3,7 @@ package lib
 import (
 	"log"
 	"sync"
+	"time"
 
 	"github.com/google/uuid"
 	"github.com/gorilla/websocket"
@@ -21,6 +22,7 @@ type ConnectionManager struct {
 // A wrapper for a WebSocket connection.
 type Connection struct {
 	WebSocket      *websocket.Conn // Underlying websocket connection
+	UserId         string          // ID of the logged in user, or empty if the connection is anonymous
 	pingHandleLock sync.Mutex      // Mutex
 }
 
@@ -39,14 +41,15 @@ func (mgr *ConnectionManager) ForEachParallel(fn func(uuid.UUID, *Connection)) {
 }
 
 // Registers a new connection (concurrency safe). Returns the UUID of the connection, as well as (a copy of) the connection itself.
-func (mgr *ConnectionManager) Register(conn *websocket.Conn) uuid.UUID {
+// userId is empty if the connection isn't logged in.
+func (mgr *ConnectionManager) Register(conn *websocket.Conn, userId string) uuid.UUID {
 	// acquire lock
 	mgr.Lock()
 	defer mgr.Unlock()
 
 	id := uuid.New()
 
-	wrapper := Connection{conn, sync.Mutex{}}
+	wrapper := Connection{conn, userId, sync.Mutex{}}
 	wrapperPointer := &wrapper
 	mgr.connections[id] = wrapperPointer // neat that this works in Go
 
@@ -71,3 +74,38 @@ func (mgr *ConnectionManager) Get(id uuid.UUID) *Connection {
 
 	return mgr.connections[id]
 }
+
+// Sends a message of type t to a connection, closing it if it can't be written to in time.
+func (mgr *ConnectionManager) send(id uuid.UUID, conn *Connection, t int, msg []byte) {
+	conn.WebSocket.SetWriteDeadline(time.Now().Add(time.Second * 2))
+	err := conn.WebSocket.WriteMessage(t, msg)
+	if err != nil {
+		mgr.logger.Printf("write timeout with WS%v\n", id)
+		conn.WebSocket.Close()
+	}
+}
+
+// Concurrently sends a message of type t to every connection (concurrency safe).
+// Connections that can't be written to in time are closed.
+func (mgr *ConnectionManager) Broadcast(t int, msg []byte) {
+	mgr.RLock()
+	defer mgr.RUnlock()
+
+	mgr.logger.Printf("broadcasting to %v connections\n", len(mgr.connections))
+	for id, eachConn := range mgr.connections {
+		mgr.logger.Printf("broadcast to WS%v\n", id)
+		go mgr.send(id, eachConn, t, msg)
+	}
+}
+
+// Concurrently sends a message of type t to every connection of the user with the given ID (concurrency safe).
+func (mgr *ConnectionManager) SendToUser(userId string, t int, msg []byte) {
+	mgr.RLock()
+	defer mgr.RUnlock()
+
+	for id, eachConn := range mgr.connections {
+		if eachConn.UserId == userId {
+			go mgr.send(id, eachConn, t, msg)
+		}
+	}
+}
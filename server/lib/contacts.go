@@ -0,0 +1,118 @@
+package lib
+
+import (
+	"context"
+
+	"go.mongodb.org/mongo-driver/bson"
+	"go.mongodb.org/mongo-driver/mongo/options"
+)
+
+// The other users that a user is connected to, by ID.
+// These are stored on the user's document.
+type Contacts struct {
+	Friends []string `bson:"friends"`
+	// Users that sent this user a friend request
+	Incoming []string `bson:"incoming"`
+	// Users that this user sent a friend request to
+	Outgoing []string `bson:"outgoing"`
+	// Users that this user blocked
+	Blocked []string `bson:"blocked"`
+}
+
+// Returns true if list contains id.
+func Contains(list []string, id string) bool {
+	for _, x := range list {
+		if x == id {
+			return true
+		}
+	}
+	return false
+}
+
+// Finds the contacts of the user with the given ID.
+func GetContacts(userId string, mongo *MongoDBConnection) (Contacts, error) {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	projection := bson.D{
+		{Key: "friends", Value: 1},
+		{Key: "incoming", Value: 1},
+		{Key: "outgoing", Value: 1},
+		{Key: "blocked", Value: 1},
+	}
+	filter := bson.M{
+		"_id": userId,
+	}
+	opts := options.FindOne().SetProjection(projection)
+
+	var contacts Contacts
+	err := users.FindOne(context.TODO(), filter, opts).Decode(&contacts)
+
+	return contacts, err
+}
+
+// Applies update to the contacts of the user with the given ID.
+func updateContacts(userId string, update bson.M, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
+
+// Records a friend request from one user to another.
+func AddFriendRequest(fromId string, toId string, mongo *MongoDBConnection) error {
+	err := updateContacts(fromId, bson.M{"$addToSet": bson.M{"outgoing": toId}}, mongo)
+	if err != nil {
+		return err
+	}
+
+	return updateContacts(toId, bson.M{"$addToSet": bson.M{"incoming": fromId}}, mongo)
+}
+
+// Removes the friend request that fromId sent to userId, making them friends if accept is true.
+func RespondToFriendRequest(userId string, fromId string, accept bool, mongo *MongoDBConnection) error {
+	mine := bson.M{"$pull": bson.M{"incoming": fromId}}
+	theirs := bson.M{"$pull": bson.M{"outgoing": userId}}
+
+	if accept {
+		mine["$addToSet"] = bson.M{"friends": fromId}
+		theirs["$addToSet"] = bson.M{"friends": userId}
+	}
+
+	if err := updateContacts(userId, mine, mongo); err != nil {
+		return err
+	}
+
+	return updateContacts(fromId, theirs, mongo)
+}
+
+// Ends the friendship between two users, or cancels the request that userId sent to otherId.
+func RemoveFriend(userId string, otherId string, mongo *MongoDBConnection) error {
+	err := updateContacts(userId, bson.M{"$pull": bson.M{"friends": otherId, "outgoing": otherId}}, mongo)
+	if err != nil {
+		return err
+	}
+
+	return updateContacts(otherId, bson.M{"$pull": bson.M{"friends": userId, "incoming": userId}}, mongo)
+}
+
+// Blocks otherId for userId, which also ends any friendship or request between them.
+func BlockUser(userId string, otherId string, mongo *MongoDBConnection) error {
+	err := updateContacts(userId, bson.M{
+		"$pull":     bson.M{"friends": otherId, "incoming": otherId, "outgoing": otherId},
+		"$addToSet": bson.M{"blocked": otherId},
+	}, mongo)
+	if err != nil {
+		return err
+	}
+
+	return updateContacts(otherId, bson.M{"$pull": bson.M{"friends": userId, "incoming": userId, "outgoing": userId}}, mongo)
+}
+
+// Unblocks otherId for userId.
+func UnblockUser(userId string, otherId string, mongo *MongoDBConnection) error {
+	return updateContacts(userId, bson.M{"$pull": bson.M{"blocked": otherId}}, mongo)
+}
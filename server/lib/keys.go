@@ -0,0 +1,94 @@
+package lib
+
+import (
+	"context"
+
+	"go.mongodb.org/mongo-driver/bson"
+	"go.mongodb.org/mongo-driver/mongo/options"
+)
+
+// The public keys of one of a user's devices, in base64.
+// These are stored on the user's document, and are only ever given to other clients, which use them to encrypt direct messages.
+type Device struct {
+	DeviceId string `bson:"deviceId" json:"deviceId"`
+	// The X25519 key that messages to this device are sealed with
+	PublicKey string `bson:"publicKey" json:"publicKey"`
+	// The Ed25519 key that this device signs the user's other devices with
+	SigningKey string `bson:"signingKey" json:"signingKey"`
+	// Signatures over this device, keyed by the ID of the device that made them
+	Signatures map[string]string `bson:"signatures" json:"signatures"`
+}
+
+// Finds the devices of the user with the given ID.
+func GetDevices(userId string, mongo *MongoDBConnection) ([]Device, error) {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	projection := bson.D{{Key: "devices", Value: 1}}
+	filter := bson.M{
+		"_id": userId,
+	}
+	opts := options.FindOne().SetProjection(projection)
+
+	var result struct {
+		Devices []Device `bson:"devices"`
+	}
+	err := users.FindOne(context.TODO(), filter, opts).Decode(&result)
+
+	return result.Devices, err
+}
+
+// Adds a device to the user with the given ID, replacing the device with the same ID if they have one.
+// New keys need new signatures, so any that the old device had are dropped.
+func SetDevice(userId string, device Device, mongo *MongoDBConnection) error {
+	if err := RemoveDevice(userId, device.DeviceId, mongo); err != nil {
+		return err
+	}
+
+	device.Signatures = map[string]string{}
+	return updateDevices(userId, bson.M{
+		"$push": bson.M{"devices": device},
+	}, mongo)
+}
+
+// Records the signature that the device with the ID signerId made over another of the user's devices.
+// The signature has to be checked before this is called.
+func AddDeviceSignature(userId string, deviceId string, signerId string, signature string, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id":              userId,
+		"devices.deviceId": deviceId,
+	}
+	update := bson.M{
+		"$set": bson.M{"devices.$.signatures." + signerId: signature},
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
+
+// Removes one of the devices of the user with the given ID, along with the signatures that it made.
+func RemoveDevice(userId string, deviceId string, mongo *MongoDBConnection) error {
+	err := updateDevices(userId, bson.M{
+		"$pull": bson.M{"devices": bson.M{"deviceId": deviceId}},
+	}, mongo)
+	if err != nil {
+		return err
+	}
+
+	return updateDevices(userId, bson.M{
+		"$unset": bson.M{"devices.$[].signatures." + deviceId: ""},
+	}, mongo)
+}
+
+// Applies update to the devices of the user with the given ID.
+func updateDevices(userId string, update bson.M, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
@@ -0,0 +1,184 @@
+package lib
+
+import (
+	"context"
+	"crypto/hmac"
+	"crypto/rand"
+	"crypto/sha1"
+	"crypto/subtle"
+	"encoding/base32"
+	"encoding/binary"
+	"encoding/hex"
+	"fmt"
+	"strings"
+	"sync"
+	"time"
+
+	"go.mongodb.org/mongo-driver/bson"
+)
+
+// How many seconds every code is valid for.
+const totpStep = 30
+
+// How many steps a code can be early or late by, like the client allows.
+const totpWindow = 1
+
+// How long a login has to be finished with a code.
+const totpChallengeLifetime = 5 * time.Minute
+
+// How many wrong codes a challenge takes before the password has to be given again.
+const totpMaxAttempts = 5
+
+// Returns the 6 digit HOTP code (RFC 4226) for the given counter.
+func hotp(secret []byte, counter uint64) string {
+	message := make([]byte, 8)
+	binary.BigEndian.PutUint64(message, counter)
+
+	mac := hmac.New(sha1.New, secret)
+	mac.Write(message)
+	sum := mac.Sum(nil)
+
+	offset := sum[len(sum)-1] & 0xf
+	value := binary.BigEndian.Uint32(sum[offset:offset+4]) & 0x7fffffff
+	return fmt.Sprintf("%06d", value%1000000)
+}
+
+// Returns true if code is the TOTP code (RFC 6238) for now, or for a step next to it.
+func ValidateTotp(secret []byte, code string, now time.Time) bool {
+	code = strings.TrimSpace(code)
+	counter := uint64(now.Unix()) / totpStep
+
+	// every step is checked, so that the time it takes doesn't say which one matched
+	valid := 0
+	for i := counter - totpWindow; i <= counter+totpWindow; i++ {
+		valid |= subtle.ConstantTimeCompare([]byte(hotp(secret, i)), []byte(code))
+	}
+	return valid == 1
+}
+
+// Decodes a base32 secret, like the ones that authenticator apps take.
+func DecodeTotpSecret(secret string) ([]byte, error) {
+	secret = strings.ToUpper(strings.ReplaceAll(secret, " ", ""))
+	return base32.StdEncoding.WithPadding(base32.NoPadding).DecodeString(strings.TrimRight(secret, "="))
+}
+
+// Turns on two-factor authentication for the user with the given ID.
+func SetTotp(userId string, secret string, hashedRecoveryCodes []string, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+	update := bson.M{
+		"$set": bson.M{
+			"totpSecret":    secret,
+			"recoveryCodes": hashedRecoveryCodes,
+		},
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
+
+// Removes one of the recovery codes of the user with the given ID, so that it can't be used again.
+// Returns true if they had it.
+func UseRecoveryCode(userId string, hashedCode string, mongo *MongoDBConnection) (bool, error) {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id":           userId,
+		"recoveryCodes": hashedCode,
+	}
+	update := bson.M{
+		"$pull": bson.M{"recoveryCodes": hashedCode},
+	}
+
+	result, err := users.UpdateOne(context.TODO(), filter, update)
+	if err != nil {
+		return false, err
+	}
+	return result.ModifiedCount == 1, nil
+}
+
+// A login that was given the right password, and that's waiting for a code.
+type totpChallenge struct {
+	userId   string
+	expires  time.Time
+	attempts int
+}
+
+// Logins that are waiting for a code, keyed by challenge ID.
+// They're only kept in memory, so restarting the server means logging in again.
+type TotpChallenges struct {
+	mutex      sync.Mutex
+	challenges map[string]*totpChallenge
+}
+
+func NewTotpChallenges() *TotpChallenges {
+	return &TotpChallenges{
+		challenges: map[string]*totpChallenge{},
+	}
+}
+
+// Starts a login for the user with the given ID, and returns the challenge ID that finishes it.
+func (c *TotpChallenges) Create(userId string) (string, error) {
+	// the challenge ID stands in for the password, so it can't be guessable
+	bytes := make([]byte, 16)
+	if _, err := rand.Read(bytes); err != nil {
+		return "", err
+	}
+	id := hex.EncodeToString(bytes)
+
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	// expired challenges are only cleaned up here, so that they don't pile up
+	now := time.Now()
+	for key, challenge := range c.challenges {
+		if now.After(challenge.expires) {
+			delete(c.challenges, key)
+		}
+	}
+
+	c.challenges[id] = &totpChallenge{
+		userId:  userId,
+		expires: now.Add(totpChallengeLifetime),
+	}
+	return id, nil
+}
+
+// Returns the ID of the user that the challenge belongs to, or false if it doesn't exist or has expired.
+func (c *TotpChallenges) Get(id string) (string, bool) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	challenge, ok := c.challenges[id]
+	if !ok || time.Now().After(challenge.expires) {
+		return "", false
+	}
+	return challenge.userId, true
+}
+
+// Records a wrong code, and drops the challenge if it had too many.
+func (c *TotpChallenges) Fail(id string) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	challenge, ok := c.challenges[id]
+	if !ok {
+		return
+	}
+
+	challenge.attempts++
+	if challenge.attempts >= totpMaxAttempts {
+		delete(c.challenges, id)
+	}
+}
+
+// Drops a challenge, e.g. because the login was finished.
+func (c *TotpChallenges) Remove(id string) {
+	c.mutex.Lock()
+	defer c.mutex.Unlock()
+
+	delete(c.challenges, id)
+}
@@ -3,6 +3,7 @@ package lib
 import (
 	"context"
 	"fmt"
+	"time"
 
 	"github.com/rs/xid"
 	"go.mongodb.org/mongo-driver/bson"
@@ -48,6 +49,7 @@ func CreateUser(username string, hashedPassword string, mongo *MongoDBConnection
 		{Key: "_id", Value: id.String()},
 		{Key: "username", Value: username},
 		{Key: "password", Value: hashedPassword},
+		{Key: "createdAt", Value: time.Now().UnixMilli()},
 	})
 	fmt.Println("inserted")
 
@@ -58,12 +60,36 @@ func CreateUser(username string, hashedPassword string, mongo *MongoDBConnection
 	return id.String(), nil
 }
 
+// A profile picture, either hosted somewhere else or uploaded.
+type Avatar struct {
+	// Either "url" or "blob"
+	Type string `bson:"type" json:"type"`
+	// The URL of the image, if Type is "url"
+	Url string `bson:"url,omitempty" json:"url,omitempty"`
+	// The MIME type of the image, if Type is "blob"
+	ContentType string `bson:"contentType,omitempty" json:"contentType,omitempty"`
+	// The base64 encoded image, if Type is "blob"
+	Data string `bson:"data,omitempty" json:"data,omitempty"`
+}
+
+// The parts of a user's profile that they can change.
+type Profile struct {
+	DisplayName string  `bson:"displayName,omitempty" json:"displayName,omitempty"`
+	Avatar      *Avatar `bson:"avatar,omitempty" json:"avatar,omitempty"`
+	Bio         string  `bson:"bio,omitempty" json:"bio,omitempty"`
+	Status      string  `bson:"status,omitempty" json:"status,omitempty"`
+}
+
 // User information that is safe to give to clients.
 type User struct {
 	// The user ID
 	Id string `bson:"_id" json:"id"`
 	// A unique, user-chosen, human-readable identifier
 	Username string `bson:"username" json:"username"`
+	// The user's profile
+	Profile `bson:",inline"`
+	// When the user was created, in milliseconds since the Unix epoch
+	CreatedAt int64 `bson:"createdAt" json:"createdAt"`
 }
 
 // User that contains information that is NOT safe to give to clients.
@@ -74,8 +100,16 @@ type UnsafeUser struct {
 	Id string `bson:"_id" json:"id"`
 	// A unique, user-chosen, human-readable identifier
 	Username string `bson:"username" json:"username"`
+	// The user's profile
+	Profile `bson:",inline"`
+	// When the user was created, in milliseconds since the Unix epoch
+	CreatedAt int64 `bson:"createdAt" json:"createdAt"`
 	// A hashed version of the user's password
 	HashedPassword string `bson:"password"` // json is purposefully omitted (we should never have to serialize HashedPassword to json)
+	// The base32 TOTP secret, if the user turned on two-factor authentication
+	TotpSecret string `bson:"totpSecret,omitempty"`
+	// Hashed versions of the recovery codes that haven't been used yet
+	RecoveryCodes []string `bson:"recoveryCodes,omitempty"`
 }
 
 // Finds the user with the given ID.
@@ -95,6 +129,28 @@ func GetUserById(userId string, mongo *MongoDBConnection) (User, error) {
 	return user, err
 }
 
+// Finds the users with the given IDs, leaving out any that don't exist.
+// Only IDs and usernames are included.
+func GetUsersByIds(userIds []string, mongo *MongoDBConnection) ([]User, error) {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	projection := bson.D{{Key: "_id", Value: 1}, {Key: "username", Value: 1}}
+	filter := bson.M{
+		"_id": bson.M{"$in": userIds},
+	}
+	opts := options.Find().SetProjection(projection)
+
+	cursor, err := users.Find(context.TODO(), filter, opts)
+	if err != nil {
+		return nil, err
+	}
+
+	found := []User{}
+	err = cursor.All(context.TODO(), &found)
+
+	return found, err
+}
+
 // Finds the user with the given username.
 // This does not include sensitive information like hashed passwords.
 func GetUserByUsername(username string, mongo *MongoDBConnection) (User, error) {
@@ -143,3 +199,80 @@ func GetUserByUsernameUnsafe(username string, mongo *MongoDBConnection) (UnsafeU
 
 	return user, err
 }
+
+// Finds the user with the given ID.
+// Like GetUserByUsernameUnsafe, this includes the hashed password.
+func GetUserByIdUnsafe(userId string, mongo *MongoDBConnection) (UnsafeUser, error) {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+
+	var user UnsafeUser
+	err := users.FindOne(context.TODO(), filter).Decode(&user)
+
+	return user, err
+}
+
+// Replaces the hashed password of the user with the given ID.
+func SetPassword(userId string, hashedPassword string, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+	update := bson.M{
+		"$set": bson.M{"password": hashedPassword},
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
+
+// Replaces the username of the user with the given ID.
+func SetUsername(userId string, username string, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+	update := bson.M{
+		"$set": bson.M{"username": username},
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
+
+// Deletes the user with the given ID.
+func DeleteUser(userId string, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+
+	_, err := users.DeleteOne(context.TODO(), filter)
+	return err
+}
+
+// Replaces the profile of the user with the given ID. Empty fields are cleared.
+func SetProfile(userId string, profile Profile, mongo *MongoDBConnection) error {
+	users := mongo.Database().Collection(USERS_COLLECTION)
+
+	filter := bson.M{
+		"_id": userId,
+	}
+	update := bson.M{
+		"$set": bson.M{
+			"displayName": profile.DisplayName,
+			"avatar":      profile.Avatar,
+			"bio":         profile.Bio,
+			"status":      profile.Status,
+		},
+	}
+
+	_, err := users.UpdateOne(context.TODO(), filter, update)
+	return err
+}
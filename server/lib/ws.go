@@ -1,11 +1,14 @@
 package lib
 
 import (
+	"encoding/json"
 	"fmt"
 	"math/rand"
 	"net/http"
+	"strings"
 	"time"
 
+	"github.com/golang-jwt/jwt/v4"
 	"github.com/google/uuid"
 	"github.com/gorilla/websocket"
 )
@@ -38,20 +41,72 @@ func pingHandler(mgr *ConnectionManager, id uuid.UUID, appData string) error {
 	time.Sleep(time.Millisecond * time.Duration(rand.Uint32()%20+20))
 
 	// handle the ping
-	conn.WebSocket.WriteControl(websocket.PongMessage, []byte(appData), time.Now().Add(time.Millisecond*500))
+	// the server time is appended so that clients can estimate their clock offset
+	pongData := fmt.Sprintf("%v:%v", appData, time.Now().UnixMilli())
+	conn.WebSocket.WriteControl(websocket.PongMessage, []byte(pongData), time.Now().Add(time.Millisecond*500))
 	return nil
 }
 
-func WebSocketHandler(w http.ResponseWriter, r *http.Request, mgr *ConnectionManager) {
+// A direct message from a client. The envelope is encrypted by the client, so it's passed along as is.
+type directMessage struct {
+	Type     string          `json:"type"`
+	To       string          `json:"to"`
+	Envelope json.RawMessage `json:"envelope"`
+	SentAt   int64           `json:"sentAt"`
+}
+
+// A chat message from a client, stamped with when it was sent according to the client's estimate of the server's clock.
+type chatMessage struct {
+	Message string `json:"message"`
+	SentAt  int64  `json:"sentAt"`
+}
+
+// Returns the ID of the user that the request's Authorization header belongs to, or an empty string if it doesn't have a valid one.
+func authenticateWebSocket(r *http.Request, key []byte) string {
+	segments := strings.SplitN(r.Header.Get("Authorization"), " ", 2)
+	if len(segments) != 2 || segments[0] != "Bearer" {
+		return ""
+	}
+
+	userId, err := VerifyJWT(segments[1], key, jwt.SigningMethodHS256)
+	if err != nil || userId == "invalid" || userId == "expired" {
+		return ""
+	}
+
+	return userId
+}
+
+// Tells every connection that the user with the given ID came online or went offline.
+func broadcastPresence(mgr *ConnectionManager, userId string, online bool) {
+	frame, _ := json.Marshal(map[string]interface{}{
+		"type":   "presence",
+		"userId": userId,
+		"online": online,
+	})
+	mgr.Broadcast(websocket.TextMessage, frame)
+}
+
+// Handles a WebSocket connection. Connections that send a valid token are associated with its user, so that messages can be sent to them, and so that others can see who sent theirs.
+func WebSocketHandler(w http.ResponseWriter, r *http.Request, mgr *ConnectionManager, key []byte) {
+	userId := authenticateWebSocket(r, key)
+
 	ws, err := wsupgrader.Upgrade(w, r, nil)
 	if err != nil {
 		mgr.logger.Printf("failed to set websocket upgrade: %+v\n", err)
 		return
 	}
 
-	id := mgr.Register(ws)
+	id := mgr.Register(ws, userId)
+	if userId != "" {
+		// deferred first so that it runs after the connection is removed
+		defer broadcastPresence(mgr, userId, false)
+	}
 	defer mgr.Remove(id)
 
+	if userId != "" {
+		broadcastPresence(mgr, userId, true)
+	}
+
 	ws.SetPingHandler(func(appData string) error {
 		go pingHandler(mgr, id, appData)
 		return nil
@@ -74,25 +129,45 @@ func WebSocketHandler(w http.ResponseWriter, r *http.Request, mgr *ConnectionMan
 			return
 		}
 
-		mgr.logger.Printf("%v message from WS-%v: '%v'\n", t, id, string(msg[:]))
-
-		outMsg := []byte(fmt.Sprintf("MESSAGE[%v]: %v", len(msg), string(msg)))
-
-		// broadcast
-		mgr.logger.Printf("broadcasting to %v connections\n", len(mgr.connections))
-		mgr.RLock()
-		for id, eachConn := range mgr.connections {
-			go func(id uuid.UUID, eachConn *Connection) {
-				mgr.logger.Printf("broadcast to WS%v\n", id)
-				eachConn.WebSocket.SetWriteDeadline(time.Now().Add(time.Second * 2))
-				err := eachConn.WebSocket.WriteMessage(t, outMsg)
-				if err != nil {
-					mgr.logger.Printf("write timeout with WS%v\n", id)
-					eachConn.WebSocket.Close()
-				}
-			}(id, eachConn)
+		// message bodies aren't logged, since they might be private
+		mgr.logger.Printf("%v message from WS-%v (%v bytes)\n", t, id, len(msg))
+
+		// direct messages only go to their recipient
+		var dm directMessage
+		if userId != "" && json.Unmarshal(msg, &dm) == nil && dm.Type == "dm" {
+			outMsg, _ := json.Marshal(map[string]interface{}{
+				"type":     "dm",
+				"userId":   userId,
+				"envelope": dm.Envelope,
+				"sentAt":   dm.SentAt,
+			})
+			mgr.SendToUser(dm.To, websocket.TextMessage, outMsg)
+			continue
 		}
-		mgr.RUnlock()
+
+		// chat messages say when they were sent, so that clients can order them; plain ones are stamped with our clock
+		text := string(msg)
+		sentAt := time.Now().UnixMilli()
+		var chat chatMessage
+		if json.Unmarshal(msg, &chat) == nil && chat.SentAt != 0 {
+			text = chat.Message
+			sentAt = chat.SentAt
+		}
+
+		var outMsg []byte
+		if userId != "" {
+			// clients need to know who sent it, e.g. to hide messages from users that they blocked
+			outMsg, _ = json.Marshal(map[string]interface{}{
+				"type":    "message",
+				"userId":  userId,
+				"message": text,
+				"sentAt":  sentAt,
+			})
+		} else {
+			outMsg = []byte(fmt.Sprintf("MESSAGE[%v]: %v", len(text), text))
+		}
+
+		mgr.Broadcast(t, outMsg)
 		// conn.WriteMessage(t, outMsg)
 	}
 }
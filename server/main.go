@@ -42,11 +42,11 @@ func main() {
 
 	router.GET("/ws", func(c *gin.Context) {
 		logger.Printf("incoming websocket connection from %v\n", c.Request.RemoteAddr)
-		lib.WebSocketHandler(c.Writer, c.Request, &connMgr)
+		lib.WebSocketHandler(c.Writer, c.Request, &connMgr, []byte(vars.JWT_KEY))
 		logger.Printf("closed\n")
 	})
 
-	api.SetupApiRoutes(router, logger, mongo, vars)
+	api.SetupApiRoutes(router, logger, mongo, vars, &connMgr)
 
 	// needs to be 0.0.0.0 to be able to connect
 	// localhost does NOT work